@@ -0,0 +1,139 @@
+use actix_service::{Service, Transform};
+use actix_web::dev::{Body, MessageBody, ResponseBody, ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue};
+use actix_web::http::StatusCode;
+use actix_web::{Error, HttpMessage, HttpResponse};
+use futures::future::{ok, Ready};
+use futures::Future;
+use serde_json::json;
+use std::cell::RefCell;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use super::{ApiKeys, API_KEY_HEADER};
+use crate::rate_limit::{RateLimitStatus, RateLimiter};
+
+/// Middleware that checks the `X-Api-Key` header and rate limits requests.
+///
+/// Requests with a valid key are limited per key and the key is added to the
+/// request extensions for the handlers. Requests without a key are limited per
+/// IP address. 401 is returned for an invalid or revoked key and 429 when the
+/// limit is reached. Every response has the `RateLimit-*` headers
+pub struct ApiKeyAuth {
+    api_keys: Arc<ApiKeys>,
+    rate_limiter: Arc<RateLimiter>,
+}
+
+impl ApiKeyAuth {
+    pub fn new(api_keys: Arc<ApiKeys>, rate_limiter: Arc<RateLimiter>) -> Self {
+        Self { api_keys, rate_limiter }
+    }
+}
+
+impl<S, B> Transform<S> for ApiKeyAuth
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + Unpin + 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<Body>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = ApiKeyAuthMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(ApiKeyAuthMiddleware {
+            service: Rc::new(RefCell::new(service)),
+            api_keys: self.api_keys.clone(),
+            rate_limiter: self.rate_limiter.clone(),
+        })
+    }
+}
+
+pub struct ApiKeyAuthMiddleware<S> {
+    service: Rc<RefCell<S>>,
+    api_keys: Arc<ApiKeys>,
+    rate_limiter: Arc<RateLimiter>,
+}
+
+impl<S, B> Service for ApiKeyAuthMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + Unpin + 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<Body>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.borrow_mut().poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        let api_key = req.headers()
+            .get(API_KEY_HEADER)
+            .map(|value| value.to_str().map_err(|_err| ()).and_then(|key| {
+                self.api_keys.authenticate(key.trim()).map_err(|_err| ())
+            }));
+
+        // Bad keys are limited by IP so they cannot be guessed without limit
+        let ip = req.peer_addr().map(|addr| addr.ip().to_string()).unwrap_or_default();
+        let status = match &api_key {
+            Some(Ok(api_key)) => self.rate_limiter.check(Some(&api_key.id.to_string()), &ip),
+            _ => self.rate_limiter.check(None, &ip),
+        };
+
+        if !status.allowed {
+            let mut res = req.into_response(reject(StatusCode::TOO_MANY_REQUESTS, "Too many requests"));
+            add_rate_limit_headers(res.headers_mut(), &status);
+            let retry_after = HeaderValue::from(seconds(&status));
+            res.headers_mut().insert(HeaderName::from_static("retry-after"), retry_after);
+            return Box::pin(ok(res));
+        }
+
+        match api_key {
+            Some(Ok(api_key)) => {
+                req.extensions_mut().insert(api_key);
+            },
+            Some(Err(())) => {
+                let mut res = req.into_response(reject(StatusCode::UNAUTHORIZED, "API key is invalid or revoked"));
+                add_rate_limit_headers(res.headers_mut(), &status);
+                return Box::pin(ok(res));
+            },
+            None => {},
+        }
+
+        let fut = self.service.borrow_mut().call(req);
+        Box::pin(async move {
+            let mut res = fut.await?;
+            add_rate_limit_headers(res.headers_mut(), &status);
+
+            Ok(res.map_body(|_, body| ResponseBody::Other(Body::from_message(body))))
+        })
+    }
+}
+
+/// Errors are in the same shape as GraphQL errors so clients can handle them the same way
+fn reject(status: StatusCode, message: &str) -> HttpResponse {
+    HttpResponse::build(status).json(json!({
+        "errors": [{ "message": message }],
+    }))
+}
+
+/// Seconds until the limit resets, rounded up so clients do not retry too early
+fn seconds(status: &RateLimitStatus) -> u64 {
+    let reset_after = status.reset_after;
+    reset_after.as_secs() + if reset_after.subsec_nanos() > 0 { 1 } else { 0 }
+}
+
+fn add_rate_limit_headers(headers: &mut HeaderMap, status: &RateLimitStatus) {
+    headers.insert(HeaderName::from_static("ratelimit-limit"), HeaderValue::from(status.limit));
+    headers.insert(HeaderName::from_static("ratelimit-remaining"), HeaderValue::from(status.remaining));
+    headers.insert(HeaderName::from_static("ratelimit-reset"), HeaderValue::from(seconds(status)));
+}
@@ -0,0 +1,129 @@
+use juniper::GraphQLEnum;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::time::SystemTime;
+use uuid::Uuid;
+
+pub mod middleware;
+pub mod store;
+
+use self::store::{ApiKeyStore, StoredApiKey};
+
+/// The header partners send their API key in
+pub const API_KEY_HEADER: &str = "X-Api-Key";
+
+/// Prefix of every key so they are easy to spot, e.g. in leaked logs
+const KEY_PREFIX: &str = "ffk_";
+
+/// What an API key can read. API keys cannot make changes
+#[derive(Clone, Copy, Debug, Eq, GraphQLEnum, PartialEq)]
+pub enum ApiKeyScope {
+    /// Players and teams
+    ReadPlayers,
+    ReadProjections,
+    ReadRankings,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ApiKey {
+    pub id: Uuid,
+    /// Who the key was issued to, e.g. the name of the partner
+    pub name: String,
+    pub scopes: Vec<ApiKeyScope>,
+    /// The user who issued the key
+    pub created_by: Uuid,
+    pub created_at: SystemTime,
+    pub revoked: bool,
+}
+
+impl ApiKey {
+    pub fn has_scope(&self, scope: ApiKeyScope) -> bool {
+        self.scopes.contains(&scope)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ApiKeyError {
+    NameRequired,
+    ScopesRequired,
+    InvalidKey,
+    NotFound,
+}
+
+impl fmt::Display for ApiKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiKeyError::NameRequired => write!(f, "API key name is required"),
+            ApiKeyError::ScopesRequired => write!(f, "API key must have at least one scope"),
+            ApiKeyError::InvalidKey => write!(f, "API key is invalid or revoked"),
+            ApiKeyError::NotFound => write!(f, "API key not found"),
+        }
+    }
+}
+
+/// Issues, revokes and checks API keys. Only a hash of the key is stored so
+/// the key is only ever shown when it is issued
+pub struct ApiKeys {
+    store: Box<dyn ApiKeyStore>,
+}
+
+impl ApiKeys {
+    pub fn new(store: Box<dyn ApiKeyStore>) -> Self {
+        Self { store }
+    }
+
+    /// Creates a new key. Returns the key along with the secret to hand to the partner
+    pub fn issue(&self, name: &str, scopes: Vec<ApiKeyScope>, created_by: Uuid) -> Result<(ApiKey, String), ApiKeyError> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(ApiKeyError::NameRequired);
+        }
+        if scopes.is_empty() {
+            return Err(ApiKeyError::ScopesRequired);
+        }
+
+        let secret = format!("{}{}", KEY_PREFIX, to_hex(&rand::thread_rng().gen::<[u8; 32]>()));
+        let api_key = ApiKey {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            scopes,
+            created_by,
+            created_at: SystemTime::now(),
+            revoked: false,
+        };
+        self.store.insert(StoredApiKey {
+            api_key: api_key.clone(),
+            key_hash: hash_key(&secret),
+        });
+
+        Ok((api_key, secret))
+    }
+
+    /// Revokes a key so it can no longer be used
+    pub fn revoke(&self, id: Uuid) -> Result<ApiKey, ApiKeyError> {
+        self.store.revoke(id).ok_or(ApiKeyError::NotFound)
+    }
+
+    pub fn list(&self) -> Vec<ApiKey> {
+        self.store.list()
+    }
+
+    /// Gets the key the secret belongs to if it has not been revoked
+    pub fn authenticate(&self, secret: &str) -> Result<ApiKey, ApiKeyError> {
+        self.store
+            .find_by_hash(&hash_key(secret))
+            .filter(|api_key| !api_key.revoked)
+            .ok_or(ApiKeyError::InvalidKey)
+    }
+}
+
+/// The keys are random so a fast hash is enough to keep them from being read
+/// out of the store
+fn hash_key(secret: &str) -> String {
+    to_hex(&Sha256::digest(secret.as_bytes()))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,62 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+use uuid::Uuid;
+
+use super::ApiKey;
+
+#[derive(Clone, Debug)]
+pub struct StoredApiKey {
+    pub api_key: ApiKey,
+    /// Hex encoded SHA-256 of the key
+    pub key_hash: String,
+}
+
+/// Storage for API keys
+pub trait ApiKeyStore: Send + Sync {
+    fn insert(&self, api_key: StoredApiKey);
+    fn find_by_hash(&self, key_hash: &str) -> Option<ApiKey>;
+    fn list(&self) -> Vec<ApiKey>;
+    /// Marks the key as revoked. Returns the updated key or None when the key does not exist
+    fn revoke(&self, id: Uuid) -> Option<ApiKey>;
+}
+
+/// Keeps API keys in memory. Keys are lost when the gateway restarts
+#[derive(Default)]
+pub struct InMemoryApiKeyStore {
+    api_keys: RwLock<HashMap<Uuid, StoredApiKey>>,
+}
+
+impl ApiKeyStore for InMemoryApiKeyStore {
+    fn insert(&self, api_key: StoredApiKey) {
+        self.api_keys.write().unwrap().insert(api_key.api_key.id, api_key);
+    }
+
+    fn find_by_hash(&self, key_hash: &str) -> Option<ApiKey> {
+        self.api_keys
+            .read()
+            .unwrap()
+            .values()
+            .find(|stored| stored.key_hash == key_hash)
+            .map(|stored| stored.api_key.clone())
+    }
+
+    fn list(&self) -> Vec<ApiKey> {
+        let mut api_keys: Vec<_> = self.api_keys
+            .read()
+            .unwrap()
+            .values()
+            .map(|stored| stored.api_key.clone())
+            .collect();
+        api_keys.sort_by_key(|api_key| api_key.created_at);
+
+        api_keys
+    }
+
+    fn revoke(&self, id: Uuid) -> Option<ApiKey> {
+        let mut api_keys = self.api_keys.write().unwrap();
+        let stored = api_keys.get_mut(&id)?;
+        stored.api_key.revoked = true;
+
+        Some(stored.api_key.clone())
+    }
+}
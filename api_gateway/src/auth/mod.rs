@@ -0,0 +1,326 @@
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use juniper::GraphQLEnum;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+pub mod store;
+
+use self::store::{RefreshToken, User, UserStore};
+
+const MIN_PASSWORD_LENGTH: usize = 8;
+
+/// How long the service tokens sent to the other services last
+const SERVICE_TOKEN_TTL: Duration = Duration::from_secs(60);
+
+/// Checked instead of a password hash when no user has the email. Made with
+/// the same `argon2::Config::default()` as the real hashes so it takes as long
+const DUMMY_PASSWORD_HASH: &str = "$argon2i$v=19$m=4096,t=3,p=1$cGxheWVycyBkdW1teSBzYWx0$xKHfhIGXhf2ANEyhk54J4NoEJjS+e2WceYWjL2+CuOM";
+
+/// What a user is allowed to do
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Permission {
+    /// Create, update and delete players, teams, projections and rankings
+    ManagePlayers,
+    /// Change the role of users
+    ManageUsers,
+    /// Change the settings of a league
+    ManageLeagueSettings,
+    /// Issue and revoke API keys for partners
+    ManageApiKeys,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, GraphQLEnum, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Commissioner,
+    Admin,
+}
+
+impl Role {
+    pub fn permissions(self) -> &'static [Permission] {
+        match self {
+            Role::User => &[],
+            Role::Commissioner => &[Permission::ManageLeagueSettings],
+            Role::Admin => &[
+                Permission::ManagePlayers,
+                Permission::ManageUsers,
+                Permission::ManageLeagueSettings,
+                Permission::ManageApiKeys,
+            ],
+        }
+    }
+
+    pub fn can(self, permission: Permission) -> bool {
+        self.permissions().contains(&permission)
+    }
+}
+
+#[derive(Debug)]
+pub struct AuthConfig {
+    /// Secret used to sign the access tokens
+    pub jwt_secret: String,
+    pub access_token_ttl: Duration,
+    pub refresh_token_ttl: Duration,
+}
+
+impl AuthConfig {
+    /// Access tokens last 15 minutes and refresh tokens last 30 days
+    pub fn new(jwt_secret: String) -> Self {
+        Self {
+            jwt_secret,
+            access_token_ttl: Duration::from_secs(15 * 60),
+            refresh_token_ttl: Duration::from_secs(30 * 24 * 60 * 60),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AuthError {
+    InvalidEmail,
+    PasswordTooShort,
+    EmailTaken,
+    InvalidCredentials,
+    InvalidToken,
+    UserNotFound,
+    Internal(String),
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::InvalidEmail => write!(f, "Email is not valid"),
+            AuthError::PasswordTooShort => {
+                write!(f, "Password must be at least {} characters", MIN_PASSWORD_LENGTH)
+            },
+            AuthError::EmailTaken => write!(f, "Email is already taken"),
+            AuthError::InvalidCredentials => write!(f, "Invalid email or password"),
+            AuthError::InvalidToken => write!(f, "Token is invalid or expired"),
+            AuthError::UserNotFound => write!(f, "User not found"),
+            AuthError::Internal(_) => write!(f, "Something went wrong"),
+        }
+    }
+}
+
+/// The claims of the access token
+#[derive(Debug, Deserialize, Serialize)]
+struct Claims {
+    sub: Uuid,
+    email: String,
+    role: Role,
+    iat: u64,
+    exp: u64,
+}
+
+/// The claims of the service token. The other services check the role again
+/// so they cannot be called directly to get around the gateway
+#[derive(Debug, Deserialize, Serialize)]
+struct ServiceClaims {
+    sub: Uuid,
+    role: Role,
+    exp: u64,
+}
+
+/// The user making the request, taken from the access token
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuthUser {
+    pub id: Uuid,
+    pub email: String,
+    pub role: Role,
+}
+
+#[derive(Debug)]
+pub struct Tokens {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub user: AuthUser,
+}
+
+/// Signs users up, logs them in and verifies their access tokens
+pub struct Auth {
+    config: AuthConfig,
+    store: Box<dyn UserStore>,
+}
+
+impl Auth {
+    pub fn new(config: AuthConfig, store: Box<dyn UserStore>) -> Self {
+        Self { config, store }
+    }
+
+    /// Signs up a user. Everyone who signs up is a `Role::User`, other roles
+    /// are given by an admin with `set_role`
+    pub fn signup(&self, email: &str, password: &str) -> Result<Tokens, AuthError> {
+        let user = self.insert_user(email, password, Role::User)?;
+
+        self.issue_tokens(&user)
+    }
+
+    /// Creates a user with the role without logging them in. Only for accounts
+    /// set up by whoever runs the gateway, i.e. the first admin at startup
+    pub fn create_user(&self, email: &str, password: &str, role: Role) -> Result<AuthUser, AuthError> {
+        let user = self.insert_user(email, password, role)?;
+
+        Ok(AuthUser {
+            id: user.id,
+            email: user.email,
+            role: user.role,
+        })
+    }
+
+    pub fn login(&self, email: &str, password: &str) -> Result<Tokens, AuthError> {
+        let user = self.store.find_user_by_email(&normalize_email(email));
+
+        // The password is checked even when there is no user so the response
+        // takes as long and does not tell which emails have an account
+        let password_hash = user.as_ref().map_or(DUMMY_PASSWORD_HASH, |user| user.password_hash.as_str());
+        let is_valid = argon2::verify_encoded(password_hash, password.as_bytes())
+            .map_err(|err| AuthError::Internal(err.to_string()))?;
+
+        match user {
+            Some(user) if is_valid => self.issue_tokens(&user),
+            _ => Err(AuthError::InvalidCredentials),
+        }
+    }
+
+    /// Exchanges a refresh token for new tokens. The refresh token is rotated
+    /// so it cannot be used again
+    pub fn refresh(&self, refresh_token: &str) -> Result<Tokens, AuthError> {
+        let refresh_token = self.store
+            .take_refresh_token(refresh_token)
+            .filter(|token| token.expires_at > SystemTime::now())
+            .ok_or(AuthError::InvalidToken)?;
+
+        let user = self.store
+            .find_user(refresh_token.user_id)
+            .ok_or(AuthError::InvalidToken)?;
+
+        self.issue_tokens(&user)
+    }
+
+    /// Verifies the access token and gets the user it was issued to
+    pub fn authenticate(&self, access_token: &str) -> Result<AuthUser, AuthError> {
+        self.authenticate_until(access_token).map(|(user, _expires_at)| user)
+    }
+
+    /// Verifies the access token and gets the user it was issued to and when
+    /// the token expires, for connections that outlive a request
+    pub fn authenticate_until(&self, access_token: &str) -> Result<(AuthUser, SystemTime), AuthError> {
+        let claims = decode::<Claims>(
+            access_token,
+            &DecodingKey::from_secret(self.config.jwt_secret.as_bytes()),
+            &Validation::default(),
+        )
+            .map_err(|_err| AuthError::InvalidToken)?
+            .claims;
+
+        let user = AuthUser {
+            id: claims.sub,
+            email: claims.email,
+            role: claims.role,
+        };
+        Ok((user, UNIX_EPOCH + Duration::from_secs(claims.exp)))
+    }
+
+    /// Changes the role of a user. The new role is in the access tokens issued
+    /// from the next login or refresh
+    pub fn set_role(&self, user_id: Uuid, role: Role) -> Result<AuthUser, AuthError> {
+        let user = self.store
+            .update_user_role(user_id, role)
+            .ok_or(AuthError::UserNotFound)?;
+
+        Ok(AuthUser {
+            id: user.id,
+            email: user.email,
+            role: user.role,
+        })
+    }
+
+    fn insert_user(&self, email: &str, password: &str, role: Role) -> Result<User, AuthError> {
+        let email = normalize_email(email);
+        if !email.contains('@') {
+            return Err(AuthError::InvalidEmail);
+        }
+        if password.chars().count() < MIN_PASSWORD_LENGTH {
+            return Err(AuthError::PasswordTooShort);
+        }
+
+        let salt = rand::thread_rng().gen::<[u8; 16]>();
+        let password_hash = argon2::hash_encoded(password.as_bytes(), &salt, &argon2::Config::default())
+            .map_err(|err| AuthError::Internal(err.to_string()))?;
+
+        let user = User {
+            id: Uuid::new_v4(),
+            email,
+            password_hash,
+            role,
+        };
+        if !self.store.insert_user(user.clone()) {
+            return Err(AuthError::EmailTaken);
+        }
+
+        Ok(user)
+    }
+
+    fn issue_tokens(&self, user: &User) -> Result<Tokens, AuthError> {
+        let now = SystemTime::now();
+        let issued_at = now.duration_since(UNIX_EPOCH)
+            .map_err(|err| AuthError::Internal(err.to_string()))?;
+
+        let claims = Claims {
+            sub: user.id,
+            email: user.email.clone(),
+            role: user.role,
+            iat: issued_at.as_secs(),
+            exp: (issued_at + self.config.access_token_ttl).as_secs(),
+        };
+        let access_token = encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(self.config.jwt_secret.as_bytes()),
+        )
+            .map_err(|err| AuthError::Internal(err.to_string()))?;
+
+        let refresh_token = rand::thread_rng()
+            .gen::<[u8; 32]>()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>();
+        self.store.insert_refresh_token(refresh_token.clone(), RefreshToken {
+            user_id: user.id,
+            expires_at: now + self.config.refresh_token_ttl,
+        });
+
+        Ok(Tokens {
+            access_token,
+            refresh_token,
+            user: AuthUser {
+                id: user.id,
+                email: user.email.clone(),
+                role: user.role,
+            },
+        })
+    }
+}
+
+/// Creates the short lived token the gateway sends to the other services to
+/// say which user it is making the request for
+pub fn service_token(secret: &str, user: &AuthUser) -> Result<String, AuthError> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)
+        .map_err(|err| AuthError::Internal(err.to_string()))?;
+
+    let claims = ServiceClaims {
+        sub: user.id,
+        role: user.role,
+        exp: (now + SERVICE_TOKEN_TTL).as_secs(),
+    };
+
+    encode(&Header::default(), &claims, &EncodingKey::from_secret(secret.as_bytes()))
+        .map_err(|err| AuthError::Internal(err.to_string()))
+}
+
+fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
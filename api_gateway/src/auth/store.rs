@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::SystemTime;
+use uuid::Uuid;
+
+use super::Role;
+
+#[derive(Clone, Debug)]
+pub struct User {
+    pub id: Uuid,
+    pub email: String,
+    /// The argon2 encoded hash of the password
+    pub password_hash: String,
+    pub role: Role,
+}
+
+#[derive(Clone, Debug)]
+pub struct RefreshToken {
+    pub user_id: Uuid,
+    pub expires_at: SystemTime,
+}
+
+/// Storage for users and their refresh tokens
+pub trait UserStore: Send + Sync {
+    /// Saves a new user. Returns false when the email is already taken
+    fn insert_user(&self, user: User) -> bool;
+    fn find_user(&self, id: Uuid) -> Option<User>;
+    fn find_user_by_email(&self, email: &str) -> Option<User>;
+    /// Changes the role of a user. Returns the updated user or None when the user does not exist
+    fn update_user_role(&self, id: Uuid, role: Role) -> Option<User>;
+    fn insert_refresh_token(&self, token: String, refresh_token: RefreshToken);
+    /// Removes the refresh token and returns it, so a refresh token can only be used once
+    fn take_refresh_token(&self, token: &str) -> Option<RefreshToken>;
+}
+
+/// Keeps users in memory, for the tests and running the gateway locally. Users
+/// and refresh tokens are lost when the gateway restarts and are not shared
+/// between replicas, so a deployment needs a `UserStore` backed by a database
+#[derive(Default)]
+pub struct InMemoryUserStore {
+    users: RwLock<HashMap<Uuid, User>>,
+    refresh_tokens: RwLock<HashMap<String, RefreshToken>>,
+}
+
+impl UserStore for InMemoryUserStore {
+    fn insert_user(&self, user: User) -> bool {
+        let mut users = self.users.write().unwrap();
+        if users.values().any(|u| u.email == user.email) {
+            return false;
+        }
+
+        users.insert(user.id, user);
+        true
+    }
+
+    fn find_user(&self, id: Uuid) -> Option<User> {
+        self.users.read().unwrap().get(&id).cloned()
+    }
+
+    fn find_user_by_email(&self, email: &str) -> Option<User> {
+        self.users.read().unwrap().values().find(|u| u.email == email).cloned()
+    }
+
+    fn update_user_role(&self, id: Uuid, role: Role) -> Option<User> {
+        let mut users = self.users.write().unwrap();
+        let user = users.get_mut(&id)?;
+        user.role = role;
+
+        Some(user.clone())
+    }
+
+    /// Expired tokens can no longer be used, so they are dropped here
+    fn insert_refresh_token(&self, token: String, refresh_token: RefreshToken) {
+        let now = SystemTime::now();
+        let mut refresh_tokens = self.refresh_tokens.write().unwrap();
+        refresh_tokens.retain(|_, refresh_token| refresh_token.expires_at > now);
+        refresh_tokens.insert(token, refresh_token);
+    }
+
+    fn take_refresh_token(&self, token: &str) -> Option<RefreshToken> {
+        self.refresh_tokens.write().unwrap().remove(token)
+    }
+}
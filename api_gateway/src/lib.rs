@@ -3,16 +3,38 @@ use mockito;
 
 use std::io;
 use std::sync::Arc;
+use std::time::Duration;
 
 use actix_cors::Cors;
-use actix_web::{middleware, web, App, Error, HttpResponse, HttpServer};
+use actix_web::{middleware, web, App, Error, HttpRequest, HttpResponse, HttpServer};
 use dotenv::dotenv;
 use juniper::http::playground::playground_source;
 use juniper::http::GraphQLRequest;
+use juniper::InputValue;
+use serde::Deserialize;
+use serde_json::json;
 
+pub mod api_keys;
+pub mod auth;
+pub mod persisted_queries;
+pub mod query_limits;
+pub mod rate_limit;
 pub mod schema;
+pub mod signing;
+pub mod subscriptions;
 
+use crate::api_keys::middleware::ApiKeyAuth;
+use crate::api_keys::store::InMemoryApiKeyStore;
+use crate::api_keys::{ApiKey, ApiKeys};
+use crate::auth::store::InMemoryUserStore;
+use crate::auth::{Auth, AuthConfig, AuthError, Role};
+use crate::persisted_queries::{PersistedQueries, PersistedQuery, PersistedQueryError};
+use crate::query_limits::QueryLimits;
+use crate::rate_limit::store::InMemoryRateLimitStore;
+use crate::rate_limit::{RateLimit, RateLimiter};
 use crate::schema::{create_schema, Schema};
+use crate::signing::SigningKey;
+use crate::subscriptions::Subscriptions;
 
 async fn playground() -> HttpResponse {
     let html = playground_source("http://localhost:4000/graphql");
@@ -22,48 +44,160 @@ async fn playground() -> HttpResponse {
 }
 
 #[cfg(not(feature = "test"))]
-fn get_config() -> schema::Config {
+pub fn get_config() -> schema::Config {
     let players_api_host = std::env::var("PLAYERS_API_URL").expect("PLAYERS_API_URL must be set");
+    let service_token_secret = std::env::var("SERVICE_TOKEN_SECRET").expect("SERVICE_TOKEN_SECRET must be set");
+    let signing_key = SigningKey {
+        id: std::env::var("REQUEST_SIGNING_KEY_ID").expect("REQUEST_SIGNING_KEY_ID must be set"),
+        secret: std::env::var("REQUEST_SIGNING_SECRET").expect("REQUEST_SIGNING_SECRET must be set"),
+    };
 
-    schema::Config { players_api_host }
+    schema::Config { players_api_host, service_token_secret, signing_key }
 }
 
 #[cfg(feature = "test")]
-fn get_config() -> schema::Config {
+pub fn get_config() -> schema::Config {
     let players_api_host = mockito::server_url();
+    let service_token_secret = "test service secret".to_string();
+    let signing_key = SigningKey {
+        id: "test".to_string(),
+        secret: "test signing secret".to_string(),
+    };
 
-    schema::Config { players_api_host }
+    schema::Config { players_api_host, service_token_secret, signing_key }
+}
+
+/// Gets the token from the `Authorization: Bearer <token>` header
+pub(crate) fn bearer_token(req: &HttpRequest) -> Option<&str> {
+    req.headers()
+        .get("Authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| {
+            if value.starts_with("Bearer ") {
+                Some(value["Bearer ".len()..].trim())
+            } else {
+                None
+            }
+        })
+}
+
+/// The body of a GraphQL request. juniper's `GraphQLRequest` does not expose
+/// the query so the body is read into this first to check the query
+#[derive(Deserialize)]
+struct GraphQLBody {
+    /// Can be left out when the query is sent as a persisted query
+    query: Option<String>,
+    #[serde(rename = "operationName")]
+    operation_name: Option<String>,
+    variables: Option<InputValue>,
+    extensions: Option<GraphQLExtensions>,
+}
+
+#[derive(Deserialize)]
+struct GraphQLExtensions {
+    #[serde(rename = "persistedQuery")]
+    persisted_query: Option<PersistedQuery>,
 }
 
 async fn graphql(
+    req: HttpRequest,
     st: web::Data<Arc<Schema>>,
-    data: web::Json<GraphQLRequest>,
+    auth: web::Data<Arc<Auth>>,
+    api_keys: web::Data<Arc<ApiKeys>>,
+    query_limits: web::Data<QueryLimits>,
+    persisted_queries: web::Data<Arc<PersistedQueries>>,
+    body: web::Json<GraphQLBody>,
 ) -> Result<HttpResponse, Error> {
+    let body = body.into_inner();
+
+    let persisted_query = body.extensions.as_ref().and_then(|extensions| extensions.persisted_query.as_ref());
+    let query = match persisted_queries.resolve(body.query, persisted_query) {
+        Ok(query) => query,
+        Err(err) => {
+            // Clients send the query after a not found or not supported error
+            // so those are not bad requests
+            let mut response = match err {
+                PersistedQueryError::NotFound | PersistedQueryError::NotSupported => HttpResponse::Ok(),
+                _ => HttpResponse::BadRequest(),
+            };
+            return Ok(response.json(json!({ "errors": [err.to_json()] })));
+        },
+    };
+
+    // Reject queries that are too deep or expensive before any of it is executed
+    if let Err(errors) = query_limits.check(&st, &query, body.operation_name.as_deref()) {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "errors": errors.iter().map(|err| err.to_json()).collect::<Vec<_>>(),
+        })));
+    }
+    // Kept to register once it has executed
+    let query_to_register = persisted_query.map(|_| query.clone());
+    let data = GraphQLRequest::new(query, body.operation_name, body.variables);
+
+    // The API key was checked by the ApiKeyAuth middleware. Requests made with
+    // an API key are not made for a user
+    let api_key = req.extensions().get::<ApiKey>().cloned();
+
+    // A request without a token is anonymous but a bad token is rejected so
+    // clients know to refresh it
+    let token = bearer_token(&req).filter(|_token| api_key.is_none());
+    let user = match token.map(|token| auth.authenticate(token)) {
+        Some(Ok(user)) => Some(user),
+        Some(Err(err)) => {
+            return Ok(HttpResponse::Unauthorized().json(json!({
+                "errors": [{ "message": err.to_string() }],
+            })));
+        },
+        None => None,
+    };
 
     let config = get_config();
-    let ctx = schema::Context::new(config);
+    let ctx = schema::Context::new(config, auth.get_ref().clone(), api_keys.get_ref().clone(), user, api_key);
 
-    let result = web::block(move || {
-        let res = data.execute(&st, &ctx);
-        Ok::<_, serde_json::error::Error>(serde_json::to_string(&res)?)
+    let (result, has_errors) = web::block(move || {
+        let res = serde_json::to_value(&data.execute(&st, &ctx))?;
+        let has_errors = res.get("errors").is_some();
+        Ok::<_, serde_json::error::Error>((serde_json::to_string(&res)?, has_errors))
     })
     .await?;
+
+    if let Some(query) = query_to_register.filter(|_| !has_errors) {
+        persisted_queries.register(&query);
+    }
     Ok(HttpResponse::Ok()
         .content_type("application/json")
         .body(result))
 }
 
-pub fn register(schema: Arc<schema::Schema>) -> impl Fn(&mut web::ServiceConfig) {
+pub fn register(
+    schema: Arc<schema::Schema>,
+    auth: Arc<Auth>,
+    api_keys: Arc<ApiKeys>,
+    rate_limiter: Arc<RateLimiter>,
+    query_limits: QueryLimits,
+    persisted_queries: Arc<PersistedQueries>,
+    subscriptions: Arc<Subscriptions>,
+) -> impl Fn(&mut web::ServiceConfig) {
     move |config: &mut web::ServiceConfig| {
         config
             .data(schema.clone())
-            .service(web::resource("/graphql").route(web::post().to(graphql)))
-            .service(web::resource("/playground").route(web::get().to(playground)));
+            .data(auth.clone())
+            .data(api_keys.clone())
+            .data(query_limits)
+            .data(persisted_queries.clone())
+            .data(subscriptions.clone())
+            .service(
+                web::scope("")
+                .wrap(ApiKeyAuth::new(api_keys.clone(), rate_limiter.clone()))
+                .service(web::resource("/graphql").route(web::post().to(graphql)))
+                .service(web::resource("/subscriptions").route(web::get().to(subscriptions::session::subscriptions)))
+                .service(web::resource("/playground").route(web::get().to(playground)))
+            );
     }
 }
 
 pub async fn run() -> io::Result<()> {
-    std::env::set_var("RUST_LOG", "actix_server=info,actix_web=info");
+    std::env::set_var("RUST_LOG", "actix_server=info,actix_web=info,api_gateway=info");
     env_logger::init();
 
     dotenv().ok();
@@ -71,6 +205,46 @@ pub async fn run() -> io::Result<()> {
     // Create Juniper schema
     let schema = Arc::new(create_schema());
 
+    // Users are shared by all the workers. They are only kept in memory so
+    // every account other than the admin is lost when the gateway restarts
+    log::warn!("Users are kept in memory and are lost when the gateway restarts");
+    let jwt_secret = std::env::var("JWT_SECRET").expect("JWT_SECRET must be set");
+    let auth = Arc::new(Auth::new(AuthConfig::new(jwt_secret), Box::new(InMemoryUserStore::default())));
+
+    // Nobody can make themselves an admin, so the first one is created here
+    if let (Ok(email), Ok(password)) = (std::env::var("ADMIN_EMAIL"), std::env::var("ADMIN_PASSWORD")) {
+        match auth.create_user(&email, &password, Role::Admin) {
+            Ok(_) | Err(AuthError::EmailTaken) => {},
+            Err(err) => panic!("Could not create the admin from ADMIN_EMAIL: {}", err),
+        }
+    }
+
+    // API keys and rate limits are shared by all the workers as well
+    let api_keys = Arc::new(ApiKeys::new(Box::new(InMemoryApiKeyStore::default())));
+    let rate_limiter = Arc::new(RateLimiter::new(
+        Box::new(InMemoryRateLimitStore::default()),
+        // Bursts of 120 requests and 2 requests a second after that
+        RateLimit { capacity: 120, refill_every: Duration::from_millis(500) },
+        // Bursts of 60 requests and 1 request a second after that
+        RateLimit { capacity: 60, refill_every: Duration::from_secs(1) },
+    ));
+
+    // When there is a file of queries only those queries can be executed,
+    // otherwise clients can register their own
+    let persisted_queries = Arc::new(match std::env::var("PERSISTED_QUERIES_FILE") {
+        Ok(path) => PersistedQueries::allowlist_from_file(&path).expect("Could not read PERSISTED_QUERIES_FILE"),
+        Err(_) => PersistedQueries::automatic(10_000),
+    });
+
+    // The gateway follows the players API's changes on its own thread and
+    // passes them to the websocket sessions of every worker
+    let subscriptions = Arc::new(Subscriptions::default());
+    {
+        let subscriptions = subscriptions.clone();
+        let config = get_config();
+        std::thread::spawn(move || subscriptions.listen(&config));
+    }
+
     // Start http server
     HttpServer::new(move || {
         App::new()
@@ -81,7 +255,15 @@ pub async fn run() -> io::Result<()> {
                     .finish()
             )
             .wrap(middleware::Logger::default())
-            .configure(register(schema.clone()))
+            .configure(register(
+                schema.clone(),
+                auth.clone(),
+                api_keys.clone(),
+                rate_limiter.clone(),
+                QueryLimits::default(),
+                persisted_queries.clone(),
+                subscriptions.clone(),
+            ))
     })
     .bind("0.0.0.0:4000")?
     .workers(3)
@@ -0,0 +1,173 @@
+/// Looks up queries by their hash so clients do not have to send the same
+/// large queries over and over
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::RwLock;
+
+/// The only version of the persisted query protocol there is
+const PROTOCOL_VERSION: u32 = 1;
+
+/// The `persistedQuery` request extension Apollo clients send
+#[derive(Debug, Deserialize)]
+pub struct PersistedQuery {
+    pub version: u32,
+    #[serde(rename = "sha256Hash")]
+    pub sha256_hash: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PersistedQueryError {
+    /// The hash is not known. Clients send the query along with the hash next
+    NotFound,
+    NotSupported,
+    HashMismatch,
+    /// The query is not in the allowlist
+    NotAllowed,
+    MissingQuery,
+}
+
+impl PersistedQueryError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            PersistedQueryError::NotFound => "PERSISTED_QUERY_NOT_FOUND",
+            PersistedQueryError::NotSupported => "PERSISTED_QUERY_NOT_SUPPORTED",
+            PersistedQueryError::HashMismatch => "PERSISTED_QUERY_HASH_MISMATCH",
+            PersistedQueryError::NotAllowed => "PERSISTED_QUERY_NOT_ALLOWED",
+            PersistedQueryError::MissingQuery => "BAD_USER_INPUT",
+        }
+    }
+
+    /// The error in the shape of a GraphQL error
+    pub fn to_json(&self) -> Value {
+        json!({
+            "message": self.to_string(),
+            "extensions": { "code": self.code() },
+        })
+    }
+}
+
+impl fmt::Display for PersistedQueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            // Apollo clients look for these exact messages
+            PersistedQueryError::NotFound => write!(f, "PersistedQueryNotFound"),
+            PersistedQueryError::NotSupported => write!(f, "PersistedQueryNotSupported"),
+            PersistedQueryError::HashMismatch => write!(f, "Provided sha256Hash does not match the query"),
+            PersistedQueryError::NotAllowed => write!(f, "Query is not in the allowlist"),
+            PersistedQueryError::MissingQuery => write!(f, "Must provide a query or a persisted query"),
+        }
+    }
+}
+
+enum Mode {
+    /// Queries are registered the first time a client sends them with their
+    /// hash. Other queries can still be sent as usual
+    Automatic { max_queries: usize },
+    /// Only the queries loaded at startup can be executed
+    Allowlist,
+}
+
+/// The queries clients can refer to by their SHA-256 hash
+pub struct PersistedQueries {
+    mode: Mode,
+    /// Hex encoded SHA-256 of the query to the query
+    queries: RwLock<HashMap<String, String>>,
+}
+
+impl PersistedQueries {
+    /// Registers queries as clients send them with their hash. Once `max_queries`
+    /// are registered new queries are still executed but not registered
+    pub fn automatic(max_queries: usize) -> Self {
+        Self {
+            mode: Mode::Automatic { max_queries },
+            queries: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Only allows the queries given
+    pub fn allowlist<I: IntoIterator<Item = String>>(queries: I) -> Self {
+        let queries = queries
+            .into_iter()
+            .map(|query| (sha256_hex(&query), query))
+            .collect();
+
+        Self {
+            mode: Mode::Allowlist,
+            queries: RwLock::new(queries),
+        }
+    }
+
+    /// Only allows the queries in the file. The file is a JSON object of the
+    /// SHA-256 hash of each query to the query, which is what tools that extract
+    /// queries from clients generate. Errors when a hash does not match its query
+    pub fn allowlist_from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let manifest: HashMap<String, String> = serde_json::from_str(&fs::read_to_string(path)?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        if let Some((hash, _)) = manifest.iter().find(|(hash, query)| hash.to_lowercase() != sha256_hex(query)) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Persisted query {} does not match its hash", hash),
+            ));
+        }
+
+        Ok(Self::allowlist(manifest.into_iter().map(|(_, query)| query)))
+    }
+
+    /// Gets the query to execute from the query and the persisted query
+    /// extension of a request. New queries are not registered until they have
+    /// run, see [register](#method.register)
+    pub fn resolve(&self, query: Option<String>, persisted_query: Option<&PersistedQuery>) -> Result<String, PersistedQueryError> {
+        let hash = match persisted_query {
+            Some(persisted_query) if persisted_query.version != PROTOCOL_VERSION => {
+                return Err(PersistedQueryError::NotSupported);
+            },
+            Some(persisted_query) => Some(persisted_query.sha256_hash.to_lowercase()),
+            None => None,
+        };
+
+        match (&self.mode, query, hash) {
+            (_, None, None) => Err(PersistedQueryError::MissingQuery),
+            (_, Some(query), Some(hash)) if sha256_hex(&query) != hash => {
+                Err(PersistedQueryError::HashMismatch)
+            },
+            (Mode::Allowlist, _, Some(hash)) => self.get(&hash).ok_or(PersistedQueryError::NotAllowed),
+            (Mode::Allowlist, Some(query), None) => {
+                self.get(&sha256_hex(&query)).ok_or(PersistedQueryError::NotAllowed)
+            },
+            (Mode::Automatic { .. }, None, Some(hash)) => self.get(&hash).ok_or(PersistedQueryError::NotFound),
+            (Mode::Automatic { .. }, Some(query), _) => Ok(query),
+        }
+    }
+
+    /// Registers a query sent with its hash so the hash can be sent alone next
+    /// time. Only call this once the query passed the limits and executed
+    /// without errors, so queries that cannot run do not use up `max_queries`.
+    /// Does nothing for an allowlist
+    pub fn register(&self, query: &str) {
+        if let Mode::Automatic { max_queries } = self.mode {
+            let mut queries = self.queries.write().unwrap();
+            if queries.len() < max_queries {
+                queries.insert(sha256_hex(query), query.to_string());
+            }
+        }
+    }
+
+    fn get(&self, hash: &str) -> Option<String> {
+        self.queries.read().unwrap().get(hash).cloned()
+    }
+}
+
+fn sha256_hex(query: &str) -> String {
+    Sha256::digest(query.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
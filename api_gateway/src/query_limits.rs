@@ -0,0 +1,508 @@
+/// Checks how deep and how expensive a query is before it is executed so a
+/// nested or huge query cannot hammer the other services
+
+use juniper::meta::MetaType;
+use juniper::parser::{Lexer, Token};
+use juniper::{GraphQLType, RootNode, Type};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Selection sets nested deeper than this are rejected while parsing, before
+/// the depth is known, so the parser cannot overflow the stack
+const MAX_NESTING: usize = 64;
+
+#[derive(Clone, Copy, Debug)]
+pub struct QueryLimits {
+    /// How deep fields can be nested. The root fields are at depth 1
+    pub max_depth: usize,
+    /// Every field costs 1, and the fields under a list cost `list_size` times as much
+    pub max_complexity: u64,
+    pub max_aliases: u64,
+    /// How many items a list is assumed to have
+    pub list_size: u64,
+}
+
+impl Default for QueryLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: 10,
+            max_complexity: 5000,
+            max_aliases: 30,
+            list_size: 20,
+        }
+    }
+}
+
+/// What a query costs. Introspection fields are free so tools like the
+/// playground keep working
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct QueryCost {
+    pub depth: usize,
+    pub complexity: u64,
+    pub aliases: u64,
+}
+
+impl QueryCost {
+    /// Adds the cost of a sibling selection
+    fn add(&mut self, other: QueryCost) {
+        self.depth = self.depth.max(other.depth);
+        self.complexity = self.complexity.saturating_add(other.complexity);
+        self.aliases = self.aliases.saturating_add(other.aliases);
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum QueryLimitError {
+    Syntax(String),
+    TooDeep { depth: usize, max: usize },
+    TooComplex { complexity: u64, max: u64 },
+    TooManyAliases { aliases: u64, max: u64 },
+}
+
+impl QueryLimitError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            QueryLimitError::Syntax(_) => "GRAPHQL_PARSE_FAILED",
+            QueryLimitError::TooDeep { .. } => "MAX_DEPTH_EXCEEDED",
+            QueryLimitError::TooComplex { .. } => "MAX_COMPLEXITY_EXCEEDED",
+            QueryLimitError::TooManyAliases { .. } => "MAX_ALIASES_EXCEEDED",
+        }
+    }
+
+    /// The error in the shape of a GraphQL error, with the limit and the
+    /// actual value in the extensions
+    pub fn to_json(&self) -> Value {
+        let extensions = match self {
+            QueryLimitError::Syntax(_) => json!({ "code": self.code() }),
+            QueryLimitError::TooDeep { depth, max } => {
+                json!({ "code": self.code(), "max": max, "actual": depth })
+            },
+            QueryLimitError::TooComplex { complexity, max } => {
+                json!({ "code": self.code(), "max": max, "actual": complexity })
+            },
+            QueryLimitError::TooManyAliases { aliases, max } => {
+                json!({ "code": self.code(), "max": max, "actual": aliases })
+            },
+        };
+
+        json!({
+            "message": self.to_string(),
+            "extensions": extensions,
+        })
+    }
+}
+
+impl fmt::Display for QueryLimitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueryLimitError::Syntax(message) => write!(f, "Could not parse query: {}", message),
+            QueryLimitError::TooDeep { depth, max } => {
+                write!(f, "Query has a depth of {} which is more than the maximum of {}", depth, max)
+            },
+            QueryLimitError::TooComplex { complexity, max } => {
+                write!(f, "Query has a complexity of {} which is more than the maximum of {}", complexity, max)
+            },
+            QueryLimitError::TooManyAliases { aliases, max } => {
+                write!(f, "Query has {} aliases which is more than the maximum of {}", aliases, max)
+            },
+        }
+    }
+}
+
+impl QueryLimits {
+    /// Gets the cost of the operation that will be executed, or of every
+    /// operation when no operation name is given. Returns every limit the
+    /// query is over. Subscriptions are checked against the subscription schema
+    pub fn check<QueryT, MutationT>(
+        &self,
+        schema: &RootNode<'static, QueryT, MutationT>,
+        query: &str,
+        operation_name: Option<&str>,
+    ) -> Result<QueryCost, Vec<QueryLimitError>>
+    where
+        QueryT: GraphQLType,
+        MutationT: GraphQLType,
+    {
+        // Queries nested past MAX_NESTING are rejected before their depth is
+        // known so report them against the configured depth
+        let too_deep = |err| match err {
+            QueryLimitError::TooDeep { depth, .. } => vec![QueryLimitError::TooDeep { depth, max: self.max_depth }],
+            err => vec![err],
+        };
+
+        let document = Parser::parse(query).map_err(too_deep)?;
+
+        let mut analyzer = Analyzer {
+            schema,
+            fragments: &document.fragments,
+            list_size: self.list_size,
+            costs: HashMap::new(),
+            visiting: Vec::new(),
+        };
+
+        let mut cost = QueryCost::default();
+        for operation in &document.operations {
+            if operation_name.is_some() && operation.name != operation_name {
+                continue;
+            }
+
+            let root_type = match operation.kind {
+                OperationKind::Mutation => schema.schema.concrete_mutation_type(),
+                _ => Some(schema.schema.concrete_query_type()),
+            };
+            let operation_cost = analyzer
+                .selections_cost(&operation.selections, root_type)
+                .map_err(too_deep)?;
+            cost.depth = cost.depth.max(operation_cost.depth);
+            cost.complexity = cost.complexity.max(operation_cost.complexity);
+            cost.aliases = cost.aliases.max(operation_cost.aliases);
+        }
+
+        let mut errors = Vec::new();
+        if cost.depth > self.max_depth {
+            errors.push(QueryLimitError::TooDeep { depth: cost.depth, max: self.max_depth });
+        }
+        if cost.complexity > self.max_complexity {
+            errors.push(QueryLimitError::TooComplex { complexity: cost.complexity, max: self.max_complexity });
+        }
+        if cost.aliases > self.max_aliases {
+            errors.push(QueryLimitError::TooManyAliases { aliases: cost.aliases, max: self.max_aliases });
+        }
+
+        if errors.is_empty() {
+            Ok(cost)
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+enum OperationKind {
+    Query,
+    Mutation,
+    Subscription,
+}
+
+struct Operation<'a> {
+    kind: OperationKind,
+    name: Option<&'a str>,
+    selections: Vec<Selection<'a>>,
+}
+
+struct Fragment<'a> {
+    type_condition: &'a str,
+    selections: Vec<Selection<'a>>,
+}
+
+enum Selection<'a> {
+    Field {
+        alias: Option<&'a str>,
+        name: &'a str,
+        selections: Vec<Selection<'a>>,
+    },
+    FragmentSpread(&'a str),
+    InlineFragment {
+        type_condition: Option<&'a str>,
+        selections: Vec<Selection<'a>>,
+    },
+}
+
+struct Document<'a> {
+    operations: Vec<Operation<'a>>,
+    fragments: HashMap<&'a str, Fragment<'a>>,
+}
+
+/// Parses just enough of the query to know its shape. Arguments, variables
+/// and directives are skipped as they do not change the cost
+struct Parser<'a> {
+    tokens: Vec<Token<'a>>,
+    position: usize,
+    nesting: usize,
+}
+
+type ParseResult<T> = Result<T, QueryLimitError>;
+
+impl<'a> Parser<'a> {
+    fn parse(query: &'a str) -> ParseResult<Document<'a>> {
+        let tokens = Lexer::new(query)
+            .map(|token| token.map(|token| token.item))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| QueryLimitError::Syntax(err.item.to_string()))?;
+
+        let mut parser = Parser { tokens, position: 0, nesting: 0 };
+        let mut document = Document {
+            operations: Vec::new(),
+            fragments: HashMap::new(),
+        };
+
+        loop {
+            match parser.peek() {
+                Token::EndOfFile => break,
+                Token::CurlyOpen => {
+                    let selections = parser.selection_set()?;
+                    document.operations.push(Operation { kind: OperationKind::Query, name: None, selections });
+                },
+                Token::Name("fragment") => {
+                    parser.next();
+                    let name = parser.name()?;
+                    parser.expect_keyword("on")?;
+                    let type_condition = parser.name()?;
+                    parser.skip_directives()?;
+                    let selections = parser.selection_set()?;
+                    document.fragments.insert(name, Fragment { type_condition, selections });
+                },
+                Token::Name(keyword) => {
+                    let kind = match keyword {
+                        "query" => OperationKind::Query,
+                        "mutation" => OperationKind::Mutation,
+                        "subscription" => OperationKind::Subscription,
+                        _ => return Err(parser.unexpected()),
+                    };
+                    parser.next();
+                    let name = match parser.peek() {
+                        Token::Name(name) => {
+                            parser.next();
+                            Some(name)
+                        },
+                        _ => None,
+                    };
+                    parser.skip_arguments()?;
+                    parser.skip_directives()?;
+                    let selections = parser.selection_set()?;
+                    document.operations.push(Operation { kind, name, selections });
+                },
+                _ => return Err(parser.unexpected()),
+            }
+        }
+
+        Ok(document)
+    }
+
+    fn peek(&self) -> Token<'a> {
+        self.tokens.get(self.position).cloned().unwrap_or(Token::EndOfFile)
+    }
+
+    fn next(&mut self) -> Token<'a> {
+        let token = self.peek();
+        self.position += 1;
+        token
+    }
+
+    fn unexpected(&self) -> QueryLimitError {
+        QueryLimitError::Syntax(format!("Unexpected \"{}\"", self.peek()))
+    }
+
+    fn expect(&mut self, expected: Token<'a>) -> ParseResult<()> {
+        if self.peek() != expected {
+            return Err(self.unexpected());
+        }
+
+        self.next();
+        Ok(())
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> ParseResult<()> {
+        match self.peek() {
+            Token::Name(name) if name == keyword => {
+                self.next();
+                Ok(())
+            },
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn name(&mut self) -> ParseResult<&'a str> {
+        match self.peek() {
+            Token::Name(name) => {
+                self.next();
+                Ok(name)
+            },
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    /// Skips arguments or variable definitions, which can have nested objects and lists
+    fn skip_arguments(&mut self) -> ParseResult<()> {
+        if self.peek() != Token::ParenOpen {
+            return Ok(());
+        }
+
+        let mut depth = 0;
+        loop {
+            match self.next() {
+                Token::ParenOpen => depth += 1,
+                Token::ParenClose => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(());
+                    }
+                },
+                Token::EndOfFile => return Err(QueryLimitError::Syntax("Unexpected end of input".to_string())),
+                _ => {},
+            }
+        }
+    }
+
+    fn skip_directives(&mut self) -> ParseResult<()> {
+        while self.peek() == Token::At {
+            self.next();
+            self.name()?;
+            self.skip_arguments()?;
+        }
+
+        Ok(())
+    }
+
+    fn selection_set(&mut self) -> ParseResult<Vec<Selection<'a>>> {
+        self.expect(Token::CurlyOpen)?;
+
+        self.nesting += 1;
+        if self.nesting > MAX_NESTING {
+            return Err(QueryLimitError::TooDeep { depth: self.nesting, max: MAX_NESTING });
+        }
+
+        let mut selections = Vec::new();
+        while self.peek() != Token::CurlyClose {
+            selections.push(self.selection()?);
+        }
+        self.next();
+
+        self.nesting -= 1;
+        Ok(selections)
+    }
+
+    fn selection(&mut self) -> ParseResult<Selection<'a>> {
+        if self.peek() == Token::Ellipsis {
+            self.next();
+
+            return match self.peek() {
+                Token::Name("on") => {
+                    self.next();
+                    let type_condition = self.name()?;
+                    self.skip_directives()?;
+                    let selections = self.selection_set()?;
+                    Ok(Selection::InlineFragment { type_condition: Some(type_condition), selections })
+                },
+                Token::Name(name) => {
+                    self.next();
+                    self.skip_directives()?;
+                    Ok(Selection::FragmentSpread(name))
+                },
+                _ => {
+                    self.skip_directives()?;
+                    let selections = self.selection_set()?;
+                    Ok(Selection::InlineFragment { type_condition: None, selections })
+                },
+            };
+        }
+
+        let mut alias = None;
+        let mut name = self.name()?;
+        if self.peek() == Token::Colon {
+            self.next();
+            alias = Some(name);
+            name = self.name()?;
+        }
+        self.skip_arguments()?;
+        self.skip_directives()?;
+
+        let selections = if self.peek() == Token::CurlyOpen {
+            self.selection_set()?
+        } else {
+            Vec::new()
+        };
+
+        Ok(Selection::Field { alias, name, selections })
+    }
+}
+
+/// Adds up the cost of the selections using the schema to know which fields
+/// are lists
+struct Analyzer<'a, 'b, QueryT: GraphQLType, MutationT: GraphQLType> {
+    schema: &'b RootNode<'static, QueryT, MutationT>,
+    fragments: &'b HashMap<&'a str, Fragment<'a>>,
+    list_size: u64,
+    /// The cost of each fragment, so a fragment spread many times is only added up once
+    costs: HashMap<&'a str, QueryCost>,
+    /// The fragments being added up, to stop on cycles
+    visiting: Vec<&'a str>,
+}
+
+impl<'a, 'b, QueryT: GraphQLType, MutationT: GraphQLType> Analyzer<'a, 'b, QueryT, MutationT> {
+    fn selections_cost(
+        &mut self,
+        selections: &[Selection<'a>],
+        parent_type: Option<&'b MetaType<'b, juniper::DefaultScalarValue>>,
+    ) -> Result<QueryCost, QueryLimitError> {
+        let mut cost = QueryCost::default();
+
+        for selection in selections {
+            match selection {
+                Selection::Field { alias, name, selections } => {
+                    let alias_count = if alias.is_some() { 1 } else { 0 };
+
+                    // Introspection does not call the other services
+                    if name.starts_with("__") {
+                        cost.add(QueryCost { depth: 0, complexity: 0, aliases: alias_count });
+                        continue;
+                    }
+
+                    let field_type = parent_type
+                        .and_then(|parent_type| parent_type.field_by_name(name))
+                        .map(|field| &field.field_type);
+                    let is_list = match field_type {
+                        Some(Type::List(_)) | Some(Type::NonNullList(_)) => true,
+                        _ => false,
+                    };
+                    let child_type = field_type
+                        .and_then(|field_type| self.schema.schema.concrete_type_by_name(field_type.innermost_name()));
+
+                    let children = self.selections_cost(selections, child_type)?;
+                    let multiplier = if is_list { self.list_size } else { 1 };
+                    cost.add(QueryCost {
+                        depth: children.depth + 1,
+                        complexity: children.complexity.saturating_mul(multiplier).saturating_add(1),
+                        aliases: children.aliases.saturating_add(alias_count),
+                    });
+                },
+                Selection::FragmentSpread(name) => {
+                    cost.add(self.fragment_cost(name)?);
+                },
+                Selection::InlineFragment { type_condition, selections } => {
+                    let fragment_type = match type_condition {
+                        Some(type_condition) => self.schema.schema.concrete_type_by_name(type_condition),
+                        None => parent_type,
+                    };
+                    cost.add(self.selections_cost(selections, fragment_type)?);
+                },
+            }
+        }
+
+        Ok(cost)
+    }
+
+    fn fragment_cost(&mut self, name: &'a str) -> Result<QueryCost, QueryLimitError> {
+        if let Some(cost) = self.costs.get(name) {
+            return Ok(*cost);
+        }
+
+        // Unknown fragments and cycles are left for juniper to report
+        let fragment = match self.fragments.get(name) {
+            Some(fragment) if !self.visiting.contains(&name) => fragment,
+            _ => return Ok(QueryCost::default()),
+        };
+        if self.visiting.len() >= MAX_NESTING {
+            return Err(QueryLimitError::TooDeep { depth: self.visiting.len(), max: MAX_NESTING });
+        }
+
+        self.visiting.push(name);
+        let fragment_type = self.schema.schema.concrete_type_by_name(fragment.type_condition);
+        let cost = self.selections_cost(&fragment.selections, fragment_type);
+        self.visiting.pop();
+
+        let cost = cost?;
+        self.costs.insert(name, cost);
+        Ok(cost)
+    }
+}
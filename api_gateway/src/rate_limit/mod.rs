@@ -0,0 +1,119 @@
+use std::time::{Duration, Instant};
+
+pub mod store;
+
+use self::store::RateLimitStore;
+
+/// A token bucket that holds `capacity` tokens and gets a token back every
+/// `refill_every`. Each request takes a token
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct RateLimit {
+    pub capacity: u32,
+    pub refill_every: Duration,
+}
+
+/// The state of a bucket after a request tried to take a token
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateLimitStatus {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    /// How long until a request would be allowed again when it was not allowed,
+    /// otherwise how long until the bucket is full again
+    pub reset_after: Duration,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TokenBucket {
+    tokens: u32,
+    /// When the last token was given back, or when the bucket was created
+    refilled_at: Instant,
+    /// When the bucket will be full again, so idle buckets can be dropped
+    full_at: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(limit: &RateLimit, now: Instant) -> Self {
+        Self {
+            tokens: limit.capacity,
+            refilled_at: now,
+            full_at: now,
+        }
+    }
+
+    /// A full bucket is the same as no bucket so it can be dropped
+    pub fn is_full(&self, now: Instant) -> bool {
+        self.full_at <= now
+    }
+
+    /// Gives back the tokens earned since the last refill then takes a token if there is one
+    pub fn take(&mut self, limit: &RateLimit, now: Instant) -> RateLimitStatus {
+        let refill_every = limit.refill_every.as_nanos().max(1);
+        let elapsed = now.saturating_duration_since(self.refilled_at).as_nanos();
+        let earned = elapsed / refill_every;
+        if earned > 0 {
+            let missing = u128::from(limit.capacity.saturating_sub(self.tokens));
+            self.tokens += earned.min(missing) as u32;
+            self.refilled_at += Duration::from_nanos((earned * refill_every) as u64);
+        }
+        // A full bucket does not earn tokens so the time towards the next token
+        // starts when a token is taken
+        if self.tokens >= limit.capacity {
+            self.refilled_at = now;
+        }
+
+        let allowed = self.tokens > 0;
+        if allowed {
+            self.tokens -= 1;
+        }
+
+        let until_next_token = if self.tokens >= limit.capacity {
+            Duration::from_secs(0)
+        } else {
+            limit.refill_every - now.saturating_duration_since(self.refilled_at).min(limit.refill_every)
+        };
+        let until_full = match limit.capacity.saturating_sub(self.tokens) {
+            0 => Duration::from_secs(0),
+            missing => until_next_token + limit.refill_every * (missing - 1),
+        };
+        self.full_at = now + until_full;
+
+        RateLimitStatus {
+            allowed,
+            limit: limit.capacity,
+            remaining: self.tokens,
+            reset_after: if allowed { until_full } else { until_next_token },
+        }
+    }
+}
+
+/// Limits requests per IP address and, for requests with a key, per API key as well
+pub struct RateLimiter {
+    store: Box<dyn RateLimitStore>,
+    pub per_api_key: RateLimit,
+    pub per_ip: RateLimit,
+}
+
+impl RateLimiter {
+    pub fn new(store: Box<dyn RateLimitStore>, per_api_key: RateLimit, per_ip: RateLimit) -> Self {
+        Self { store, per_api_key, per_ip }
+    }
+
+    /// Takes a token from the IP's bucket then from the key's bucket. The
+    /// status sent back is the one of the bucket that ran out, or else the one
+    /// with the fewest tokens left
+    pub fn check(&self, api_key_id: Option<&str>, ip: &str) -> RateLimitStatus {
+        let ip_status = self.store.take(&format!("ip:{}", ip), &self.per_ip);
+        let api_key_id = match api_key_id {
+            Some(api_key_id) if ip_status.allowed => api_key_id,
+            _ => return ip_status,
+        };
+
+        let api_key_status = self.store.take(&format!("api_key:{}", api_key_id), &self.per_api_key);
+        if !api_key_status.allowed || api_key_status.remaining < ip_status.remaining {
+            api_key_status
+        } else {
+            ip_status
+        }
+    }
+}
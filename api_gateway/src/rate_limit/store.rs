@@ -0,0 +1,87 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use super::{RateLimit, RateLimitStatus, TokenBucket};
+
+/// How many buckets to keep. When there are this many, the least recently seen
+/// bucket is dropped for a new one if it is full, otherwise new keys share a bucket
+pub const MAX_BUCKETS: usize = 10_000;
+
+/// Storage for the token buckets. Each call has to take the token atomically
+/// so two workers cannot both take the last token
+pub trait RateLimitStore: Send + Sync {
+    fn take(&self, key: &str, limit: &RateLimit) -> RateLimitStatus;
+}
+
+/// Keeps the buckets in memory, so the limits are per gateway instance
+pub struct InMemoryRateLimitStore {
+    max_buckets: usize,
+    buckets: Mutex<Buckets>,
+}
+
+#[derive(Default)]
+struct Buckets {
+    by_key: HashMap<String, (TokenBucket, u64)>,
+    /// The keys by when they were last seen, oldest first
+    by_last_seen: BTreeMap<u64, String>,
+    last_seen: u64,
+    /// Shared by the keys that came when there was no room for their own bucket
+    overflow: HashMap<RateLimit, TokenBucket>,
+}
+
+impl InMemoryRateLimitStore {
+    pub fn new(max_buckets: usize) -> Self {
+        Self {
+            max_buckets,
+            buckets: Mutex::new(Buckets::default()),
+        }
+    }
+}
+
+impl Default for InMemoryRateLimitStore {
+    fn default() -> Self {
+        Self::new(MAX_BUCKETS)
+    }
+}
+
+impl RateLimitStore for InMemoryRateLimitStore {
+    fn take(&self, key: &str, limit: &RateLimit) -> RateLimitStatus {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        let buckets = &mut *buckets;
+        buckets.last_seen += 1;
+        let last_seen = buckets.last_seen;
+
+        if let Some((bucket, seen)) = buckets.by_key.get_mut(key) {
+            buckets.by_last_seen.remove(seen);
+            buckets.by_last_seen.insert(last_seen, key.to_string());
+            *seen = last_seen;
+            return bucket.take(limit, now);
+        }
+
+        if buckets.by_key.len() >= self.max_buckets {
+            // A full bucket is the same as no bucket so dropping it loses nothing
+            let oldest = buckets.by_last_seen.iter().next().map(|(seen, key)| (*seen, key.clone()));
+            match oldest {
+                Some((seen, oldest)) if buckets.by_key[&oldest].0.is_full(now) => {
+                    buckets.by_last_seen.remove(&seen);
+                    buckets.by_key.remove(&oldest);
+                },
+                _ => {
+                    return buckets.overflow
+                        .entry(*limit)
+                        .or_insert_with(|| TokenBucket::new(limit, now))
+                        .take(limit, now);
+                },
+            }
+        }
+
+        let mut bucket = TokenBucket::new(limit, now);
+        let status = bucket.take(limit, now);
+        buckets.by_key.insert(key.to_string(), (bucket, last_seen));
+        buckets.by_last_seen.insert(last_seen, key.to_string());
+
+        status
+    }
+}
@@ -0,0 +1,66 @@
+use juniper::{FieldResult, GraphQLInputObject, GraphQLObject};
+use uuid::Uuid;
+
+use super::Context;
+use crate::api_keys::ApiKeyScope;
+
+#[derive(Debug, GraphQLObject)]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub name: String,
+    pub scopes: Vec<ApiKeyScope>,
+    /// The id of the user who issued the key
+    pub created_by: Uuid,
+    pub revoked: bool,
+}
+
+impl From<crate::api_keys::ApiKey> for ApiKey {
+    fn from(api_key: crate::api_keys::ApiKey) -> Self {
+        Self {
+            id: api_key.id,
+            name: api_key.name,
+            scopes: api_key.scopes,
+            created_by: api_key.created_by,
+            revoked: api_key.revoked,
+        }
+    }
+}
+
+#[derive(Debug, GraphQLObject)]
+pub struct IssueApiKeyPayload {
+    pub api_key: ApiKey,
+    /// The key to send as `X-Api-Key`. It is only shown once
+    pub key: String,
+}
+
+#[derive(GraphQLInputObject)]
+pub struct IssueApiKeyInput {
+    /// Who the key is for, e.g. the name of the partner
+    pub name: String,
+    pub scopes: Vec<ApiKeyScope>,
+}
+
+#[derive(GraphQLInputObject)]
+pub struct RevokeApiKeyInput {
+    pub id: Uuid,
+}
+
+pub fn api_keys(context: &Context) -> FieldResult<Vec<ApiKey>> {
+    Ok(context.api_keys.list().into_iter().map(ApiKey::from).collect())
+}
+
+pub fn issue_api_key(input: IssueApiKeyInput, context: &Context) -> FieldResult<IssueApiKeyPayload> {
+    let user = context.require_user()?;
+    let (api_key, key) = context.api_keys.issue(&input.name, input.scopes, user.id)?;
+
+    Ok(IssueApiKeyPayload {
+        api_key: ApiKey::from(api_key),
+        key,
+    })
+}
+
+pub fn revoke_api_key(input: RevokeApiKeyInput, context: &Context) -> FieldResult<ApiKey> {
+    let api_key = context.api_keys.revoke(input.id)?;
+
+    Ok(ApiKey::from(api_key))
+}
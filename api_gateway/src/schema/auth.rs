@@ -0,0 +1,106 @@
+use juniper::{FieldError, FieldResult, GraphQLInputObject, GraphQLObject};
+use uuid::Uuid;
+
+use super::Context;
+use super::errors::{error, ErrorCode};
+use crate::auth::{AuthError, AuthUser, Role, Tokens};
+
+#[derive(Debug, GraphQLObject)]
+pub struct User {
+    pub id: Uuid,
+    pub email: String,
+    pub role: Role,
+}
+
+impl From<&AuthUser> for User {
+    fn from(user: &AuthUser) -> Self {
+        Self {
+            id: user.id,
+            email: user.email.clone(),
+            role: user.role,
+        }
+    }
+}
+
+#[derive(Debug, GraphQLObject)]
+pub struct AuthPayload {
+    /// Short lived token to send as `Authorization: Bearer <token>`
+    pub access_token: String,
+    /// Long lived token to get new tokens with `refreshToken`
+    pub refresh_token: String,
+    pub user: User,
+}
+
+impl From<Tokens> for AuthPayload {
+    fn from(tokens: Tokens) -> Self {
+        Self {
+            user: User::from(&tokens.user),
+            access_token: tokens.access_token,
+            refresh_token: tokens.refresh_token,
+        }
+    }
+}
+
+#[derive(GraphQLInputObject)]
+pub struct SignupInput {
+    pub email: String,
+    pub password: String,
+}
+
+#[derive(GraphQLInputObject)]
+pub struct LoginInput {
+    pub email: String,
+    pub password: String,
+}
+
+#[derive(GraphQLInputObject)]
+pub struct RefreshTokenInput {
+    pub refresh_token: String,
+}
+
+#[derive(GraphQLInputObject)]
+pub struct SetUserRoleInput {
+    pub user_id: Uuid,
+    pub role: Role,
+}
+
+/// Gives the auth errors a code clients can branch on
+fn auth_error(err: AuthError) -> FieldError {
+    let code = match err {
+        AuthError::InvalidEmail | AuthError::PasswordTooShort => ErrorCode::Validation,
+        AuthError::EmailTaken => ErrorCode::Conflict,
+        AuthError::InvalidCredentials | AuthError::InvalidToken => ErrorCode::Unauthorized,
+        AuthError::UserNotFound => ErrorCode::NotFound,
+        AuthError::Internal(_) => ErrorCode::Internal,
+    };
+
+    error(code, err)
+}
+
+pub fn me(context: &Context) -> FieldResult<User> {
+    context.require_user().map(User::from)
+}
+
+pub fn signup(input: SignupInput, context: &Context) -> FieldResult<AuthPayload> {
+    let tokens = context.auth.signup(&input.email, &input.password).map_err(auth_error)?;
+
+    Ok(AuthPayload::from(tokens))
+}
+
+pub fn login(input: LoginInput, context: &Context) -> FieldResult<AuthPayload> {
+    let tokens = context.auth.login(&input.email, &input.password).map_err(auth_error)?;
+
+    Ok(AuthPayload::from(tokens))
+}
+
+pub fn refresh_token(input: RefreshTokenInput, context: &Context) -> FieldResult<AuthPayload> {
+    let tokens = context.auth.refresh(&input.refresh_token).map_err(auth_error)?;
+
+    Ok(AuthPayload::from(tokens))
+}
+
+pub fn set_user_role(input: SetUserRoleInput, context: &Context) -> FieldResult<User> {
+    let user = context.auth.set_role(input.user_id, input.role).map_err(auth_error)?;
+
+    Ok(User::from(&user))
+}
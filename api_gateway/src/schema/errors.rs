@@ -0,0 +1,126 @@
+/// The errors resolvers send back. Every error has an `extensions.code` so
+/// clients can branch on it instead of on the message, i.e.
+/// `{ "message": "Player not found", "extensions": { "code": "NOT_FOUND", "upstreamStatus": 404 } }`
+
+use juniper::{FieldError, Object, Value};
+use std::fmt::Display;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ErrorCode {
+    NotFound,
+    /// The input is not valid. The fields are in `extensions.fieldErrors` when known
+    Validation,
+    /// The request needs a logged in user, or the credentials are not valid
+    Unauthorized,
+    /// The user or API key is not allowed to do this
+    Forbidden,
+    /// The change would make a duplicate
+    Conflict,
+    /// A service the gateway calls could not be reached or is down
+    UpstreamUnavailable,
+    Internal,
+}
+
+impl ErrorCode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::NotFound => "NOT_FOUND",
+            ErrorCode::Validation => "VALIDATION",
+            ErrorCode::Unauthorized => "UNAUTHORIZED",
+            ErrorCode::Forbidden => "FORBIDDEN",
+            ErrorCode::Conflict => "CONFLICT",
+            ErrorCode::UpstreamUnavailable => "UPSTREAM_UNAVAILABLE",
+            ErrorCode::Internal => "INTERNAL",
+        }
+    }
+
+    /// The code for an error response from another service
+    pub fn from_status(status: u16) -> Self {
+        match status {
+            400 | 422 => ErrorCode::Validation,
+            401 => ErrorCode::Unauthorized,
+            403 => ErrorCode::Forbidden,
+            404 => ErrorCode::NotFound,
+            409 => ErrorCode::Conflict,
+            502 | 503 | 504 => ErrorCode::UpstreamUnavailable,
+            _ => ErrorCode::Internal,
+        }
+    }
+}
+
+/// A field of the input that is not valid
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldViolation {
+    pub field: String,
+    pub message: String,
+}
+
+/// Builds an error with a code and, for errors from another service, what
+/// that service sent back
+#[derive(Debug)]
+pub struct GraphQLError {
+    message: String,
+    code: ErrorCode,
+    upstream_status: Option<u16>,
+    upstream_code: Option<String>,
+    field_errors: Vec<FieldViolation>,
+}
+
+impl GraphQLError {
+    pub fn new(code: ErrorCode, message: impl Display) -> Self {
+        Self {
+            message: message.to_string(),
+            code,
+            upstream_status: None,
+            upstream_code: None,
+            field_errors: Vec::new(),
+        }
+    }
+
+    pub fn with_upstream_status(mut self, status: u16) -> Self {
+        self.upstream_status = Some(status);
+        self
+    }
+
+    /// The error code the other service sent, i.e. `foreign_key_violation`
+    pub fn with_upstream_code(mut self, code: String) -> Self {
+        self.upstream_code = Some(code);
+        self
+    }
+
+    pub fn with_field_errors(mut self, field_errors: Vec<FieldViolation>) -> Self {
+        self.field_errors = field_errors;
+        self
+    }
+
+    pub fn into_field_error(self) -> FieldError {
+        let mut extensions = Object::with_capacity(4);
+        extensions.add_field("code", Value::scalar(self.code.as_str().to_string()));
+        if let Some(status) = self.upstream_status {
+            extensions.add_field("upstreamStatus", Value::scalar(i32::from(status)));
+        }
+        if let Some(code) = self.upstream_code {
+            extensions.add_field("upstreamCode", Value::scalar(code));
+        }
+        if !self.field_errors.is_empty() {
+            let field_errors = self.field_errors
+                .into_iter()
+                .map(|violation| {
+                    let mut field_error = Object::with_capacity(2);
+                    field_error.add_field("field", Value::scalar(violation.field));
+                    field_error.add_field("message", Value::scalar(violation.message));
+
+                    Value::object(field_error)
+                })
+                .collect();
+            extensions.add_field("fieldErrors", Value::list(field_errors));
+        }
+
+        FieldError::new(self.message, Value::object(extensions))
+    }
+}
+
+/// Shorthand for an error that only has a code
+pub fn error(code: ErrorCode, message: impl Display) -> FieldError {
+    GraphQLError::new(code, message).into_field_error()
+}
@@ -1,74 +1,228 @@
 use juniper::Context as JuniperContext;
-use juniper::{FieldResult, RootNode};
+use juniper::{EmptyMutation, FieldResult, RootNode};
+use std::sync::Arc;
 use uuid::Uuid;
 
+use crate::api_keys::{ApiKey, ApiKeyScope, ApiKeys};
+use crate::auth::{Auth, AuthUser, Permission};
+use crate::signing::SigningKey;
+use crate::subscriptions::ChangeEvent;
+use errors::{error, ErrorCode};
+
+pub mod api_keys;
+pub mod auth;
+pub mod errors;
 #[macro_use]
 pub mod players_api;
+pub mod subscriptions;
 
 pub struct QueryRoot;
 pub struct MutationRoot;
+pub struct SubscriptionRoot;
 
 pub type Schema = RootNode<'static, QueryRoot, MutationRoot>;
 
-#[derive(Debug)]
+/// juniper cannot run subscription operations yet, so subscriptions are run
+/// as queries on this schema each time there is a change event
+pub type SubscriptionSchema = RootNode<'static, SubscriptionRoot, EmptyMutation<Context>>;
+
+#[derive(Clone, Debug)]
 pub struct Config {
     pub(crate) players_api_host: String,
+    /// Secret shared with the other services to sign service tokens
+    pub(crate) service_token_secret: String,
+    /// Key the requests to the other services are signed with
+    pub(crate) signing_key: SigningKey,
 }
 
+#[derive(Clone)]
 pub struct Context {
     config: Config,
+    auth: Arc<Auth>,
+    api_keys: Arc<ApiKeys>,
+    /// The user making the request. None when no access token was sent
+    user: Option<AuthUser>,
+    /// The API key the request was made with. None when no API key was sent
+    api_key: Option<ApiKey>,
+    /// The change a subscription is being run for. None for queries and mutations
+    event: Option<ChangeEvent>,
 }
 
 impl JuniperContext for Context {}
 
 impl Context {
-    pub fn new(config: Config) -> Self {
+    pub fn new(
+        config: Config,
+        auth: Arc<Auth>,
+        api_keys: Arc<ApiKeys>,
+        user: Option<AuthUser>,
+        api_key: Option<ApiKey>,
+    ) -> Self {
         Self {
             config,
+            auth,
+            api_keys,
+            user,
+            api_key,
+            event: None,
+        }
+    }
+
+    /// Sets the change event subscriptions are run for
+    pub fn with_event(self, event: ChangeEvent) -> Self {
+        Self {
+            event: Some(event),
+            ..self
         }
     }
+
+    pub fn event(&self) -> Option<&ChangeEvent> {
+        self.event.as_ref()
+    }
+
+    /// Gets the user making the request. Resolvers that need an authenticated
+    /// user call this first so anonymous requests get an error
+    pub fn require_user(&self) -> FieldResult<&AuthUser> {
+        self.user.as_ref().ok_or_else(|| error(ErrorCode::Unauthorized, "You must be logged in"))
+    }
+
+    /// Gets the user making the request if their role has the permission
+    pub fn require_permission(&self, permission: Permission) -> FieldResult<&AuthUser> {
+        let user = self.require_user()?;
+        if !user.role.can(permission) {
+            return Err(error(ErrorCode::Forbidden, "You do not have permission to do this"));
+        }
+
+        Ok(user)
+    }
+
+    /// Checks the API key the request was made with has the scope. Requests
+    /// made without an API key are not limited by scopes
+    pub fn require_scope(&self, scope: ApiKeyScope) -> FieldResult<()> {
+        match &self.api_key {
+            Some(api_key) if !api_key.has_scope(scope) => {
+                Err(error(ErrorCode::Forbidden, "This API key does not have access to this"))
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Creates the token to send to the other services so they can check the
+    /// user making the request as well
+    pub(crate) fn service_token(&self) -> FieldResult<String> {
+        let user = self.require_user()?;
+
+        Ok(crate::auth::service_token(&self.config.service_token_secret, user)?)
+    }
 }
 
 #[juniper::object(Context = Context)]
 impl QueryRoot { 
+    // Auth
+    fn me(context: &Context) -> FieldResult<auth::User> {
+        auth::me(context)
+    }
+
+    // API keys
+    fn api_keys(context: &Context) -> FieldResult<Vec<api_keys::ApiKey>> {
+        context.require_permission(Permission::ManageApiKeys)?;
+        api_keys::api_keys(context)
+    }
+
     // Players API
-    fn players(context: &Context) -> FieldResult<Vec<players_api::Player>> {
-        players_api::players(context)
+    fn players(rookie: Option<bool>, context: &Context) -> FieldResult<Vec<players_api::Player>> {
+        context.require_scope(ApiKeyScope::ReadPlayers)?;
+        players_api::players(rookie, context)
     }
     fn player(id: Uuid, context: &Context) -> FieldResult<players_api::Player> {
+        context.require_scope(ApiKeyScope::ReadPlayers)?;
         players_api::player(id, context)
     }
     fn teams(context: &Context) -> FieldResult<Vec<players_api::Team>> {
+        context.require_scope(ApiKeyScope::ReadPlayers)?;
         players_api::teams(context)
     }
     fn team(id: Uuid, context: &Context) -> FieldResult<players_api::Team> {
+        context.require_scope(ApiKeyScope::ReadPlayers)?;
         players_api::team(id, context)
     }
+    fn rankings(format: players_api::ScoringFormat, position: Option<String>, context: &Context) -> FieldResult<Vec<players_api::RankingWithPlayer>> {
+        context.require_scope(ApiKeyScope::ReadRankings)?;
+        players_api::rankings(format, position, context)
+    }
 }
 
 #[juniper::object(Context = Context)]
 impl MutationRoot { 
+    // Auth
+    fn signup(input: auth::SignupInput, context: &Context) -> FieldResult<auth::AuthPayload> {
+        auth::signup(input, context)
+    }
+    fn login(input: auth::LoginInput, context: &Context) -> FieldResult<auth::AuthPayload> {
+        auth::login(input, context)
+    }
+    fn refresh_token(input: auth::RefreshTokenInput, context: &Context) -> FieldResult<auth::AuthPayload> {
+        auth::refresh_token(input, context)
+    }
+    fn set_user_role(input: auth::SetUserRoleInput, context: &Context) -> FieldResult<auth::User> {
+        context.require_permission(Permission::ManageUsers)?;
+        auth::set_user_role(input, context)
+    }
+
+    // API keys
+    fn issue_api_key(input: api_keys::IssueApiKeyInput, context: &Context) -> FieldResult<api_keys::IssueApiKeyPayload> {
+        context.require_permission(Permission::ManageApiKeys)?;
+        api_keys::issue_api_key(input, context)
+    }
+    fn revoke_api_key(input: api_keys::RevokeApiKeyInput, context: &Context) -> FieldResult<api_keys::ApiKey> {
+        context.require_permission(Permission::ManageApiKeys)?;
+        api_keys::revoke_api_key(input, context)
+    }
+
     // Players API
     fn create_player(input: players_api::CreatePlayerInput, context: &Context) -> FieldResult<players_api::CreatePlayerResponse> {
+        context.require_permission(Permission::ManagePlayers)?;
         players_api::create_player(input, context)
     }
     fn update_player(input: players_api::UpdatePlayerInput, context: &Context) -> FieldResult<players_api::UpdatePlayerResponse> {
+        context.require_permission(Permission::ManagePlayers)?;
         players_api::update_player(input, context)
     }
     fn delete_player(input: players_api::DeletePlayerInput, context: &Context) -> FieldResult<players_api::DeletePlayerResponse> {
+        context.require_permission(Permission::ManagePlayers)?;
         players_api::delete_player(input, context)
     }
     fn create_team(input: players_api::CreateTeamInput, context: &Context) -> FieldResult<players_api::CreateTeamResponse> {
+        context.require_permission(Permission::ManagePlayers)?;
         players_api::create_team(input, context)
     }
     fn update_team(input: players_api::UpdateTeamInput, context: &Context) -> FieldResult<players_api::UpdateTeamResponse> {
+        context.require_permission(Permission::ManagePlayers)?;
         players_api::update_team(input, context)
     }
     fn delete_team(input: players_api::DeleteTeamInput, context: &Context) -> FieldResult<players_api::DeleteTeamResponse> {
+        context.require_permission(Permission::ManagePlayers)?;
         players_api::delete_team(input, context)
     }
 }
 
+#[juniper::object(Context = Context)]
+impl SubscriptionRoot {
+    // Players API
+    fn player_updated(id: Uuid, context: &Context) -> FieldResult<Option<players_api::Player>> {
+        context.require_scope(ApiKeyScope::ReadPlayers)?;
+        subscriptions::player_updated(id, context)
+    }
+    fn team_updated(id: Option<Uuid>, context: &Context) -> FieldResult<Option<players_api::Team>> {
+        context.require_scope(ApiKeyScope::ReadPlayers)?;
+        subscriptions::team_updated(id, context)
+    }
+}
+
 pub fn create_schema() -> Schema {
     Schema::new(QueryRoot {}, MutationRoot {})
 }
+
+pub fn create_subscription_schema() -> SubscriptionSchema {
+    SubscriptionSchema::new(SubscriptionRoot {}, EmptyMutation::new())
+}
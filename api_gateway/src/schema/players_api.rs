@@ -1,32 +1,41 @@
-use juniper::{FieldResult, GraphQLInputObject, GraphQLObject};
+use juniper::{FieldResult, GraphQLEnum, GraphQLInputObject, GraphQLObject};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use super::Context;
+use super::errors::{error, ErrorCode, FieldViolation, GraphQLError};
+use crate::api_keys::ApiKeyScope;
+use crate::signing::SignRequest;
+
+/// The header the players API reads the service token from
+const SERVICE_TOKEN_HEADER: &str = "X-Service-Token";
 
 /// Additional functions on Result which will help with handling
 /// responses
 trait ReqwestResponse {
-    fn handle_response_errors(self) -> reqwest::Result<Result<reqwest::blocking::Response, String>>;
+    fn handle_response_errors(self, action: &str) -> FieldResult<reqwest::blocking::Response>;
 }
 
 impl ReqwestResponse for reqwest::Result<reqwest::blocking::Response> {
-    /// Handles response status errors i.e. 400. The players_api will return errors with the
-    /// JSON body { "message": String, "data": Object }. This function will extract the message
-    /// part and return it as an error
-    fn handle_response_errors(self) -> reqwest::Result<Result<reqwest::blocking::Response, String>> {
-        self.map(|res| {
-            if !res.status().is_success() {
-                let err_message = res.json::<JsonError>()
-                    .map_or("Something went wrong with the request".to_string(), |json_err| {
-                        json_err.message
-                    });
-
-                return Err(err_message);
-            }
+    /// Handles failed requests. When the players API cannot be reached the error is
+    /// `UPSTREAM_UNAVAILABLE` with what was being done, i.e. "fetching the player".
+    /// Error responses are [Problem](./struct.Problem.html) details, which this
+    /// function turns into a GraphQL error with the upstream status and field errors
+    fn handle_response_errors(self, action: &str) -> FieldResult<reqwest::blocking::Response> {
+        let res = self.map_err(|_err| error(ErrorCode::UpstreamUnavailable, format!("There was an error {}", action)))?;
+
+        if !res.status().is_success() {
+            let status = res.status().as_u16();
+            let err = match res.json::<Problem>() {
+                Ok(problem) => problem.into_graphql_error(status),
+                Err(_) => GraphQLError::new(ErrorCode::from_status(status), format!("There was an error {}", action))
+                    .with_upstream_status(status),
+            };
+
+            return Err(err.into_field_error());
+        }
 
-            Ok(res)
-        })
+        Ok(res)
     }
 }
 
@@ -46,11 +55,52 @@ macro_rules! PlayerBase {
     }
 }
 
+/// An [RFC 7807](https://tools.ietf.org/html/rfc7807) problem sent by the
+/// players API, i.e. `{ "type": "...", "title": "Not Found", "status": 404,
+/// "detail": "Player not found", "code": "not_found" }`
+#[derive(Deserialize)]
+pub struct Problem {
+    title: Option<String>,
+    detail: Option<String>,
+    code: Option<String>,
+    /// The fields of the request that are not valid
+    #[serde(default)]
+    errors: Vec<ProblemFieldError>,
+}
+
 #[derive(Deserialize)]
-pub struct JsonError {
+struct ProblemFieldError {
+    field: String,
     message: String,
 }
 
+impl Problem {
+    /// The message is the detail of the problem. The code is picked from the
+    /// status, except when the players API could not reach its database
+    fn into_graphql_error(self, status: u16) -> GraphQLError {
+        let message = self.detail
+            .or(self.title)
+            .unwrap_or_else(|| "Something went wrong with the request".to_string());
+        let code = match self.code.as_deref() {
+            Some("database_unavailable") => ErrorCode::UpstreamUnavailable,
+            _ => ErrorCode::from_status(status),
+        };
+        let field_errors = self.errors
+            .into_iter()
+            .map(|error| FieldViolation { field: error.field, message: error.message })
+            .collect();
+
+        let err = GraphQLError::new(code, message)
+            .with_upstream_status(status)
+            .with_field_errors(field_errors);
+
+        match self.code {
+            Some(upstream_code) => err.with_upstream_code(upstream_code),
+            None => err,
+        }
+    }
+}
+
 #[derive(Debug, GraphQLObject, Clone, Deserialize, Serialize)]
 pub struct Team {
     pub id: Uuid,
@@ -58,10 +108,22 @@ pub struct Team {
     pub abbreviation: String,
 }
 
+#[derive(Debug, GraphQLObject, Clone, Deserialize, Serialize)]
+pub struct Projection {
+    pub player_id: Uuid,
+    pub season: i32,
+    /// Week 0 is the season long projection
+    pub week: i32,
+    /// Where the projection came from. Null when it is the average of all sources
+    pub source: Option<String>,
+    pub points: f64,
+}
+
 PlayerBase!(
-    #[derive(Debug, Deserialize, Serialize)]
+    #[derive(Clone, Debug, Deserialize, Serialize)]
     Player {
         id: Uuid,
+        rookie: bool,
 
         #[serde(skip_deserializing)]
         team: Option<Team>,
@@ -75,6 +137,7 @@ impl Player {
     fn id(&self) -> &Uuid { &self.id } 
     fn first_name(&self) -> &str { &self.first_name }
     fn last_name(&self) -> &str { &self.last_name }
+    fn rookie(&self) -> bool { self.rookie }
 
     /// Gets the team of the player. For read methods,
     /// the team is returned with the player so we can skip the request
@@ -87,6 +150,13 @@ impl Player {
             (Some(team_id), None) => team(team_id, context).map(Some),
         }
     }
+
+    /// Gets the projected points of the player for a season and week. When no
+    /// source is given, the projection is averaged across all sources
+    fn projection(&self, season: i32, week: i32, source: Option<String>, context: &Context) -> FieldResult<Option<Projection>> {
+        context.require_scope(ApiKeyScope::ReadProjections)?;
+        projection(self.id, season, week, source, context)
+    }
 }
 
 #[derive(Deserialize, Serialize)]
@@ -95,17 +165,19 @@ pub struct PlayerWithTeam {
     pub team: Option<Team>,
 }
 
-pub fn players(context: &Context) -> FieldResult<Vec<Player>> {
+pub fn players(rookie: Option<bool>, context: &Context) -> FieldResult<Vec<Player>> {
     let api = &context.config.players_api_host;
     let client = reqwest::blocking::Client::new();
 
-    let players: Vec<_> = client
-        .get(format!("{}/players", api).as_str())
+    let mut request = client.get(format!("{}/players", api).as_str());
+    if let Some(rookie) = rookie {
+        request = request.query(&[("rookie", rookie)]);
+    }
+
+    let players: Vec<_> = request
+        .sign(&context.config.signing_key)
         .send()
-        .handle_response_errors()
-        .unwrap_or_else(|_err| {
-            Err("There was an error fetching the players".to_string())
-        })?
+        .handle_response_errors("fetching the players")?
         .json::<Vec<PlayerWithTeam>>()?
         .into_iter()
         .map(|player_with_team| {
@@ -125,11 +197,9 @@ pub fn player(id: Uuid, context: &Context) -> FieldResult<Player> {
 
     let player = client
         .get(format!("{}/players/{}", api, id).as_str())
+        .sign(&context.config.signing_key)
         .send()
-        .handle_response_errors()
-        .unwrap_or_else(|_err| {
-            Err("There was an error fetching the player".to_string())
-        })?
+        .handle_response_errors("fetching the player")?
         .json::<PlayerWithTeam>()
         .map(|player_with_team| {
             Player {
@@ -141,9 +211,46 @@ pub fn player(id: Uuid, context: &Context) -> FieldResult<Player> {
     Ok(player)
 }
 
+pub fn projection(
+    player_id: Uuid,
+    season: i32,
+    week: i32,
+    source: Option<String>,
+    context: &Context
+) -> FieldResult<Option<Projection>> {
+    let api = &context.config.players_api_host;
+    let client = reqwest::blocking::Client::new();
+
+    let request = match source {
+        Some(source) => client
+            .get(format!("{}/projections", api).as_str())
+            .query(&[("source", source)]),
+        None => client.get(format!("{}/projections/average", api).as_str()),
+    };
+
+    let projection = request
+        .query(&[
+            ("player_id", player_id.to_string()),
+            ("season", season.to_string()),
+            ("week", week.to_string()),
+        ])
+        .sign(&context.config.signing_key)
+        .send()
+        .handle_response_errors("fetching the projection")?
+        .json::<Vec<Projection>>()?
+        .into_iter()
+        .next();
+
+    Ok(projection)
+}
+
 PlayerBase!(
     #[derive(GraphQLInputObject, Serialize)]
-    CreatePlayerInput { }
+    CreatePlayerInput {
+        /// Defaults to false so clients that do not know about rookies still work
+        #[serde(skip_serializing_if = "Option::is_none")]
+        rookie: Option<bool>,
+    }
 );
 
 pub struct CreatePlayerResponse {
@@ -161,12 +268,10 @@ pub fn create_player(input: CreatePlayerInput, context: &Context) -> FieldResult
 
     let player = client
         .post(format!("{}/players", api).as_str())
-        .json(&input)
+        .header(SERVICE_TOKEN_HEADER, context.service_token()?)
+        .sign_json(&input, &context.config.signing_key)
         .send()
-        .handle_response_errors()
-        .unwrap_or_else(|_err| {
-            Err("There was an error creating the player".to_string())
-        })?
+        .handle_response_errors("creating the player")?
         .json::<Player>()?;
 
     Ok(CreatePlayerResponse { player })
@@ -177,6 +282,10 @@ PlayerBase!(
     UpdatePlayerInput { 
         #[serde(skip_serializing)]
         id: Uuid,
+        /// Left out to keep the player's current value, so clients that do not
+        /// know about rookies still work
+        #[serde(skip_serializing_if = "Option::is_none")]
+        rookie: Option<bool>,
     }
 );
 
@@ -195,12 +304,10 @@ pub fn update_player(input: UpdatePlayerInput, context: &Context) -> FieldResult
 
     let player = client
         .put(format!("{}/players/{}", api, input.id).as_str())
-        .json(&input)
+        .header(SERVICE_TOKEN_HEADER, context.service_token()?)
+        .sign_json(&input, &context.config.signing_key)
         .send()
-        .handle_response_errors()
-        .unwrap_or_else(|_err| {
-            Err("There was an error updating the player".to_string())
-        })?
+        .handle_response_errors("updating the player")?
         .json::<Player>()?;
 
     Ok(UpdatePlayerResponse { player })
@@ -222,11 +329,10 @@ pub fn delete_player(input: DeletePlayerInput, context: &Context) -> FieldResult
 
     client
         .delete(format!("{}/players/{}", api, input.id).as_str())
+        .header(SERVICE_TOKEN_HEADER, context.service_token()?)
+        .sign(&context.config.signing_key)
         .send()
-        .handle_response_errors()
-        .unwrap_or_else(|_err| {
-            Err("There was an error deleting the player".to_string())
-        })?;
+        .handle_response_errors("deleting the player")?;
 
     Ok(DeletePlayerResponse { success: true })
 }
@@ -237,11 +343,9 @@ pub fn teams(context: &Context) -> FieldResult<Vec<Team>> {
 
     let teams: Vec<_> = client
         .get(format!("{}/teams", api).as_str())
+        .sign(&context.config.signing_key)
         .send()
-        .handle_response_errors()
-        .unwrap_or_else(|_err| {
-            Err("There was an error fetching the teams".to_string())
-        })?
+        .handle_response_errors("fetching the teams")?
         .json::<Vec<Team>>()?
         .into_iter()
         .collect();
@@ -255,11 +359,9 @@ pub fn team(id: Uuid, context: &Context) -> FieldResult<Team> {
 
     let team = client
         .get(format!("{}/teams/{}", api, id).as_str())
+        .sign(&context.config.signing_key)
         .send()
-        .handle_response_errors()
-        .unwrap_or_else(|_err| {
-            Err("There was an error fetching the team".to_string())
-        })?
+        .handle_response_errors("fetching the team")?
         .json::<Team>()?;
 
     Ok(team)
@@ -286,12 +388,10 @@ pub fn create_team(input: CreateTeamInput, context: &Context) -> FieldResult<Cre
 
     let team = client
         .post(format!("{}/teams", api).as_str())
-        .json(&input)
+        .header(SERVICE_TOKEN_HEADER, context.service_token()?)
+        .sign_json(&input, &context.config.signing_key)
         .send()
-        .handle_response_errors()
-        .unwrap_or_else(|_err| {
-            Err("There was an error creating the team".to_string())
-        })?
+        .handle_response_errors("creating the team")?
         .json::<Team>()?;
 
     Ok(CreateTeamResponse { team })
@@ -320,12 +420,10 @@ pub fn update_team(input: UpdateTeamInput, context: &Context) -> FieldResult<Upd
 
     let team = client
         .put(format!("{}/teams/{}", api, input.id).as_str())
-        .json(&input)
+        .header(SERVICE_TOKEN_HEADER, context.service_token()?)
+        .sign_json(&input, &context.config.signing_key)
         .send()
-        .handle_response_errors()
-        .unwrap_or_else(|_err| {
-            Err("There was an error updating the team".to_string())
-        })?
+        .handle_response_errors("updating the team")?
         .json::<Team>()?;
 
     Ok(UpdateTeamResponse { team })
@@ -347,11 +445,75 @@ pub fn delete_team(input: DeleteTeamInput, context: &Context) -> FieldResult<Del
 
     client
         .delete(format!("{}/teams/{}", api, input.id).as_str())
+        .header(SERVICE_TOKEN_HEADER, context.service_token()?)
+        .sign(&context.config.signing_key)
         .send()
-        .handle_response_errors()
-        .unwrap_or_else(|_err| {
-            Err("There was an error deleting the team".to_string())
-        })?;
+        .handle_response_errors("deleting the team")?;
 
     Ok(DeleteTeamResponse { success: true })
 }
+
+#[derive(Clone, Copy, Debug, Deserialize, GraphQLEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScoringFormat {
+    Standard,
+    /// Half a point per reception
+    Half,
+    /// A point per reception
+    Ppr,
+}
+
+impl ScoringFormat {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ScoringFormat::Standard => "standard",
+            ScoringFormat::Half => "half",
+            ScoringFormat::Ppr => "ppr",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Ranking {
+    pub player_id: Uuid,
+    pub scoring_format: ScoringFormat,
+    pub position: String,
+    pub overall_rank: i32,
+    pub position_rank: i32,
+    pub tier: i32,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct RankingWithPlayer {
+    pub ranking: Ranking,
+    pub player: Player,
+}
+
+#[juniper::object(Context = Context, name = "Ranking")]
+impl RankingWithPlayer {
+    fn scoring_format(&self) -> ScoringFormat { self.ranking.scoring_format }
+    /// The position the player is ranked at, i.e. QB or RB
+    fn position(&self) -> &str { &self.ranking.position }
+    fn overall_rank(&self) -> i32 { self.ranking.overall_rank }
+    fn position_rank(&self) -> i32 { self.ranking.position_rank }
+    fn tier(&self) -> i32 { self.ranking.tier }
+    fn player(&self) -> &Player { &self.player }
+}
+
+pub fn rankings(format: ScoringFormat, position: Option<String>, context: &Context) -> FieldResult<Vec<RankingWithPlayer>> {
+    let api = &context.config.players_api_host;
+    let client = reqwest::blocking::Client::new();
+
+    let mut request = client.get(format!("{}/rankings/{}", api, format.as_str()).as_str());
+    if let Some(position) = position {
+        request = request.query(&[("position", position)]);
+    }
+
+    let rankings = request
+        .sign(&context.config.signing_key)
+        .send()
+        .handle_response_errors("fetching the rankings")?
+        .json::<Vec<RankingWithPlayer>>()?;
+
+    Ok(rankings)
+}
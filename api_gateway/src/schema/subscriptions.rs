@@ -0,0 +1,23 @@
+use juniper::FieldResult;
+use uuid::Uuid;
+
+use super::Context;
+use super::players_api::{Player, Team};
+use crate::subscriptions::ChangeEvent;
+
+/// Gets the player when the event is an update to the player. Null otherwise
+pub fn player_updated(id: Uuid, context: &Context) -> FieldResult<Option<Player>> {
+    match context.event() {
+        Some(ChangeEvent::PlayerUpdated(player)) if player.id == id => Ok(Some(player.clone())),
+        _ => Ok(None),
+    }
+}
+
+/// Gets the team when the event is an update to the team, or to any team
+/// when no id is given. Null otherwise
+pub fn team_updated(id: Option<Uuid>, context: &Context) -> FieldResult<Option<Team>> {
+    match context.event() {
+        Some(ChangeEvent::TeamUpdated(team)) if id.map_or(true, |id| team.id == id) => Ok(Some(team.clone())),
+        _ => Ok(None),
+    }
+}
@@ -0,0 +1,239 @@
+/// Pushes changes from the players API to clients over websockets. The
+/// gateway follows the players API's event stream and runs each subscription
+/// again whenever there is a change
+
+use futures::channel::mpsc::{channel, Receiver, Sender};
+use juniper::parser::{Lexer, Token};
+use juniper::{InputValue, Variables};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::error::Error;
+use std::io::{self, BufRead, BufReader};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use crate::schema::players_api::{Player, Team};
+use crate::schema::{create_subscription_schema, Config, Context, SubscriptionSchema};
+use crate::signing::SignRequest;
+
+pub mod session;
+
+/// How long to wait before following the event stream again when it ends
+const RECONNECT_AFTER: Duration = Duration::from_secs(5);
+
+/// The header the players API reads the id of the last event the gateway got from
+const LAST_EVENT_ID_HEADER: &str = "Last-Event-ID";
+
+/// How many events can wait for a subscription. Subscriptions that fall
+/// further behind are dropped so a slow client cannot use up the memory
+const SUBSCRIBER_BUFFER: usize = 100;
+
+/// A change published by the players API. Sent as `{ "type": "PlayerUpdated", "data": Player }`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", content = "data")]
+pub enum ChangeEvent {
+    PlayerUpdated(Player),
+    TeamUpdated(Team),
+}
+
+/// The payload of a graphql-ws `start` message
+#[derive(Clone, Debug, Deserialize)]
+pub struct SubscriptionRequest {
+    pub query: String,
+    #[serde(rename = "operationName")]
+    pub operation_name: Option<String>,
+    pub variables: Option<InputValue>,
+}
+
+impl SubscriptionRequest {
+    fn variables(&self) -> Variables {
+        self.variables
+            .as_ref()
+            .and_then(|variables| {
+                variables.to_object_value().map(|variables| {
+                    variables.into_iter()
+                        .map(|(name, value)| (name.to_owned(), value.clone()))
+                        .collect()
+                })
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// The subscription schema and the sessions waiting for change events. Shared
+/// by all the workers
+pub struct Subscriptions {
+    pub schema: SubscriptionSchema,
+    subscribers: Mutex<Vec<Sender<ChangeEvent>>>,
+    /// Sent when following the event stream again so no events are missed
+    last_event_id: Mutex<Option<String>>,
+}
+
+impl Default for Subscriptions {
+    fn default() -> Self {
+        Self {
+            schema: create_subscription_schema(),
+            subscribers: Mutex::new(Vec::new()),
+            last_event_id: Mutex::new(None),
+        }
+    }
+}
+
+impl Subscriptions {
+    /// The events end when the subscriber falls more than `SUBSCRIBER_BUFFER`
+    /// events behind
+    pub fn subscribe(&self) -> Receiver<ChangeEvent> {
+        let (sender, receiver) = channel(SUBSCRIBER_BUFFER);
+        self.subscribers.lock().unwrap().push(sender);
+
+        receiver
+    }
+
+    pub fn publish(&self, event: ChangeEvent) {
+        // Sessions that were closed or are too far behind are dropped here
+        let mut subscribers = self.subscribers.lock().unwrap();
+        *subscribers = subscribers
+            .drain(..)
+            .filter_map(|mut subscriber| subscriber.try_send(event.clone()).ok().map(|_| subscriber))
+            .collect();
+    }
+
+    /// Follows the players API's event stream, publishing each event. Blocks
+    /// forever so it is meant to be run on its own thread
+    pub fn listen(&self, config: &Config) {
+        loop {
+            if let Err(err) = self.follow_events(config) {
+                log::error!("Could not read the players API events: {}", err);
+            }
+
+            thread::sleep(RECONNECT_AFTER);
+        }
+    }
+
+    fn follow_events(&self, config: &Config) -> Result<(), Box<dyn Error>> {
+        // The stream stays open as long as the players API is up
+        let client = reqwest::blocking::Client::builder().timeout(None).build()?;
+        let mut request = client.get(format!("{}/events", config.players_api_host).as_str());
+        if let Some(last_event_id) = self.last_event_id.lock().unwrap().clone() {
+            request = request.header(LAST_EVENT_ID_HEADER, last_event_id);
+        }
+
+        let response = request
+            .sign(&config.signing_key)
+            .send()?
+            .error_for_status()?;
+
+        self.read_events(BufReader::new(response))?;
+        Ok(())
+    }
+
+    /// Reads [Server-Sent Events](https://html.spec.whatwg.org/multipage/server-sent-events.html)
+    /// until the stream ends, publishing each change event. Events the gateway
+    /// does not know about are skipped
+    pub fn read_events<R: BufRead>(&self, reader: R) -> io::Result<()> {
+        let mut id = None;
+        let mut data = Vec::new();
+
+        for line in reader.lines() {
+            let line = line?;
+
+            // A blank line ends the event
+            if line.is_empty() {
+                if let Ok(event) = serde_json::from_str(&data.join("\n")) {
+                    self.publish(event);
+                }
+                if let Some(id) = id.take() {
+                    *self.last_event_id.lock().unwrap() = Some(id);
+                }
+                data.clear();
+            } else if line.starts_with("id:") {
+                id = Some(line["id:".len()..].trim().to_string());
+            } else if line.starts_with("data:") {
+                data.push(line["data:".len()..].trim_start().to_string());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// juniper cannot parse subscription operations against a schema without a
+/// subscription type, so the `subscription` keywords are swapped for `query`
+fn as_query(query: &str) -> String {
+    let mut result = String::with_capacity(query.len());
+    let mut copied = 0;
+    // The lexer counts positions in chars, which are not byte offsets once
+    // the query has non-ASCII characters in it
+    let offsets = query
+        .char_indices()
+        .map(|(offset, _char)| offset)
+        .chain(std::iter::once(query.len()))
+        .collect::<Vec<_>>();
+    // Operations are only defined outside of selection sets and arguments
+    let mut nesting = 0;
+
+    for token in Lexer::new(query) {
+        let token = match token {
+            Ok(token) => token,
+            // Leave the rest for juniper to report
+            Err(_) => break,
+        };
+
+        match token.item {
+            Token::CurlyOpen | Token::ParenOpen | Token::BracketOpen => nesting += 1,
+            Token::CurlyClose | Token::ParenClose | Token::BracketClose => nesting -= 1,
+            Token::Name("subscription") if nesting == 0 => {
+                result.push_str(&query[copied..offsets[token.start.index()]]);
+                result.push_str("query");
+                copied = offsets[token.end.index()];
+            },
+            _ => {},
+        }
+    }
+    result.push_str(&query[copied..]);
+
+    result
+}
+
+/// Runs the subscription. Returns the payload to send to the client, which is
+/// None when the subscription is not for the event the context has
+pub fn execute(schema: &SubscriptionSchema, request: &SubscriptionRequest, context: &Context) -> Option<Value> {
+    let query = as_query(&request.query);
+    let result = juniper::execute(
+        &query,
+        request.operation_name.as_deref(),
+        schema,
+        &request.variables(),
+        context,
+    );
+
+    match result {
+        Ok((data, errors)) => {
+            // Every subscription field is null when the event is not for them
+            let matched = data
+                .as_object_value()
+                .map_or(true, |data| data.iter().any(|(_, value)| !value.is_null()));
+
+            if errors.is_empty() {
+                if matched {
+                    Some(json!({ "data": data }))
+                } else {
+                    None
+                }
+            } else {
+                Some(json!({ "data": data, "errors": errors }))
+            }
+        },
+        Err(err) => Some(json!({ "errors": err })),
+    }
+}
+
+/// Checks the subscription can be run before the client is subscribed. Runs
+/// the subscription without an event, which only gets errors back
+pub fn validate(schema: &SubscriptionSchema, request: &SubscriptionRequest, context: &Context) -> Result<(), Value> {
+    match execute(schema, request, context) {
+        Some(payload) => Err(payload["errors"].clone()),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,268 @@
+/// The websocket transport for subscriptions. Speaks the
+/// [graphql-ws protocol](https://github.com/apollographql/subscriptions-transport-ws/blob/master/PROTOCOL.md)
+
+use actix::{Actor, ActorContext, AsyncContext, SpawnHandle, StreamHandler};
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use actix_web_actors::ws;
+use futures::stream::StreamExt;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use super::{execute, validate, SubscriptionRequest, Subscriptions};
+use crate::api_keys::{ApiKey, ApiKeys};
+use crate::auth::{Auth, AuthUser};
+use crate::query_limits::QueryLimits;
+use crate::schema::Context;
+
+/// The websocket subprotocol graphql-ws clients ask for
+const PROTOCOL: &str = "graphql-ws";
+
+/// How often a keep alive message is sent so proxies do not close idle sockets
+const KEEP_ALIVE_EVERY: Duration = Duration::from_secs(15);
+
+/// How many subscriptions one websocket can run at once
+const MAX_SUBSCRIPTIONS: usize = 20;
+
+/// A message from the client
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    ConnectionInit { payload: Option<Value> },
+    Start { id: String, payload: SubscriptionRequest },
+    Stop { id: String },
+    ConnectionTerminate,
+}
+
+/// The result of one of the session's subscriptions for a change event. None
+/// when the event is not for what the client subscribed to
+struct SubscriptionEvent {
+    id: String,
+    payload: Option<Value>,
+}
+
+/// One websocket connection. Each subscription the client starts gets its own
+/// stream of change events
+pub struct SubscriptionSession {
+    subscriptions: Arc<Subscriptions>,
+    auth: Arc<Auth>,
+    api_keys: Arc<ApiKeys>,
+    query_limits: QueryLimits,
+    /// The API key the websocket was opened with
+    api_key: Option<ApiKey>,
+    /// The token sent with the upgrade request. Clients that cannot set
+    /// headers send it in the `connection_init` payload instead
+    token: Option<String>,
+    /// None until the client sends `connection_init`
+    user: Option<Option<AuthUser>>,
+    /// The subscriptions that are running by the id the client gave them
+    running: HashMap<String, SpawnHandle>,
+}
+
+impl SubscriptionSession {
+    /// Sends a message with a payload to the client
+    fn send(&self, ctx: &mut ws::WebsocketContext<Self>, message_type: &str, id: Option<&str>, payload: Option<Value>) {
+        let mut message = json!({ "type": message_type });
+        if let Some(id) = id {
+            message["id"] = json!(id);
+        }
+        if let Some(payload) = payload {
+            message["payload"] = payload;
+        }
+
+        ctx.text(message.to_string());
+    }
+
+    fn context(&self, user: Option<AuthUser>) -> Context {
+        Context::new(crate::get_config(), self.auth.clone(), self.api_keys.clone(), user, self.api_key.clone())
+    }
+
+    fn connection_init(&mut self, payload: Option<Value>, ctx: &mut ws::WebsocketContext<Self>) {
+        // The connection is set up once, so a repeated init does not start
+        // another keep alive or change the user
+        if self.user.is_some() {
+            let payload = json!({ "message": "connection_init was already sent" });
+            return self.send(ctx, "error", None, Some(payload));
+        }
+
+        // Requests made with an API key are not made for a user, same as /graphql
+        let token = payload
+            .as_ref()
+            .and_then(|payload| payload.get("authorization").or_else(|| payload.get("Authorization")))
+            .and_then(|value| value.as_str())
+            .and_then(|value| {
+                if value.starts_with("Bearer ") {
+                    Some(value["Bearer ".len()..].trim().to_string())
+                } else {
+                    None
+                }
+            })
+            .or_else(|| self.token.clone())
+            .filter(|_token| self.api_key.is_none());
+
+        let user = match token.map(|token| self.auth.authenticate_until(&token)) {
+            Some(Ok((user, expires_at))) => {
+                // The session stops being the user's when the token expires.
+                // Clients connect again with a new token
+                let expires_in = expires_at.duration_since(SystemTime::now()).unwrap_or_default();
+                ctx.run_later(expires_in, |session, ctx| {
+                    session.send(ctx, "connection_error", None, Some(json!({ "message": "Token expired" })));
+                    ctx.close(Some(ws::CloseReason {
+                        code: ws::CloseCode::Policy,
+                        description: Some("Token expired".to_string()),
+                    }));
+                    ctx.stop();
+                });
+                Some(user)
+            },
+            Some(Err(err)) => {
+                self.send(ctx, "connection_error", None, Some(json!({ "message": err.to_string() })));
+                ctx.close(None);
+                ctx.stop();
+                return;
+            },
+            None => None,
+        };
+        self.user = Some(user);
+
+        self.send(ctx, "connection_ack", None, None);
+        self.send(ctx, "ka", None, None);
+        ctx.run_interval(KEEP_ALIVE_EVERY, |session, ctx| session.send(ctx, "ka", None, None));
+    }
+
+    fn start(&mut self, id: String, request: SubscriptionRequest, ctx: &mut ws::WebsocketContext<Self>) {
+        let user = match &self.user {
+            Some(user) => user.clone(),
+            None => {
+                let errors = json!([{ "message": "connection_init must be sent before starting a subscription" }]);
+                return self.send(ctx, "error", Some(&id), Some(errors));
+            },
+        };
+
+        // Starting a subscription with an id that is running replaces it
+        self.stop(&id, ctx);
+
+        if self.running.len() >= MAX_SUBSCRIPTIONS {
+            let errors = json!([{ "message": format!("A connection can run at most {} subscriptions", MAX_SUBSCRIPTIONS) }]);
+            return self.send(ctx, "error", Some(&id), Some(errors));
+        }
+
+        if let Err(errors) = self.query_limits.check(&self.subscriptions.schema, &request.query, request.operation_name.as_deref()) {
+            let errors = errors.iter().map(|err| err.to_json()).collect::<Vec<_>>();
+            return self.send(ctx, "error", Some(&id), Some(json!(errors)));
+        }
+        let context = self.context(user);
+        if let Err(errors) = validate(&self.subscriptions.schema, &request, &context) {
+            return self.send(ctx, "error", Some(&id), Some(errors));
+        }
+
+        // Resolvers can call the other services so they are run off the
+        // session's thread. The events of a subscription are run one at a
+        // time so they are sent in order
+        let subscriptions = self.subscriptions.clone();
+        let events = {
+            let id = id.clone();
+            self.subscriptions
+                .subscribe()
+                .then(move |event| {
+                    let subscriptions = subscriptions.clone();
+                    let request = request.clone();
+                    let context = context.clone().with_event(event);
+                    web::block(move || Ok::<_, ()>(execute(&subscriptions.schema, &request, &context)))
+                })
+                .map(move |result| SubscriptionEvent { id: id.clone(), payload: result.ok().and_then(|payload| payload) })
+        };
+        let handle = ctx.add_stream(events);
+        self.running.insert(id, handle);
+    }
+
+    fn stop(&mut self, id: &str, ctx: &mut ws::WebsocketContext<Self>) {
+        if let Some(handle) = self.running.remove(id) {
+            ctx.cancel_future(handle);
+        }
+    }
+}
+
+impl Actor for SubscriptionSession {
+    type Context = ws::WebsocketContext<Self>;
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for SubscriptionSession {
+    fn handle(&mut self, message: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        let text = match message {
+            Ok(ws::Message::Text(text)) => text,
+            Ok(ws::Message::Ping(message)) => return ctx.pong(&message),
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                return ctx.stop();
+            },
+            Ok(_) => return,
+            Err(_) => return ctx.stop(),
+        };
+
+        match serde_json::from_str(&text) {
+            Ok(ClientMessage::ConnectionInit { payload }) => self.connection_init(payload, ctx),
+            Ok(ClientMessage::Start { id, payload }) => self.start(id, payload, ctx),
+            Ok(ClientMessage::Stop { id }) => {
+                self.stop(&id, ctx);
+                self.send(ctx, "complete", Some(&id), None);
+            },
+            Ok(ClientMessage::ConnectionTerminate) => {
+                ctx.close(None);
+                ctx.stop();
+            },
+            Err(err) => {
+                let payload = json!({ "message": format!("Could not read message: {}", err) });
+                self.send(ctx, "error", None, Some(payload));
+            },
+        }
+    }
+}
+
+impl StreamHandler<SubscriptionEvent> for SubscriptionSession {
+    fn handle(&mut self, item: SubscriptionEvent, ctx: &mut Self::Context) {
+        if !self.running.contains_key(&item.id) {
+            return;
+        }
+        if let Some(payload) = item.payload {
+            self.send(ctx, "data", Some(&item.id), Some(payload));
+        }
+    }
+
+    /// Stopping a subscription cancels its events without finishing them, so
+    /// they only end when the session fell too far behind. The client has
+    /// missed events by then and needs to connect again
+    fn finished(&mut self, ctx: &mut Self::Context) {
+        ctx.close(Some(ws::CloseReason {
+            code: ws::CloseCode::Again,
+            description: Some("Too many events are waiting to be sent".to_string()),
+        }));
+        ctx.stop();
+    }
+}
+
+/// Opens the websocket for subscriptions. The API key is checked by the
+/// ApiKeyAuth middleware and the user is authenticated on `connection_init`
+pub async fn subscriptions(
+    req: HttpRequest,
+    stream: web::Payload,
+    subscriptions: web::Data<Arc<Subscriptions>>,
+    auth: web::Data<Arc<Auth>>,
+    api_keys: web::Data<Arc<ApiKeys>>,
+    query_limits: web::Data<QueryLimits>,
+) -> Result<HttpResponse, Error> {
+    let session = SubscriptionSession {
+        subscriptions: subscriptions.get_ref().clone(),
+        auth: auth.get_ref().clone(),
+        api_keys: api_keys.get_ref().clone(),
+        query_limits: *query_limits.get_ref(),
+        api_key: req.extensions().get::<ApiKey>().cloned(),
+        token: crate::bearer_token(&req).map(|token| token.to_string()),
+        user: None,
+        running: HashMap::new(),
+    };
+
+    ws::start_with_protocols(session, &[PROTOCOL], &req, stream)
+}
@@ -0,0 +1,146 @@
+mod common;
+
+#[cfg(test)]
+mod api_keys_tests {
+    use crate::common::{
+        call_api_key_request, create_access_token, create_api_keys, create_auth, create_rate_limiter,
+        get_body, ADMIN_EMAIL,
+    };
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    use api_gateway::api_keys::ApiKeyScope;
+    use api_gateway::schema::create_schema;
+
+    fn graphql_request(query: &str) -> TestRequest {
+        TestRequest::post()
+            .uri("/graphql")
+            .peer_addr("10.0.0.1:5000".parse().unwrap())
+            .set_json(&json!({ "query": query }))
+    }
+
+    #[actix_rt::test]
+    async fn test_admin_can_issue_and_revoke_api_keys() {
+        let schema = Arc::new(create_schema());
+        let auth = create_auth();
+        let api_keys = create_api_keys();
+        let access_token = create_access_token(&auth, ADMIN_EMAIL);
+
+        let request = graphql_request(r#"
+            mutation {
+                issueApiKey(input: { name: "Partner", scopes: [READ_PLAYERS] }) {
+                    key
+                    apiKey {
+                        id
+                        name
+                        scopes
+                        revoked
+                    }
+                }
+            }
+        "#).header("Authorization", format!("Bearer {}", access_token));
+        let response = call_api_key_request(schema.clone(), auth.clone(), api_keys.clone(), create_rate_limiter(100), request).await;
+        let result = get_body(response).await;
+
+        let issued = &result["data"]["issueApiKey"];
+        assert_eq!(issued["apiKey"]["name"], "Partner");
+        assert_eq!(issued["apiKey"]["scopes"], json!(["READ_PLAYERS"]));
+        assert_eq!(issued["apiKey"]["revoked"], false);
+
+        let key = issued["key"].as_str().unwrap();
+        let api_key = api_keys.authenticate(key).unwrap();
+        assert_eq!(api_key.scopes, vec![ApiKeyScope::ReadPlayers]);
+
+        let request = graphql_request(&format!(r#"
+            mutation {{
+                revokeApiKey(input: {{ id: "{}" }}) {{
+                    revoked
+                }}
+            }}
+        "#, api_key.id)).header("Authorization", format!("Bearer {}", access_token));
+        let response = call_api_key_request(schema, auth, api_keys.clone(), create_rate_limiter(100), request).await;
+        let result = get_body(response).await;
+
+        assert_eq!(result["data"]["revokeApiKey"]["revoked"], true);
+        assert!(api_keys.authenticate(key).is_err());
+    }
+
+    #[actix_rt::test]
+    async fn test_api_key_is_limited_to_its_scopes() {
+        let schema = Arc::new(create_schema());
+        let api_keys = create_api_keys();
+        let (_, key) = api_keys.issue("Partner", vec![ApiKeyScope::ReadPlayers], Uuid::new_v4()).unwrap();
+
+        let request = graphql_request(r#"
+            query {
+                rankings(format: PPR) {
+                    overallRank
+                }
+            }
+        "#).header("X-Api-Key", key);
+        let response = call_api_key_request(schema, create_auth(), api_keys, create_rate_limiter(100), request).await;
+        let result = get_body(response).await;
+
+        assert_eq!(result["errors"][0]["message"], "This API key does not have access to this");
+    }
+
+    #[actix_rt::test]
+    async fn test_invalid_api_key_is_unauthorized() {
+        let schema = Arc::new(create_schema());
+
+        let request = graphql_request("query { teams { id } }").header("X-Api-Key", "ffk_not_a_key");
+        let response = call_api_key_request(schema, create_auth(), create_api_keys(), create_rate_limiter(100), request).await;
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_rt::test]
+    async fn test_requests_are_rate_limited() {
+        let schema = Arc::new(create_schema());
+        let auth = create_auth();
+        let api_keys = create_api_keys();
+        let rate_limiter = create_rate_limiter(2);
+        let (_, key) = api_keys.issue("Partner", vec![ApiKeyScope::ReadPlayers], Uuid::new_v4()).unwrap();
+
+        let mut statuses = Vec::new();
+        for _ in 0..3 {
+            let request = graphql_request("query { me { email } }").header("X-Api-Key", key.clone());
+            let response = call_api_key_request(schema.clone(), auth.clone(), api_keys.clone(), rate_limiter.clone(), request).await;
+            let remaining = response.headers().get("RateLimit-Remaining").unwrap().to_str().unwrap().to_string();
+            statuses.push((response.status(), remaining));
+
+            if response.status() == StatusCode::TOO_MANY_REQUESTS {
+                assert!(response.headers().contains_key("Retry-After"));
+                let result: Value = get_body(response).await;
+                assert_eq!(result["errors"][0]["message"], "Too many requests");
+            }
+        }
+
+        assert_eq!(statuses, vec![
+            (StatusCode::OK, "1".to_string()),
+            (StatusCode::OK, "0".to_string()),
+            (StatusCode::TOO_MANY_REQUESTS, "0".to_string()),
+        ]);
+
+        // Requests with a key are limited by IP as well, so the IP is out of
+        // tokens for requests without the key too
+        let request = graphql_request("query { me { email } }");
+        let response = call_api_key_request(schema.clone(), auth.clone(), api_keys.clone(), rate_limiter.clone(), request).await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        // and the key is out of tokens from another IP
+        let request = graphql_request("query { me { email } }")
+            .peer_addr("10.0.0.2:5000".parse().unwrap())
+            .header("X-Api-Key", key.clone());
+        let response = call_api_key_request(schema.clone(), auth.clone(), api_keys.clone(), rate_limiter.clone(), request).await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        let request = graphql_request("query { me { email } }").peer_addr("10.0.0.2:5000".parse().unwrap());
+        let response = call_api_key_request(schema, auth, api_keys, rate_limiter, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get("RateLimit-Limit").unwrap(), "2");
+    }
+}
@@ -0,0 +1,266 @@
+mod common;
+
+#[cfg(test)]
+mod auth_tests {
+    use crate::common::{create_access_token, create_auth, get_auth_response, get_response, ADMIN_EMAIL};
+    use actix_web::http::StatusCode;
+    use mockito::{mock, Matcher};
+    use serde_json::json;
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime};
+    use uuid::Uuid;
+
+    use api_gateway::auth::store::{InMemoryUserStore, RefreshToken, UserStore};
+    use api_gateway::auth::{AuthError, Role};
+    use api_gateway::schema::create_schema;
+
+    #[actix_rt::test]
+    async fn test_signup_returns_tokens_for_me() {
+        let schema = Arc::new(create_schema());
+        let auth = create_auth();
+
+        let payload = json!({
+            "query": r#"
+                mutation {
+                    signup(input: { email: "Dak@Example.com", password: "password123" }) {
+                        accessToken
+                    }
+                }
+            "#,
+        });
+        let (status, result) = get_auth_response(schema.clone(), auth.clone(), payload, None).await;
+        assert!(status.is_success());
+
+        let access_token = result["data"]["signup"]["accessToken"].as_str().unwrap().to_string();
+        let payload = json!({
+            "query": "query { me { email } }",
+        });
+        let (status, result) = get_auth_response(schema, auth, payload, Some(&access_token)).await;
+
+        assert!(status.is_success());
+        assert_eq!(result, json!({
+            "data": {
+                "me": {
+                    "email": "dak@example.com",
+                }
+            }
+        }));
+    }
+
+    #[actix_rt::test]
+    async fn test_signup_never_makes_an_admin() {
+        let auth = create_auth();
+
+        let tokens = auth.signup("owner@example.com", "password123").unwrap();
+        assert_eq!(tokens.user.role, Role::User);
+
+        // The admin's email cannot be taken over either
+        assert_eq!(auth.signup(ADMIN_EMAIL, "password123").unwrap_err(), AuthError::EmailTaken);
+    }
+
+    #[actix_rt::test]
+    async fn test_login_rejects_wrong_password() {
+        let schema = Arc::new(create_schema());
+        let auth = create_auth();
+        create_access_token(&auth, "test@example.com");
+
+        let payload = json!({
+            "query": r#"
+                mutation {
+                    login(input: { email: "test@example.com", password: "wrong password" }) {
+                        accessToken
+                    }
+                }
+            "#,
+        });
+        let (_, result) = get_auth_response(schema, auth, payload, None).await;
+
+        assert_eq!(result["errors"][0]["message"], "Invalid email or password");
+        assert_eq!(result["errors"][0]["extensions"]["code"], "UNAUTHORIZED");
+    }
+
+    #[actix_rt::test]
+    async fn test_login_rejects_unknown_email_like_wrong_password() {
+        let schema = Arc::new(create_schema());
+
+        let payload = json!({
+            "query": r#"
+                mutation {
+                    login(input: { email: "nobody@example.com", password: "password123" }) {
+                        accessToken
+                    }
+                }
+            "#,
+        });
+        let (_, result) = get_response(schema, payload).await;
+
+        assert_eq!(result["errors"][0]["message"], "Invalid email or password");
+        assert_eq!(result["errors"][0]["extensions"]["code"], "UNAUTHORIZED");
+    }
+
+    #[actix_rt::test]
+    async fn test_refresh_token_can_only_be_used_once() {
+        let schema = Arc::new(create_schema());
+        let auth = create_auth();
+        let refresh_token = auth.signup("test@example.com", "password123").unwrap().refresh_token;
+
+        let payload = json!({
+            "query": format!(r#"
+                mutation {{
+                    refreshToken(input: {{ refreshToken: "{}" }}) {{
+                        user {{
+                            email
+                        }}
+                    }}
+                }}
+            "#, refresh_token),
+        });
+        let (_, result) = get_auth_response(schema.clone(), auth.clone(), payload.clone(), None).await;
+        assert_eq!(result["data"]["refreshToken"]["user"]["email"], "test@example.com");
+
+        let (_, result) = get_auth_response(schema, auth, payload, None).await;
+        assert_eq!(result["errors"][0]["message"], "Token is invalid or expired");
+    }
+
+    #[actix_rt::test]
+    async fn test_expired_refresh_tokens_are_dropped() {
+        let store = InMemoryUserStore::default();
+        let user_id = Uuid::new_v4();
+        store.insert_refresh_token("expired".to_string(), RefreshToken {
+            user_id,
+            expires_at: SystemTime::now() - Duration::from_secs(1),
+        });
+        store.insert_refresh_token("current".to_string(), RefreshToken {
+            user_id,
+            expires_at: SystemTime::now() + Duration::from_secs(60),
+        });
+
+        assert!(store.take_refresh_token("expired").is_none());
+        assert!(store.take_refresh_token("current").is_some());
+    }
+
+    #[actix_rt::test]
+    async fn test_mutations_require_a_user() {
+        let schema = Arc::new(create_schema());
+
+        let payload = json!({
+            "query": format!(r#"
+                mutation {{
+                    deletePlayer(input: {{ id: "{}" }}) {{
+                        success
+                    }}
+                }}
+            "#, Uuid::new_v4()),
+        });
+        let (_, result) = get_response(schema, payload).await;
+
+        assert_eq!(result["errors"][0]["message"], "You must be logged in");
+        assert_eq!(result["errors"][0]["extensions"]["code"], "UNAUTHORIZED");
+    }
+
+    #[actix_rt::test]
+    async fn test_invalid_token_is_unauthorized() {
+        let schema = Arc::new(create_schema());
+
+        let payload = json!({
+            "query": "query { me { email } }",
+        });
+        let (status, _) = get_auth_response(schema, create_auth(), payload, Some("not a token")).await;
+
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_rt::test]
+    async fn test_authenticate_until_gets_when_the_token_expires() {
+        let auth = create_auth();
+        let access_token = create_access_token(&auth, ADMIN_EMAIL);
+
+        let (user, expires_at) = auth.authenticate_until(&access_token).unwrap();
+
+        assert_eq!(user.email, ADMIN_EMAIL);
+        // Access tokens last 15 minutes and the expiry is in whole seconds
+        let expires_in = expires_at.duration_since(SystemTime::now()).unwrap();
+        assert!(expires_in > Duration::from_secs(14 * 60) && expires_in <= Duration::from_secs(15 * 60));
+    }
+
+    #[actix_rt::test]
+    async fn test_mutations_require_permission() {
+        let schema = Arc::new(create_schema());
+        let auth = create_auth();
+        let access_token = create_access_token(&auth, "test@example.com");
+
+        let payload = json!({
+            "query": format!(r#"
+                mutation {{
+                    deleteTeam(input: {{ id: "{}" }}) {{
+                        success
+                    }}
+                }}
+            "#, Uuid::new_v4()),
+        });
+        let (_, result) = get_auth_response(schema, auth, payload, Some(&access_token)).await;
+
+        assert_eq!(result["errors"][0]["message"], "You do not have permission to do this");
+    }
+
+    #[actix_rt::test]
+    async fn test_admin_mutations_send_service_token() {
+        let schema = Arc::new(create_schema());
+        let auth = create_auth();
+        let access_token = create_access_token(&auth, ADMIN_EMAIL);
+        let team_id = Uuid::new_v4();
+
+        let _m = mock("DELETE", format!("/teams/{}", team_id).as_str())
+            .match_header("x-service-token", Matcher::Any)
+            .with_status(204)
+            .create();
+
+        let payload = json!({
+            "query": format!(r#"
+                mutation {{
+                    deleteTeam(input: {{ id: "{}" }}) {{
+                        success
+                    }}
+                }}
+            "#, team_id),
+        });
+        let (_, result) = get_auth_response(schema, auth, payload, Some(&access_token)).await;
+
+        assert_eq!(result, json!({
+            "data": {
+                "deleteTeam": {
+                    "success": true,
+                }
+            }
+        }));
+    }
+
+    #[actix_rt::test]
+    async fn test_admin_can_set_user_role() {
+        let schema = Arc::new(create_schema());
+        let auth = create_auth();
+        let access_token = create_access_token(&auth, ADMIN_EMAIL);
+        let user = auth.signup("commissioner@example.com", "password123").unwrap().user;
+
+        let payload = json!({
+            "query": format!(r#"
+                mutation {{
+                    setUserRole(input: {{ userId: "{}", role: COMMISSIONER }}) {{
+                        email
+                        role
+                    }}
+                }}
+            "#, user.id),
+        });
+        let (_, result) = get_auth_response(schema, auth, payload, Some(&access_token)).await;
+
+        assert_eq!(result, json!({
+            "data": {
+                "setUserRole": {
+                    "email": "commissioner@example.com",
+                    "role": "COMMISSIONER",
+                }
+            }
+        }));
+    }
+}
@@ -1,30 +1,142 @@
+// Each test file only uses some of these helpers
+#![allow(dead_code)]
+
 use actix_web::{test, App};
 use actix_web::http::StatusCode;
 use actix_web::dev::ServiceResponse;
 use serde_json::Value;
 use std::sync::Arc;
+use std::time::Duration;
 
+use api_gateway::api_keys::store::InMemoryApiKeyStore;
+use api_gateway::api_keys::ApiKeys;
+use api_gateway::auth::store::InMemoryUserStore;
+use api_gateway::auth::{Auth, AuthConfig, Role};
+use api_gateway::persisted_queries::PersistedQueries;
+use api_gateway::query_limits::QueryLimits;
+use api_gateway::rate_limit::store::InMemoryRateLimitStore;
+use api_gateway::rate_limit::{RateLimit, RateLimiter};
 use api_gateway::register;
 use api_gateway::schema::Schema;
+use api_gateway::subscriptions::Subscriptions;
 
-async fn get_body(response: ServiceResponse) -> Value {
+pub async fn get_body(response: ServiceResponse) -> Value {
     let body = actix_web::test::read_body(response).await;
     serde_json::from_str(std::str::from_utf8(&body).expect("utf8 parse error")).expect("json parse error")
 }
 
-async fn call_request(schema: Arc<Schema>, payload: Value) -> ServiceResponse {
+async fn call_request(schema: Arc<Schema>, auth: Arc<Auth>, payload: Value, token: Option<&str>) -> ServiceResponse {
+    let mut request = test::TestRequest::post().uri("/graphql").set_json(&payload);
+    if let Some(token) = token {
+        request = request.header("Authorization", format!("Bearer {}", token));
+    }
+
+    call_api_key_request(schema, auth, create_api_keys(), create_rate_limiter(100), request).await
+}
+
+/// Calls the request with the API keys and rate limits given
+pub async fn call_api_key_request(
+    schema: Arc<Schema>,
+    auth: Arc<Auth>,
+    api_keys: Arc<ApiKeys>,
+    rate_limiter: Arc<RateLimiter>,
+    request: test::TestRequest,
+) -> ServiceResponse {
+    let mut app = test::init_service(App::new()
+        .configure(register(
+            schema,
+            auth,
+            api_keys,
+            rate_limiter,
+            QueryLimits::default(),
+            Arc::new(PersistedQueries::automatic(100)),
+            Arc::new(Subscriptions::default()),
+        ))).await;
+
+    test::call_service(&mut app, request.to_request()).await
+}
+
+/// Calls the request with the query limits and persisted queries given and
+/// gets the status and response
+pub async fn get_query_response(
+    schema: Arc<Schema>,
+    query_limits: QueryLimits,
+    persisted_queries: Arc<PersistedQueries>,
+    payload: Value,
+) -> (StatusCode, Value) {
     let mut app = test::init_service(App::new()
-        .configure(register(schema.clone()))).await;
-    let request = test::TestRequest::post().uri("/graphql").set_json(&payload).to_request();
+        .configure(register(
+            schema,
+            create_auth(),
+            create_api_keys(),
+            create_rate_limiter(100),
+            query_limits,
+            persisted_queries,
+            Arc::new(Subscriptions::default()),
+        ))).await;
+    let request = test::TestRequest::post().uri("/graphql").set_json(&payload);
+
+    let response = test::call_service(&mut app, request.to_request()).await;
+    let status = response.status();
+    let body = get_body(response).await;
 
-    test::call_service(&mut app, request).await
+    (status, body)
+}
+
+/// Email of the admin the auth service is created with, like the one the
+/// gateway creates at startup
+pub const ADMIN_EMAIL: &str = "admin@example.com";
+
+const PASSWORD: &str = "password123";
+
+/// Creates the auth service with only the admin in the user store
+pub fn create_auth() -> Arc<Auth> {
+    let auth = Auth::new(AuthConfig::new("test secret".to_string()), Box::new(InMemoryUserStore::default()));
+    auth.create_user(ADMIN_EMAIL, PASSWORD, Role::Admin).expect("creating the admin failed");
+
+    Arc::new(auth)
+}
+
+/// Creates the API key service with an empty store
+pub fn create_api_keys() -> Arc<ApiKeys> {
+    Arc::new(ApiKeys::new(Box::new(InMemoryApiKeyStore::default())))
+}
+
+/// Creates a rate limiter that allows `capacity` requests per key and per IP
+/// and does not refill during a test
+pub fn create_rate_limiter(capacity: u32) -> Arc<RateLimiter> {
+    let limit = RateLimit {
+        capacity,
+        refill_every: Duration::from_secs(60 * 60),
+    };
+
+    Arc::new(RateLimiter::new(Box::new(InMemoryRateLimitStore::default()), limit, limit))
+}
+
+/// Gets an access token for the user, signing them up when they do not have an account
+pub fn create_access_token(auth: &Auth, email: &str) -> String {
+    auth.login(email, PASSWORD)
+        .or_else(|_| auth.signup(email, PASSWORD))
+        .expect("signup failed")
+        .access_token
 }
 
 /// Calls the request and gets the status and response
 pub async fn get_response(schema: Arc<Schema>, payload: Value) -> (StatusCode, Value) {
-        let response = call_request(schema, payload).await;
-        let status = response.status();
-        let body = get_body(response).await;
+    get_auth_response(schema, create_auth(), payload, None).await
+}
+
+/// Calls the request as the user the access token belongs to and gets the
+/// status and response
+pub async fn get_auth_response(
+    schema: Arc<Schema>,
+    auth: Arc<Auth>,
+    payload: Value,
+    token: Option<&str>,
+) -> (StatusCode, Value) {
+    let response = call_request(schema, auth, payload, token).await;
+    let status = response.status();
+    let body = get_body(response).await;
 
-        (status, body)
+    (status, body)
 }
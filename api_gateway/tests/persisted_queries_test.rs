@@ -0,0 +1,131 @@
+mod common;
+
+#[cfg(test)]
+mod persisted_queries_tests {
+    use crate::common::get_query_response;
+    use actix_web::http::StatusCode;
+    use mockito::mock;
+    use serde_json::{json, Value};
+    use sha2::{Digest, Sha256};
+    use std::sync::Arc;
+
+    use api_gateway::persisted_queries::PersistedQueries;
+    use api_gateway::query_limits::QueryLimits;
+    use api_gateway::schema::create_schema;
+
+    /// Executes without calling the players API and errors as there is no user
+    const QUERY: &str = "query { me { email } }";
+
+    /// Executes without any errors once the players API is mocked
+    const TEAMS_QUERY: &str = "query { teams { id } }";
+
+    fn sha256_hex(query: &str) -> String {
+        Sha256::digest(query.as_bytes()).iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    fn persisted_query(hash: &str) -> Value {
+        json!({
+            "persistedQuery": {
+                "version": 1,
+                "sha256Hash": hash,
+            }
+        })
+    }
+
+    #[actix_rt::test]
+    async fn test_registers_query_on_miss() {
+        let schema = Arc::new(create_schema());
+        let persisted_queries = Arc::new(PersistedQueries::automatic(10));
+        let hash = sha256_hex(TEAMS_QUERY);
+        let _m = mock("GET", "/teams")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body("[]")
+            .create();
+
+        let payload = json!({ "extensions": persisted_query(&hash) });
+        let (status, result) = get_query_response(schema.clone(), QueryLimits::default(), persisted_queries.clone(), payload.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(result["errors"][0]["message"], "PersistedQueryNotFound");
+        assert_eq!(result["errors"][0]["extensions"]["code"], "PERSISTED_QUERY_NOT_FOUND");
+
+        let register = json!({ "query": TEAMS_QUERY, "extensions": persisted_query(&hash) });
+        let (_, result) = get_query_response(schema.clone(), QueryLimits::default(), persisted_queries.clone(), register).await;
+        assert_eq!(result, json!({ "data": { "teams": [] } }));
+
+        let (_, result) = get_query_response(schema, QueryLimits::default(), persisted_queries, payload).await;
+        assert_eq!(result, json!({ "data": { "teams": [] } }));
+    }
+
+    #[actix_rt::test]
+    async fn test_does_not_register_query_with_errors() {
+        let schema = Arc::new(create_schema());
+        let persisted_queries = Arc::new(PersistedQueries::automatic(10));
+        let hash = sha256_hex(QUERY);
+
+        let register = json!({ "query": QUERY, "extensions": persisted_query(&hash) });
+        let (_, result) = get_query_response(schema.clone(), QueryLimits::default(), persisted_queries.clone(), register).await;
+        assert_eq!(result["errors"][0]["message"], "You must be logged in");
+
+        let payload = json!({ "extensions": persisted_query(&hash) });
+        let (_, result) = get_query_response(schema, QueryLimits::default(), persisted_queries, payload).await;
+        assert_eq!(result["errors"][0]["extensions"]["code"], "PERSISTED_QUERY_NOT_FOUND");
+    }
+
+    #[actix_rt::test]
+    async fn test_does_not_register_query_over_the_limits() {
+        let schema = Arc::new(create_schema());
+        let persisted_queries = Arc::new(PersistedQueries::automatic(10));
+        let query = "query { typename: __typename }";
+        let hash = sha256_hex(query);
+        let query_limits = QueryLimits { max_aliases: 0, ..QueryLimits::default() };
+
+        let register = json!({ "query": query, "extensions": persisted_query(&hash) });
+        let (status, _) = get_query_response(schema.clone(), query_limits, persisted_queries.clone(), register).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let payload = json!({ "extensions": persisted_query(&hash) });
+        let (_, result) = get_query_response(schema, QueryLimits::default(), persisted_queries, payload).await;
+        assert_eq!(result["errors"][0]["extensions"]["code"], "PERSISTED_QUERY_NOT_FOUND");
+    }
+
+    #[actix_rt::test]
+    async fn test_rejects_hash_that_does_not_match_query() {
+        let schema = Arc::new(create_schema());
+
+        let payload = json!({ "query": QUERY, "extensions": persisted_query(&sha256_hex(TEAMS_QUERY)) });
+        let (status, result) = get_query_response(schema, QueryLimits::default(), Arc::new(PersistedQueries::automatic(10)), payload).await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(result["errors"][0]["extensions"]["code"], "PERSISTED_QUERY_HASH_MISMATCH");
+    }
+
+    #[actix_rt::test]
+    async fn test_allowlist_only_executes_listed_queries() {
+        let schema = Arc::new(create_schema());
+        let path = std::env::temp_dir().join(format!("persisted_queries_{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&path, json!({ sha256_hex(QUERY): QUERY }).to_string()).unwrap();
+        let persisted_queries = Arc::new(PersistedQueries::allowlist_from_file(&path).unwrap());
+        std::fs::remove_file(&path).unwrap();
+
+        let payload = json!({ "extensions": persisted_query(&sha256_hex(QUERY)) });
+        let (_, result) = get_query_response(schema.clone(), QueryLimits::default(), persisted_queries.clone(), payload).await;
+        assert_eq!(result["errors"][0]["message"], "You must be logged in");
+
+        let payload = json!({ "query": TEAMS_QUERY });
+        let (status, result) = get_query_response(schema, QueryLimits::default(), persisted_queries, payload).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(result["errors"][0]["extensions"]["code"], "PERSISTED_QUERY_NOT_ALLOWED");
+    }
+
+    #[actix_rt::test]
+    async fn test_allowlist_file_must_match_hashes() {
+        let path = std::env::temp_dir().join(format!("persisted_queries_{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&path, json!({ sha256_hex(TEAMS_QUERY): QUERY }).to_string()).unwrap();
+
+        let result = PersistedQueries::allowlist_from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(result.is_err());
+    }
+}
@@ -2,15 +2,16 @@ mod common;
 
 #[cfg(test)]
 mod players_api_tests {
-    use crate::common::get_response;
+    use crate::common::{create_access_token, create_auth, get_auth_response, get_response, ADMIN_EMAIL};
     use fake::{Fake, Faker};
-    use mockito::mock;
+    use mockito::{mock, Matcher};
     use serde_json::json;
     use std::sync::Arc;
     use uuid::Uuid;
 
     use api_gateway::schema::create_schema;
     use api_gateway::schema::players_api::*;
+    use api_gateway::signing::signature;
 
     #[actix_rt::test]
     async fn test_get_players() {
@@ -44,6 +45,7 @@ mod players_api_tests {
                         first_name: Faker.fake(),
                         last_name: Faker.fake(),
                         team_id,
+                        rookie: false,
                         team: None,
                     },
                     team: team_id.map(|id| Team {
@@ -78,5 +80,316 @@ mod players_api_tests {
         }));
     }
 
+    #[actix_rt::test]
+    async fn test_get_player_projection_averages_sources() {
+        let schema = Arc::new(create_schema());
+        let player_id = Uuid::new_v4();
+
+        let payload = json!({
+            "query": format!(r#"
+                query {{
+                    player(id: "{}") {{
+                        projection(season: 2020, week: 1) {{
+                            week
+                            source
+                            points
+                        }}
+                    }}
+                }}
+            "#, player_id),
+        });
+
+        let _player_mock = mock("GET", format!("/players/{}", player_id).as_str())
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(json!({
+                "player": {
+                    "id": player_id,
+                    "first_name": "Alvin",
+                    "last_name": "Kamara",
+                    "team_id": null,
+                    "rookie": false,
+                },
+                "team": null,
+            }).to_string())
+            .create();
+
+        let _projection_mock = mock("GET", "/projections/average")
+            .match_query(Matcher::UrlEncoded("player_id".into(), player_id.to_string()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(json!([{
+                "player_id": player_id,
+                "season": 2020,
+                "week": 1,
+                "points": 21.5,
+                "sources": 2,
+            }]).to_string())
+            .create();
+
+        let (status, result) = get_response(schema, payload).await;
+        assert!(status.is_success());
+        assert_eq!(result, json!({
+            "data": {
+                "player": {
+                    "projection": {
+                        "week": 1,
+                        "source": null,
+                        "points": 21.5,
+                    }
+                }
+            }
+        }));
+    }
+
+    #[actix_rt::test]
+    async fn test_get_rankings() {
+        let schema = Arc::new(create_schema());
+        let player_id = Uuid::new_v4();
+
+        let payload = json!({
+            "query": r#"
+                query {
+                    rankings(format: PPR, position: "WR") {
+                        scoringFormat
+                        overallRank
+                        positionRank
+                        tier
+                        player {
+                            lastName
+                        }
+                    }
+                }
+            "#,
+        });
+
+        let _m = mock("GET", "/rankings/ppr")
+            .match_query(Matcher::UrlEncoded("position".into(), "WR".into()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(json!([{
+                "ranking": {
+                    "player_id": player_id,
+                    "scoring_format": "ppr",
+                    "position": "WR",
+                    "overall_rank": 4,
+                    "position_rank": 1,
+                    "tier": 1,
+                },
+                "player": {
+                    "id": player_id,
+                    "first_name": "Michael",
+                    "last_name": "Thomas",
+                    "team_id": null,
+                    "rookie": false,
+                },
+            }]).to_string())
+            .create();
+
+        let (status, result) = get_response(schema, payload).await;
+        assert!(status.is_success());
+        assert_eq!(result, json!({
+            "data": {
+                "rankings": [{
+                    "scoringFormat": "PPR",
+                    "overallRank": 4,
+                    "positionRank": 1,
+                    "tier": 1,
+                    "player": {
+                        "lastName": "Thomas",
+                    },
+                }]
+            }
+        }));
+    }
+
+    #[actix_rt::test]
+    async fn test_requests_are_signed() {
+        let schema = Arc::new(create_schema());
+
+        let payload = json!({
+            "query": "query { teams { id } }",
+        });
+
+        let _m = mock("GET", "/teams")
+            .match_header("x-signature-key-id", "test")
+            .match_header("x-signature-timestamp", Matcher::Regex("^[0-9]+$".into()))
+            .match_header("x-signature-nonce", Matcher::Regex("^[0-9a-f]{32}$".into()))
+            .match_header("x-signature", Matcher::Regex("^[0-9a-f]{64}$".into()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body("[]")
+            .create();
+
+        let (status, result) = get_response(schema, payload).await;
+        assert!(status.is_success());
+        assert_eq!(result, json!({
+            "data": {
+                "teams": []
+            }
+        }));
+    }
+
+    #[actix_rt::test]
+    async fn test_signature_matches_the_players_api() {
+        // The players API's signing_test checks the same signature
+        let signature = signature(
+            "test secret",
+            "post",
+            "/players?rookie=true",
+            1_583_798_400,
+            "00112233445566778899aabbccddeeff",
+            br#"{"first_name":"Josh"}"#,
+        );
+        assert_eq!(signature, "966679230345861bd3d78e3dc335c560431e182214a1d9c6ff3c398fb6cf8c34");
+    }
+
+    #[actix_rt::test]
+    async fn test_problem_details_are_put_in_error_extensions() {
+        let schema = Arc::new(create_schema());
+        let id = Uuid::new_v4();
+
+        let payload = json!({
+            "query": format!(r#"query {{ player(id: "{}") {{ id }} }}"#, id),
+        });
+
+        let _m = mock("GET", format!("/players/{}", id).as_str())
+            .with_status(404)
+            .with_header("content-type", "application/problem+json")
+            .with_body(json!({
+                "type": "urn:players-api:problem:not_found",
+                "title": "Not Found",
+                "status": 404,
+                "detail": "Player not found",
+                "code": "not_found",
+            }).to_string())
+            .create();
+
+        let (_, result) = get_response(schema, payload).await;
+        assert_eq!(result["errors"][0]["message"], "Player not found");
+        assert_eq!(result["errors"][0]["extensions"], json!({
+            "code": "NOT_FOUND",
+            "upstreamStatus": 404,
+            "upstreamCode": "not_found",
+        }));
+    }
+
+    #[actix_rt::test]
+    async fn test_upstream_errors_without_problem_details_get_a_code() {
+        let schema = Arc::new(create_schema());
+        let id = Uuid::new_v4();
+
+        let payload = json!({
+            "query": format!(r#"query {{ team(id: "{}") {{ id }} }}"#, id),
+        });
+
+        let _m = mock("GET", format!("/teams/{}", id).as_str())
+            .with_status(503)
+            .with_body("Service Unavailable")
+            .create();
+
+        let (_, result) = get_response(schema, payload).await;
+        assert_eq!(result["errors"][0]["message"], "There was an error fetching the team");
+        assert_eq!(result["errors"][0]["extensions"], json!({
+            "code": "UPSTREAM_UNAVAILABLE",
+            "upstreamStatus": 503,
+        }));
+    }
+
+    #[actix_rt::test]
+    async fn test_create_player_without_rookie() {
+        let schema = Arc::new(create_schema());
+        let auth = create_auth();
+        let access_token = create_access_token(&auth, ADMIN_EMAIL);
+        let player_id = Uuid::new_v4();
+
+        let payload = json!({
+            "query": r#"
+                mutation {
+                    createPlayer(input: { firstName: "Joe", lastName: "Burrow" }) {
+                        player { id rookie }
+                    }
+                }
+            "#,
+        });
+
+        let _m = mock("POST", "/players")
+            .match_body(Matcher::Json(json!({
+                "first_name": "Joe",
+                "last_name": "Burrow",
+                "team_id": null,
+            })))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(json!({
+                "id": player_id,
+                "first_name": "Joe",
+                "last_name": "Burrow",
+                "team_id": null,
+                "rookie": false,
+            }).to_string())
+            .create();
+
+        let (_, result) = get_auth_response(schema, auth, payload, Some(&access_token)).await;
+        assert_eq!(result, json!({
+            "data": {
+                "createPlayer": {
+                    "player": {
+                        "id": player_id,
+                        "rookie": false,
+                    }
+                }
+            }
+        }));
+    }
+
+    #[actix_rt::test]
+    async fn test_update_player_without_rookie_keeps_rookie() {
+        let schema = Arc::new(create_schema());
+        let auth = create_auth();
+        let access_token = create_access_token(&auth, ADMIN_EMAIL);
+        let player_id = Uuid::new_v4();
+
+        let payload = json!({
+            "query": format!(r#"
+                mutation {{
+                    updatePlayer(input: {{ id: "{}", firstName: "Joey", lastName: "Burrow" }}) {{
+                        player {{ id rookie }}
+                    }}
+                }}
+            "#, player_id),
+        });
+
+        // The players API keeps the stored value when rookie is not sent
+        let _m = mock("PUT", format!("/players/{}", player_id).as_str())
+            .match_body(Matcher::Json(json!({
+                "first_name": "Joey",
+                "last_name": "Burrow",
+                "team_id": null,
+            })))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(json!({
+                "id": player_id,
+                "first_name": "Joey",
+                "last_name": "Burrow",
+                "team_id": null,
+                "rookie": true,
+            }).to_string())
+            .create();
+
+        let (_, result) = get_auth_response(schema, auth, payload, Some(&access_token)).await;
+        assert_eq!(result, json!({
+            "data": {
+                "updatePlayer": {
+                    "player": {
+                        "id": player_id,
+                        "rookie": true,
+                    }
+                }
+            }
+        }));
+    }
+
     // TODO: fill out more tests
 }
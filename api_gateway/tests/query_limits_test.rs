@@ -0,0 +1,149 @@
+mod common;
+
+#[cfg(test)]
+mod query_limits_tests {
+    use crate::common::get_query_response;
+    use actix_web::http::StatusCode;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    use api_gateway::persisted_queries::PersistedQueries;
+    use api_gateway::query_limits::QueryLimits;
+    use api_gateway::schema::create_schema;
+
+    fn limits() -> QueryLimits {
+        QueryLimits {
+            max_depth: 3,
+            max_complexity: 50,
+            max_aliases: 2,
+            list_size: 10,
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_rejects_deep_queries() {
+        let schema = Arc::new(create_schema());
+
+        let payload = json!({
+            "query": r#"
+                query {
+                    rankings(format: PPR) {
+                        ...RankingFields
+                    }
+                }
+
+                fragment RankingFields on Ranking {
+                    player {
+                        team {
+                            abbreviation
+                        }
+                    }
+                }
+            "#,
+        });
+        let (status, result) = get_query_response(schema, limits(), Arc::new(PersistedQueries::automatic(10)), payload).await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(result["errors"][0]["extensions"], json!({
+            "code": "MAX_DEPTH_EXCEEDED",
+            "max": 3,
+            "actual": 4,
+        }));
+    }
+
+    #[actix_rt::test]
+    async fn test_rejects_complex_queries() {
+        let schema = Arc::new(create_schema());
+
+        // 1 for rankings and 10 times the 4 fields under it
+        let payload = json!({
+            "query": r#"
+                query {
+                    rankings(format: PPR) {
+                        tier
+                        player {
+                            firstName
+                            lastName
+                        }
+                    }
+                }
+            "#,
+        });
+        let (status, _) = get_query_response(schema.clone(), limits(), Arc::new(PersistedQueries::automatic(10)), payload).await;
+        assert_ne!(status, StatusCode::BAD_REQUEST);
+
+        // 1 for rankings and 10 times the 6 fields under it
+        let payload = json!({
+            "query": r#"
+                query {
+                    rankings(format: PPR) {
+                        tier
+                        overallRank
+                        player {
+                            firstName
+                            lastName
+                            rookie
+                        }
+                    }
+                }
+            "#,
+        });
+        let (status, result) = get_query_response(schema, limits(), Arc::new(PersistedQueries::automatic(10)), payload).await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(result["errors"][0]["extensions"], json!({
+            "code": "MAX_COMPLEXITY_EXCEEDED",
+            "max": 50,
+            "actual": 61,
+        }));
+    }
+
+    #[actix_rt::test]
+    async fn test_rejects_too_many_aliases() {
+        let schema = Arc::new(create_schema());
+
+        let payload = json!({
+            "query": r#"
+                query {
+                    a: me { email }
+                    b: me { email }
+                    c: me { email }
+                }
+            "#,
+        });
+        let (status, result) = get_query_response(schema, limits(), Arc::new(PersistedQueries::automatic(10)), payload).await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(result["errors"][0]["message"], "Query has 3 aliases which is more than the maximum of 2");
+        assert_eq!(result["errors"][0]["extensions"]["code"], "MAX_ALIASES_EXCEEDED");
+    }
+
+    #[actix_rt::test]
+    async fn test_introspection_is_not_limited() {
+        let schema = Arc::new(create_schema());
+
+        let payload = json!({
+            "query": r#"
+                query {
+                    __schema {
+                        types {
+                            fields {
+                                type {
+                                    ofType {
+                                        ofType {
+                                            name
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            "#,
+        });
+        let (status, result) = get_query_response(schema, limits(), Arc::new(PersistedQueries::automatic(10)), payload).await;
+
+        assert!(status.is_success());
+        assert!(result["errors"].is_null());
+    }
+}
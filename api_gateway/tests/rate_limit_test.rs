@@ -0,0 +1,38 @@
+#[cfg(test)]
+mod rate_limit_tests {
+    use std::time::Duration;
+
+    use api_gateway::rate_limit::RateLimit;
+    use api_gateway::rate_limit::store::{InMemoryRateLimitStore, RateLimitStore};
+
+    const LIMIT: RateLimit = RateLimit {
+        capacity: 1,
+        refill_every: Duration::from_secs(60 * 60),
+    };
+
+    #[test]
+    fn test_new_keys_share_a_bucket_when_the_store_is_full() {
+        let store = InMemoryRateLimitStore::new(1);
+
+        assert!(store.take("ip:10.0.0.1", &LIMIT).allowed);
+        // There is no room for the other IPs' own buckets
+        assert!(store.take("ip:10.0.0.2", &LIMIT).allowed);
+        assert!(!store.take("ip:10.0.0.3", &LIMIT).allowed);
+        assert!(!store.take("ip:10.0.0.1", &LIMIT).allowed);
+    }
+
+    #[test]
+    fn test_least_recently_seen_full_bucket_is_replaced() {
+        let limit = RateLimit { capacity: 1, refill_every: Duration::from_millis(10) };
+        let store = InMemoryRateLimitStore::new(2);
+
+        assert!(store.take("ip:10.0.0.1", &limit).allowed);
+        assert!(store.take("ip:10.0.0.2", &LIMIT).allowed);
+        std::thread::sleep(Duration::from_millis(20));
+
+        // 10.0.0.1's bucket is full again so 10.0.0.3 gets its own bucket
+        assert!(store.take("ip:10.0.0.3", &LIMIT).allowed);
+        assert!(!store.take("ip:10.0.0.3", &LIMIT).allowed);
+        assert!(!store.take("ip:10.0.0.2", &LIMIT).allowed);
+    }
+}
@@ -0,0 +1,148 @@
+mod common;
+
+#[cfg(test)]
+mod subscriptions_tests {
+    use crate::common::{create_api_keys, create_auth};
+    use serde_json::json;
+    use std::io::Cursor;
+    use std::time::SystemTime;
+    use uuid::Uuid;
+
+    use api_gateway::api_keys::{ApiKey, ApiKeyScope};
+    use api_gateway::get_config;
+    use api_gateway::schema::players_api::{Player, Team};
+    use api_gateway::schema::Context;
+    use api_gateway::subscriptions::{execute, validate, ChangeEvent, SubscriptionRequest, Subscriptions};
+
+    fn request(query: &str) -> SubscriptionRequest {
+        serde_json::from_value(json!({ "query": query })).unwrap()
+    }
+
+    fn create_context(api_key: Option<ApiKey>) -> Context {
+        Context::new(get_config(), create_auth(), create_api_keys(), None, api_key)
+    }
+
+    fn player(id: Uuid) -> Player {
+        Player {
+            id,
+            first_name: "Drew".to_string(),
+            last_name: "Brees".to_string(),
+            team_id: None,
+            rookie: false,
+            team: None,
+        }
+    }
+
+    #[test]
+    fn test_read_events_publishes_change_events() {
+        let subscriptions = Subscriptions::default();
+        let mut subscriber = subscriptions.subscribe();
+
+        let team_id = Uuid::new_v4();
+        let stream = format!(
+            "event: Unknown\ndata: {{\"type\":\"Unknown\",\"data\":{{}}}}\n\n\
+             event: TeamUpdated\ndata: {{\"type\":\"TeamUpdated\",\"data\":{{\"id\":\"{}\",\"display_name\":\"New Orleans Saints\",\"abbreviation\":\"NO\"}}}}\n\n",
+            team_id,
+        );
+        subscriptions.read_events(Cursor::new(stream)).unwrap();
+
+        match subscriber.try_next() {
+            Ok(Some(ChangeEvent::TeamUpdated(team))) => assert_eq!(team.id, team_id),
+            event => panic!("Expected a TeamUpdated event, got {:?}", event),
+        }
+        assert!(subscriber.try_next().is_err());
+    }
+
+    #[test]
+    fn test_subscriber_that_falls_behind_is_dropped() {
+        let subscriptions = Subscriptions::default();
+        let mut subscriber = subscriptions.subscribe();
+
+        let published = 500;
+        for _ in 0..published {
+            subscriptions.publish(ChangeEvent::PlayerUpdated(player(Uuid::new_v4())));
+        }
+
+        let mut received = 0;
+        loop {
+            match subscriber.try_next() {
+                Ok(Some(_event)) => received += 1,
+                // The events end instead of waiting for more
+                Ok(None) => break,
+                Err(err) => panic!("Expected the events to end, got {:?}", err),
+            }
+        }
+        assert!(received < published);
+    }
+
+    #[test]
+    fn test_subscription_with_non_ascii_characters() {
+        let subscriptions = Subscriptions::default();
+        let request = request("# Équipes\nsubscription { teamUpdated { abbreviation } }");
+
+        assert!(validate(&subscriptions.schema, &request, &create_context(None)).is_ok());
+    }
+
+    #[test]
+    fn test_player_updated_is_only_sent_for_the_player() {
+        let subscriptions = Subscriptions::default();
+        let id = Uuid::new_v4();
+        let request = request(&format!(r#"
+            subscription {{
+                playerUpdated(id: "{}") {{
+                    id
+                    lastName
+                }}
+            }}
+        "#, id));
+
+        let context = create_context(None).with_event(ChangeEvent::PlayerUpdated(player(id)));
+        let payload = execute(&subscriptions.schema, &request, &context);
+        assert_eq!(payload, Some(json!({
+            "data": {
+                "playerUpdated": {
+                    "id": id.to_string(),
+                    "lastName": "Brees",
+                },
+            },
+        })));
+
+        let context = create_context(None).with_event(ChangeEvent::PlayerUpdated(player(Uuid::new_v4())));
+        assert_eq!(execute(&subscriptions.schema, &request, &context), None);
+
+        let team = Team {
+            id: Uuid::new_v4(),
+            display_name: "New Orleans Saints".to_string(),
+            abbreviation: "NO".to_string(),
+        };
+        let context = create_context(None).with_event(ChangeEvent::TeamUpdated(team));
+        assert_eq!(execute(&subscriptions.schema, &request, &context), None);
+    }
+
+    #[test]
+    fn test_validate_checks_api_key_scopes() {
+        let subscriptions = Subscriptions::default();
+        let request = request("subscription { teamUpdated { abbreviation } }");
+
+        assert!(validate(&subscriptions.schema, &request, &create_context(None)).is_ok());
+
+        let api_key = ApiKey {
+            id: Uuid::new_v4(),
+            name: "Partner".to_string(),
+            scopes: vec![ApiKeyScope::ReadRankings],
+            created_by: Uuid::new_v4(),
+            created_at: SystemTime::now(),
+            revoked: false,
+        };
+        let errors = validate(&subscriptions.schema, &request, &create_context(Some(api_key))).unwrap_err();
+        assert_eq!(errors[0]["message"], "This API key does not have access to this");
+    }
+
+    #[test]
+    fn test_validate_rejects_unknown_fields() {
+        let subscriptions = Subscriptions::default();
+        let request = request("subscription { players { id } }");
+
+        assert!(validate(&subscriptions.schema, &request, &create_context(None)).is_err());
+    }
+}
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+alter table players
+add rookie boolean not null default false;
@@ -0,0 +1,12 @@
+-- Your SQL goes here
+-- week 0 holds the season long projection
+create table projections (
+  player_id uuid not null references players(id) on delete cascade,
+  season integer not null,
+  week integer not null,
+  source varchar not null,
+  points double precision not null,
+  created_at timestamp default now(),
+  updated_at timestamp,
+  primary key (player_id, season, week, source)
+)
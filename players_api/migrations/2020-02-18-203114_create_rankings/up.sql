@@ -0,0 +1,12 @@
+-- Your SQL goes here
+create table rankings (
+  player_id uuid not null references players(id) on delete cascade,
+  scoring_format varchar not null,
+  position varchar not null,
+  overall_rank integer not null,
+  position_rank integer not null,
+  tier integer not null,
+  created_at timestamp default now(),
+  updated_at timestamp,
+  primary key (player_id, scoring_format)
+)
@@ -0,0 +1,7 @@
+-- Your SQL goes here
+create table events (
+  id bigserial primary key,
+  event_type varchar not null,
+  data jsonb not null,
+  created_at timestamp not null default now()
+)
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+drop table webhook_dead_letters;
+drop table webhook_deliveries;
+drop table webhooks
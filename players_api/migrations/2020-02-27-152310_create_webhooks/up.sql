@@ -0,0 +1,33 @@
+-- Your SQL goes here
+create table webhooks (
+  id uuid primary key default gen_random_uuid(),
+  url varchar not null,
+  event_types text[] not null,
+  secret varchar not null,
+  created_at timestamp default now(),
+  updated_at timestamp
+);
+
+create table webhook_deliveries (
+  id bigserial primary key,
+  webhook_id uuid not null references webhooks(id) on delete cascade,
+  event_id bigint not null references events(id),
+  status varchar not null default 'pending',
+  attempts integer not null default 0,
+  next_attempt_at timestamp not null default now(),
+  response_status integer,
+  last_error varchar,
+  created_at timestamp not null default now(),
+  updated_at timestamp
+);
+
+create index webhook_deliveries_pending on webhook_deliveries (next_attempt_at) where status = 'pending';
+
+create table webhook_dead_letters (
+  id bigserial primary key,
+  delivery_id bigint not null references webhook_deliveries(id) on delete cascade,
+  webhook_id uuid not null references webhooks(id) on delete cascade,
+  event_id bigint not null references events(id),
+  last_error varchar not null,
+  created_at timestamp not null default now()
+);
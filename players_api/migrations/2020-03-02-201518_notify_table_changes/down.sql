@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+drop trigger teams_notify_table_change on teams;
+drop trigger players_notify_table_change on players;
+drop function notify_table_change();
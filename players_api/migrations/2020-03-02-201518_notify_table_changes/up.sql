@@ -0,0 +1,30 @@
+-- Your SQL goes here
+-- Tells every replica listening on table_changes which row changed so they
+-- can evict it from their caches. Sent when the transaction commits
+create or replace function notify_table_change() returns trigger as $$
+declare
+  changed_id uuid;
+begin
+  if TG_OP = 'DELETE' then
+    changed_id := OLD.id;
+  else
+    changed_id := NEW.id;
+  end if;
+
+  perform pg_notify('table_changes', json_build_object(
+    'table', TG_TABLE_NAME,
+    'operation', TG_OP,
+    'id', changed_id
+  )::text);
+
+  return null;
+end;
+$$ language plpgsql;
+
+create trigger players_notify_table_change
+after insert or update or delete on players
+for each row execute procedure notify_table_change();
+
+create trigger teams_notify_table_change
+after insert or update or delete on teams
+for each row execute procedure notify_table_change();
@@ -0,0 +1,19 @@
+-- This file should undo anything in `up.sql`
+drop index events_created_at;
+
+-- Dead letters of deleted events cannot point at them again
+delete from webhook_dead_letters where event_id is null or delivery_id is null;
+
+alter table webhook_dead_letters
+  drop constraint webhook_dead_letters_delivery_id_fkey,
+  add constraint webhook_dead_letters_delivery_id_fkey foreign key (delivery_id) references webhook_deliveries(id) on delete cascade,
+  drop constraint webhook_dead_letters_event_id_fkey,
+  add constraint webhook_dead_letters_event_id_fkey foreign key (event_id) references events(id),
+  alter column delivery_id set not null,
+  alter column event_id set not null,
+  drop column data,
+  drop column event_type;
+
+alter table webhook_deliveries
+  drop constraint webhook_deliveries_event_id_fkey,
+  add constraint webhook_deliveries_event_id_fkey foreign key (event_id) references events(id);
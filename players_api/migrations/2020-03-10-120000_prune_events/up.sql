@@ -0,0 +1,28 @@
+-- Your SQL goes here
+-- Old events are deleted, so their deliveries go with them
+alter table webhook_deliveries
+  drop constraint webhook_deliveries_event_id_fkey,
+  add constraint webhook_deliveries_event_id_fkey foreign key (event_id) references events(id) on delete cascade;
+
+-- Dead letters are kept after their event and delivery are deleted, so they
+-- have their own copy of the event
+alter table webhook_dead_letters
+  add column event_type varchar,
+  add column data jsonb;
+
+update webhook_dead_letters
+  set event_type = events.event_type, data = events.data
+  from events
+  where events.id = webhook_dead_letters.event_id;
+
+alter table webhook_dead_letters
+  alter column event_type set not null,
+  alter column data set not null,
+  alter column event_id drop not null,
+  alter column delivery_id drop not null,
+  drop constraint webhook_dead_letters_event_id_fkey,
+  add constraint webhook_dead_letters_event_id_fkey foreign key (event_id) references events(id) on delete set null,
+  drop constraint webhook_dead_letters_delivery_id_fkey,
+  add constraint webhook_dead_letters_delivery_id_fkey foreign key (delivery_id) references webhook_deliveries(id) on delete set null;
+
+create index events_created_at on events (created_at);
@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+alter table rankings drop constraint rankings_scoring_format_overall_rank_key;
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+alter table rankings
+  add constraint rankings_scoring_format_overall_rank_key unique (scoring_format, overall_rank);
@@ -0,0 +1,27 @@
+-- The players, teams and events tables from the Postgres migrations, for the
+-- `sqlite` feature. Ids are uuids stored as text
+create table if not exists teams (
+  id text primary key not null,
+  display_name text not null,
+  abbreviation text not null,
+  created_at timestamp default current_timestamp,
+  updated_at timestamp
+);
+
+create table if not exists players (
+  id text primary key not null,
+  first_name text not null,
+  last_name text not null,
+  created_at timestamp default current_timestamp,
+  updated_at timestamp,
+  team_id text references teams(id),
+  rookie boolean not null default 0
+);
+
+-- Event ids are never reused, so clients can tell which events they missed
+create table if not exists events (
+  id integer primary key autoincrement,
+  event_type text not null,
+  data text not null,
+  created_at timestamp not null default current_timestamp
+);
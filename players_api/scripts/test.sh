@@ -15,4 +15,5 @@ done
 
 DATABASE_URL=$DATABASE_URL_TEST diesel migration run
 
-cargo test -- --test-threads=5;
+# Arguments are passed to cargo, i.e. `./scripts/test.sh --features sqlite`
+cargo test "$@" -- --test-threads=5;
@@ -0,0 +1,67 @@
+/// Checks the service credential the API gateway sends along with writes
+
+use actix_web::dev::Payload;
+use actix_web::{web, Error, FromRequest, HttpRequest};
+use futures::future::{ready, Ready};
+use jsonwebtoken::{decode, DecodingKey, Validation};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::AppData;
+use crate::common::ApiError;
+
+/// The header the service token is sent in
+pub const SERVICE_TOKEN_HEADER: &str = "X-Service-Token";
+
+/// The claims of the service token. The token is a JWT signed by the gateway
+/// with the shared `SERVICE_TOKEN_SECRET`
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ServiceClaims {
+    /// The id of the user the gateway is making the request for
+    pub sub: Uuid,
+    pub role: String,
+    pub exp: u64,
+}
+
+/// Extractor for requests made on behalf of an admin. Add it to the arguments
+/// of a handler to only let admins through
+///
+/// 401 is returned when the service token is missing or invalid
+///
+/// 403 is returned when the user is not an admin
+#[derive(Debug)]
+pub struct AdminCredential {
+    pub user_id: Uuid,
+}
+
+fn reject(req: &HttpRequest, err: ApiError) -> Error {
+    let problem = err.to_problem().with_instance(req.path());
+    actix_web::error::InternalError::from_response(err, problem.to_response()).into()
+}
+
+impl FromRequest for AdminCredential {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let secret = match req.app_data::<web::Data<AppData>>() {
+            Some(data) => data.service_token_secret.clone(),
+            None => return ready(Err(reject(req, ApiError::Internal("Something went wrong".to_string())))),
+        };
+
+        let claims = req.headers()
+            .get(SERVICE_TOKEN_HEADER)
+            .and_then(|token| token.to_str().ok())
+            .and_then(|token| {
+                decode::<ServiceClaims>(token, &DecodingKey::from_secret(secret.as_bytes()), &Validation::default()).ok()
+            })
+            .map(|token| token.claims);
+
+        ready(match claims {
+            Some(claims) if claims.role == "admin" => Ok(AdminCredential { user_id: claims.sub }),
+            Some(_) => Err(reject(req, ApiError::Forbidden("Only admins can do this".to_string()))),
+            None => Err(reject(req, ApiError::Unauthorized("Missing or invalid service token".to_string()))),
+        })
+    }
+}
@@ -0,0 +1,154 @@
+/// Keeps the players and teams that are fetched by id in memory. Every
+/// replica evicts the entries for a row when Postgres notifies it the row
+/// changed, see [notifications](../notifications/index.html). Entries also
+/// expire and the caches are bounded, so a missed notification does not
+/// keep a stale record around forever
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::players::models::PlayerWithTeam;
+use crate::teams::models::Team;
+
+/// How many records a cache keeps before it evicts the oldest
+const MAX_ENTRIES: usize = 10_000;
+
+/// How long a record is kept, in case the notification that evicts it is missed
+const TTL: Duration = Duration::from_secs(5 * 60);
+
+struct CacheEntry<T> {
+    record: T,
+    inserted_at: Instant,
+}
+
+/// Records by their id
+pub struct RecordCache<T> {
+    entries: RwLock<HashMap<Uuid, CacheEntry<T>>>,
+    /// Goes up with every eviction, see [insert_unless_evicted](#method.insert_unless_evicted)
+    generation: AtomicU64,
+    max_entries: usize,
+    ttl: Duration,
+}
+
+impl<T> Default for RecordCache<T> {
+    fn default() -> Self {
+        Self::new(MAX_ENTRIES, TTL)
+    }
+}
+
+impl<T> RecordCache<T> {
+    pub fn new(max_entries: usize, ttl: Duration) -> Self {
+        Self {
+            entries: RwLock::new(HashMap::new()),
+            generation: AtomicU64::new(0),
+            max_entries,
+            ttl,
+        }
+    }
+}
+
+impl<T: Clone> RecordCache<T> {
+    pub fn get(&self, id: &Uuid) -> Option<T> {
+        self.entries
+            .read()
+            .unwrap()
+            .get(id)
+            .filter(|entry| entry.inserted_at.elapsed() < self.ttl)
+            .map(|entry| entry.record.clone())
+    }
+
+    pub fn insert(&self, id: Uuid, record: T) {
+        let mut entries = self.entries.write().unwrap();
+        self.insert_entry(&mut entries, id, record);
+    }
+
+    /// Take this before reading a record from the database and pass it to
+    /// [insert_unless_evicted](#method.insert_unless_evicted)
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    /// Inserts the record unless anything was evicted since the generation
+    /// was taken, as the record read could be older than the change that
+    /// was evicted
+    pub fn insert_unless_evicted(&self, id: Uuid, record: T, generation: u64) {
+        let mut entries = self.entries.write().unwrap();
+        if self.generation() == generation {
+            self.insert_entry(&mut entries, id, record);
+        }
+    }
+
+    fn insert_entry(&self, entries: &mut HashMap<Uuid, CacheEntry<T>>, id: Uuid, record: T) {
+        if entries.len() >= self.max_entries && !entries.contains_key(&id) {
+            let ttl = self.ttl;
+            entries.retain(|_, entry| entry.inserted_at.elapsed() < ttl);
+
+            if entries.len() >= self.max_entries {
+                let oldest = entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.inserted_at)
+                    .map(|(id, _)| *id);
+                if let Some(oldest) = oldest {
+                    entries.remove(&oldest);
+                }
+            }
+        }
+
+        entries.insert(id, CacheEntry { record, inserted_at: Instant::now() });
+    }
+
+    pub fn evict(&self, id: &Uuid) {
+        let mut entries = self.entries.write().unwrap();
+        entries.remove(id);
+        self.generation.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Evicts every record the predicate is true for
+    pub fn evict_where<F: Fn(&T) -> bool>(&self, predicate: F) {
+        let mut entries = self.entries.write().unwrap();
+        entries.retain(|_, entry| !predicate(&entry.record));
+        self.generation.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn clear(&self) {
+        let mut entries = self.entries.write().unwrap();
+        entries.clear();
+        self.generation.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// The caches shared by all the workers
+#[derive(Default)]
+pub struct Caches {
+    /// Players with their team, as sent by `GET /players/{id}`
+    pub players: RecordCache<PlayerWithTeam>,
+    pub teams: RecordCache<Team>,
+}
+
+impl Caches {
+    pub fn clear(&self) {
+        self.players.clear();
+        self.teams.clear();
+    }
+
+    pub fn evict_player(&self, id: &Uuid) {
+        self.players.evict(id);
+    }
+
+    /// Players are cached with their team so they are evicted too
+    pub fn evict_team(&self, id: &Uuid) {
+        self.teams.evict(id);
+        self.players.evict_where(|player| player.team.as_ref().map_or(false, |team| team.id == *id));
+    }
+}
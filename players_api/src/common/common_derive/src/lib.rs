@@ -1,3 +1,5 @@
+#![recursion_limit = "256"]
+
 extern crate proc_macro;
 
 use quote::quote;
@@ -17,13 +19,12 @@ fn impl_deserialize_error_handler(ast: &syn::DeriveInput) -> TokenStream {
     let gen = quote! {
         impl crate::common::DeserializeErrorHandler for #name {
             fn handle_deserialize(cfg: actix_web::web::JsonConfig) -> actix_web::web::JsonConfig {
-                cfg.error_handler(|err, _req| {
-                    let err_message = format!("{}", &err);
-                    actix_web::error::InternalError::from_response(
-                        err, actix_web::HttpResponse::BadRequest().json(crate::common::JsonError::<bool> {
-                            message: err_message,
-                            data: None,
-                        })).into()
+                cfg.error_handler(|err, req| {
+                    let problem = crate::common::ApiError::Validation(format!("{}", &err))
+                        .to_problem()
+                        .with_instance(req.path());
+
+                    actix_web::error::InternalError::from_response(err, problem.to_response()).into()
                 })
             }
         }
@@ -31,3 +32,557 @@ fn impl_deserialize_error_handler(ast: &syn::DeriveInput) -> TokenStream {
 
     gen.into()
 }
+
+/// Derives `crate::common::Validate` from `#[validate(...)]` attributes on the
+/// fields, i.e.
+///
+/// ```ignore
+/// #[validate(length(min = 1, max = 64))]
+/// #[validate(regex = "^[A-Z]+$", message = "must be upper case letters")]
+/// #[validate(range(min = 1, max = 17))]
+/// ```
+///
+/// `Option` fields are only checked when they are set
+#[proc_macro_derive(Validate, attributes(validate))]
+pub fn validate_derive(input: TokenStream) -> TokenStream {
+    let ast = syn::parse(input).unwrap();
+
+    impl_validate(&ast)
+}
+
+fn impl_validate(ast: &syn::DeriveInput) -> TokenStream {
+    let name = &ast.ident;
+    let fields = match &ast.data {
+        syn::Data::Struct(syn::DataStruct { fields: syn::Fields::Named(fields), .. }) => &fields.named,
+        _ => panic!("Validate can only be derived for structs with named fields"),
+    };
+
+    let checks = fields.iter().flat_map(|field| {
+        let ident = field.ident.as_ref().unwrap();
+        let field_name = ident.to_string();
+        let value = if is_option(&field.ty) {
+            quote! { self.#ident.as_ref() }
+        } else {
+            quote! { Some(&self.#ident) }
+        };
+
+        field.attrs.iter()
+            .filter_map(|attr| attr.interpret_meta())
+            .filter(|meta| meta.name() == "validate")
+            .map(move |meta| {
+                let (setup, condition, message) = validation(&meta, &field_name);
+
+                quote! {
+                    if let Some(value) = #value {
+                        #setup
+                        if #condition {
+                            errors.push(crate::common::FieldError::new(#field_name, &#message));
+                        }
+                    }
+                }
+            })
+    }).collect::<Vec<_>>();
+
+    let gen = quote! {
+        impl crate::common::Validate for #name {
+            fn validate(&self) -> Vec<crate::common::FieldError> {
+                let mut errors = Vec::new();
+                #(#checks)*
+
+                errors
+            }
+        }
+    };
+
+    gen.into()
+}
+
+/// What the condition needs, the condition that makes the field not valid and
+/// the message sent when it is
+fn validation(
+    meta: &syn::Meta,
+    field_name: &str,
+) -> (proc_macro2::TokenStream, proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    let nested: Vec<&syn::Meta> = match meta {
+        syn::Meta::List(list) => list.nested.iter().map(|nested| match nested {
+            syn::NestedMeta::Meta(meta) => meta,
+            syn::NestedMeta::Literal(_) => panic!("validate expects length(...), range(...) or regex = \"...\""),
+        }).collect(),
+        _ => panic!("validate expects length(...), range(...) or regex = \"...\""),
+    };
+
+    let message = nested.iter().find_map(|meta| match meta {
+        syn::Meta::NameValue(name_value) if name_value.ident == "message" => Some(lit_str(&name_value.lit)),
+        _ => None,
+    });
+
+    let rule = nested.iter()
+        .find(|meta| meta.name() != "message")
+        .unwrap_or_else(|| panic!("validate on {} has no rule", field_name));
+
+    let mut setup = quote! {};
+    let (condition, default_message) = match rule {
+        syn::Meta::List(list) if list.ident == "length" => {
+            let (min, max) = bounds(list);
+            let length = quote! { crate::common::HasLength::length(value) };
+
+            match (min, max) {
+                (Some(min), Some(max)) => (
+                    quote! { #length < #min || #length > #max },
+                    quote! { format!("{} must be between {} and {} characters long", #field_name, #min, #max) },
+                ),
+                (Some(min), None) => (
+                    quote! { #length < #min },
+                    quote! { format!("{} must be at least {} characters long", #field_name, #min) },
+                ),
+                (None, Some(max)) => (
+                    quote! { #length > #max },
+                    quote! { format!("{} must be at most {} characters long", #field_name, #max) },
+                ),
+                (None, None) => panic!("length on {} needs a min or a max", field_name),
+            }
+        },
+        syn::Meta::List(list) if list.ident == "range" => {
+            let (min, max) = bounds(list);
+
+            match (min, max) {
+                (Some(min), Some(max)) => (
+                    quote! { *value < #min || *value > #max },
+                    quote! { format!("{} must be between {} and {}", #field_name, #min, #max) },
+                ),
+                (Some(min), None) => (
+                    quote! { *value < #min },
+                    quote! { format!("{} must be at least {}", #field_name, #min) },
+                ),
+                (None, Some(max)) => (
+                    quote! { *value > #max },
+                    quote! { format!("{} must be at most {}", #field_name, #max) },
+                ),
+                (None, None) => panic!("range on {} needs a min or a max", field_name),
+            }
+        },
+        syn::Meta::NameValue(name_value) if name_value.ident == "regex" => {
+            let pattern = lit_str(&name_value.lit);
+            // A bad pattern fails the build instead of the first request
+            if let Err(err) = regex::Regex::new(&pattern) {
+                panic!("regex on {} is not valid: {}", field_name, err);
+            }
+
+            // Compiled the first time it is used, then shared
+            setup = quote! {
+                static REGEX: once_cell::sync::Lazy<regex::Regex> =
+                    once_cell::sync::Lazy::new(|| regex::Regex::new(#pattern).unwrap());
+            };
+            (
+                quote! { !REGEX.is_match(value) },
+                quote! { format!("{} must match {}", #field_name, #pattern) },
+            )
+        },
+        _ => panic!("validate on {} expects length(...), range(...) or regex = \"...\"", field_name),
+    };
+
+    match message {
+        Some(message) => (setup, condition, quote! { #message.to_string() }),
+        None => (setup, condition, default_message),
+    }
+}
+
+/// The `min` and `max` of `length(...)` or `range(...)`
+fn bounds(list: &syn::MetaList) -> (Option<syn::Lit>, Option<syn::Lit>) {
+    let mut min = None;
+    let mut max = None;
+
+    for nested in list.nested.iter() {
+        match nested {
+            syn::NestedMeta::Meta(syn::Meta::NameValue(name_value)) if name_value.ident == "min" => {
+                min = Some(name_value.lit.clone());
+            },
+            syn::NestedMeta::Meta(syn::Meta::NameValue(name_value)) if name_value.ident == "max" => {
+                max = Some(name_value.lit.clone());
+            },
+            _ => panic!("{} expects min = ... and max = ...", list.ident),
+        }
+    }
+
+    (min, max)
+}
+
+fn lit_str(lit: &syn::Lit) -> String {
+    match lit {
+        syn::Lit::Str(lit) => lit.value(),
+        _ => panic!("validate expects a string"),
+    }
+}
+
+fn is_option(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(type_path) => type_path.path.segments.iter().last()
+            .map(|segment| segment.ident == "Option")
+            .unwrap_or(false),
+        _ => false,
+    }
+}
+
+/// Derives the get-all, get-one, create, update and delete handlers for a
+/// diesel model along with a `register` that adds their routes, i.e.
+///
+/// ```ignore
+/// #[derive(Crud, Queryable)]
+/// #[table_name = "teams"]
+/// #[crud(path = "/teams", repository = "teams", create_form = "CreateTeamForm", update_form = "UpdateTeamForm")]
+/// pub struct Team { ... }
+/// ```
+///
+/// The handlers call the repository in `AppData::repositories` named by
+/// `repository`. The `Repository` for `PgRepository<Team>` is derived too.
+/// The model needs an `id: Uuid` and to implement `crate::common::CrudHooks`,
+/// which makes the change events and evicts the caches. A handler can be
+/// swapped for a hand written one with `get_all`, `get_one`, `create`,
+/// `update` or `delete`, i.e. `get_all = "crate::players::get_players"`.
+/// `foreign_key_violation` and `delete_restricted` set the messages sent when
+/// the record refers to one that does not exist, or one still refers to it
+#[proc_macro_derive(Crud, attributes(crud))]
+pub fn crud_derive(input: TokenStream) -> TokenStream {
+    let ast = syn::parse(input).unwrap();
+
+    let mut gen = impl_crud_handlers(&ast);
+    gen.extend(impl_pg_repository(&ast));
+
+    gen
+}
+
+/// The `#[crud(...)]` options
+struct CrudOptions {
+    options: Vec<(String, String)>,
+}
+
+impl CrudOptions {
+    fn new(ast: &syn::DeriveInput) -> Self {
+        Self { options: name_values(&ast.attrs, "crud") }
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        self.options.iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.clone())
+    }
+
+    fn required(&self, key: &str) -> String {
+        self.get(key).unwrap_or_else(|| panic!("Crud needs #[crud({} = \"...\")]", key))
+    }
+
+    fn path(&self, key: &str) -> syn::Path {
+        parse_path(&self.required(key))
+    }
+}
+
+fn parse_path(value: &str) -> syn::Path {
+    syn::parse_str::<syn::Path>(value).unwrap_or_else(|_| panic!("{} is not a path", value))
+}
+
+fn impl_crud_handlers(ast: &syn::DeriveInput) -> TokenStream {
+    let name = &ast.ident;
+    let options = CrudOptions::new(ast);
+    let path = options.required("path");
+    let item_path = format!("{}/{{id}}", path);
+    let repository = syn::Ident::new(&options.required("repository"), proc_macro2::Span::call_site());
+    let create_form = options.path("create_form");
+    let update_form = options.path("update_form");
+
+    let get_all_doc = format!("Gets all the {} records\n\n\
+        # Returns\n\n\
+        200 is returned and sends an array of {}\n\n\
+        500 or 503 is returned when there is a database error", name, name);
+    let get_one_doc = format!("Fetches a {}\n\n\
+        # Returns\n\n\
+        200 is returned if the {} is found and sends it back\n\n\
+        404 is returned when the {} is not found by the given id\n\n\
+        500 or 503 is returned when there is any other database error", name, name, name);
+    let create_doc = format!("Creates a {}\n\n\
+        # Returns\n\n\
+        200 is returned when the creation is successful and sends the created {}. \
+        The `CrudHooks::created_event` is published\n\n\
+        400 is returned when there is a foreign key violation\n\n\
+        401 or 403 is returned when the request is not made for an admin\n\n\
+        422 is returned when the body is not valid. The fields that are not valid are in `errors`\n\n\
+        500 or 503 is returned when there is any other database error", name, name);
+    let update_doc = format!("Updates a {}\n\n\
+        # Returns\n\n\
+        200 is returned when the update was successful and sends the updated {}. \
+        The `CrudHooks::updated_event` is published\n\n\
+        401 or 403 is returned when the request is not made for an admin\n\n\
+        404 is returned when the {} is not found by the given id\n\n\
+        422 is returned when the body is not valid. The fields that are not valid are in `errors`\n\n\
+        500 or 503 is returned when there is any other database error", name, name, name);
+    let delete_doc = format!("Deletes a {}\n\n\
+        # Returns\n\n\
+        204 is returned when the delete was successful or the {} does not exist. \
+        The `CrudHooks::deleted_event` is published when it was deleted\n\n\
+        400 is returned when a record still refers to it\n\n\
+        401 or 403 is returned when the request is not made for an admin\n\n\
+        500 or 503 is returned when there is any other database error", name, name);
+
+    let mut handlers = Vec::new();
+    let mut route = |key: &str, generated: proc_macro2::TokenStream| match options.get(key) {
+        Some(handler) => {
+            let handler = parse_path(&handler);
+            quote! { #handler }
+        },
+        None => {
+            handlers.push(generated);
+            let handler = syn::Ident::new(key, proc_macro2::Span::call_site());
+            quote! { #name::#handler }
+        },
+    };
+
+    let get_all = route("get_all", quote! {
+        #[doc = #get_all_doc]
+        pub async fn get_all(
+            data: actix_web::web::Data<crate::AppData>
+        ) -> Result<actix_web::HttpResponse, crate::common::ApiError> {
+            let repository = data.repositories.#repository.clone();
+            let records = crate::db::block(move || repository.all()).await?;
+
+            Ok(actix_web::HttpResponse::Ok().json(records))
+        }
+    });
+
+    let get_one = route("get_one", quote! {
+        #[doc = #get_one_doc]
+        pub async fn get_one(
+            data: actix_web::web::Data<crate::AppData>,
+            path: actix_web::web::Path<uuid::Uuid>
+        ) -> Result<actix_web::HttpResponse, crate::common::ApiError> {
+            let id = path.into_inner();
+            let cache = <#name as crate::common::CrudHooks>::cache(&data.caches);
+            if let Some(record) = cache.and_then(|cache| cache.get(&id)) {
+                return Ok(actix_web::HttpResponse::Ok().json(record));
+            }
+
+            // A change made while the record is read evicts it before this inserts it
+            let generation = cache.map(|cache| cache.generation());
+            let repository = data.repositories.#repository.clone();
+            let record = crate::db::block(move || repository.find(id)).await?;
+
+            if let (Some(cache), Some(generation)) = (cache, generation) {
+                cache.insert_unless_evicted(id, record.clone(), generation);
+            }
+            Ok(actix_web::HttpResponse::Ok().json(record))
+        }
+    });
+
+    let create = route("create", quote! {
+        #[doc = #create_doc]
+        pub async fn create(
+            data: actix_web::web::Data<crate::AppData>,
+            _admin: crate::auth::AdminCredential,
+            form: crate::common::ValidJson<#create_form>
+        ) -> Result<actix_web::HttpResponse, crate::common::ApiError> {
+            let form = form.into_inner();
+            let repository = data.repositories.#repository.clone();
+            let (record, event) = crate::db::block(move || repository.create(form)).await?;
+
+            if let Some(event) = event {
+                data.events.publish(event);
+            }
+            Ok(actix_web::HttpResponse::Ok().json(record))
+        }
+    });
+
+    let update = route("update", quote! {
+        #[doc = #update_doc]
+        pub async fn update(
+            data: actix_web::web::Data<crate::AppData>,
+            _admin: crate::auth::AdminCredential,
+            path: actix_web::web::Path<uuid::Uuid>,
+            form: crate::common::ValidJson<#update_form>
+        ) -> Result<actix_web::HttpResponse, crate::common::ApiError> {
+            let form = form.into_inner();
+            let id = path.into_inner();
+            let repository = data.repositories.#repository.clone();
+            let (record, event) = crate::db::block(move || repository.update(id, form)).await?;
+
+            <#name as crate::common::CrudHooks>::evict(&data.caches, &id);
+            if let Some(event) = event {
+                data.events.publish(event);
+            }
+            Ok(actix_web::HttpResponse::Ok().json(record))
+        }
+    });
+
+    let delete = route("delete", quote! {
+        #[doc = #delete_doc]
+        pub async fn delete(
+            data: actix_web::web::Data<crate::AppData>,
+            _admin: crate::auth::AdminCredential,
+            path: actix_web::web::Path<uuid::Uuid>
+        ) -> Result<actix_web::HttpResponse, crate::common::ApiError> {
+            let id = path.into_inner();
+            let repository = data.repositories.#repository.clone();
+            let event = crate::db::block(move || repository.delete(id)).await?;
+
+            <#name as crate::common::CrudHooks>::evict(&data.caches, &id);
+            if let Some(event) = event {
+                data.events.publish(event);
+            }
+            Ok(actix_web::HttpResponse::NoContent().finish())
+        }
+    });
+
+    let gen = quote! {
+        impl #name {
+            #(#handlers)*
+
+            /// Adds the routes for the handlers, with the JSON config of the forms
+            pub fn register(config: &mut actix_web::web::ServiceConfig) {
+                use actix_web::FromRequest;
+                use crate::common::DeserializeErrorHandler;
+
+                config
+                    .service(
+                        actix_web::web::resource(#path)
+                        .app_data(
+                            crate::common::ValidJson::<#create_form>::configure(#create_form::handle_deserialize)
+                        )
+                        .route(actix_web::web::get().to(#get_all))
+                        .route(actix_web::web::post().to(#create))
+                    )
+                    .service(
+                        actix_web::web::resource(#item_path)
+                        .app_data(
+                            crate::common::ValidJson::<#update_form>::configure(#update_form::handle_deserialize)
+                        )
+                        .route(actix_web::web::get().to(#get_one))
+                        .route(actix_web::web::put().to(#update))
+                        .route(actix_web::web::delete().to(#delete))
+                    );
+            }
+        }
+    };
+
+    gen.into()
+}
+
+/// The `Repository` for `PgRepository<Model>`. The change events are recorded
+/// in the same transaction as the change
+fn impl_pg_repository(ast: &syn::DeriveInput) -> TokenStream {
+    let name = &ast.ident;
+    let options = CrudOptions::new(ast);
+    let table_name = ast.attrs.iter()
+        .filter_map(|attr| attr.interpret_meta())
+        .find_map(|meta| match meta {
+            syn::Meta::NameValue(name_value) if name_value.ident == "table_name" => Some(lit_str(&name_value.lit)),
+            _ => None,
+        })
+        .unwrap_or_else(|| panic!("Crud on {} needs #[table_name = \"...\"]", name));
+    let table = parse_path(&format!("crate::schema::{}::table", table_name));
+    let create_form = options.path("create_form");
+    let update_form = options.path("update_form");
+    let not_found = format!("{} not found", name);
+
+    let map_err = |key: &str| match options.get(key) {
+        Some(message) => quote! { .map_err(crate::common::ApiError::foreign_key_violation(#message)) },
+        None => quote! { .map_err(crate::common::ApiError::from) },
+    };
+    let map_create_err = map_err("foreign_key_violation");
+    let map_delete_err = map_err("delete_restricted");
+
+    let gen = quote! {
+        impl crate::repository::Repository for crate::repository::PgRepository<#name> {
+            type Record = #name;
+            type CreateForm = #create_form;
+            type UpdateForm = #update_form;
+
+            fn all(&self) -> Result<Vec<#name>, crate::common::ApiError> {
+                use diesel::prelude::*;
+
+                let connection = self.connection()?;
+                Ok(#table.load::<#name>(&connection)?)
+            }
+
+            fn find(&self, id: uuid::Uuid) -> Result<#name, crate::common::ApiError> {
+                use diesel::prelude::*;
+
+                let connection = self.connection()?;
+                #table
+                    .find(id)
+                    .first::<#name>(&connection)
+                    .map_err(crate::common::ApiError::not_found(#not_found))
+            }
+
+            fn create(
+                &self,
+                form: #create_form
+            ) -> Result<(#name, Option<crate::events::StoredEvent>), crate::common::ApiError> {
+                use diesel::prelude::*;
+
+                let connection = self.connection()?;
+                connection.transaction::<_, diesel::result::Error, _>(|| {
+                    let record = diesel::insert_into(#table)
+                        .values(form)
+                        .get_result::<#name>(&connection)?;
+                    let event = match <#name as crate::common::CrudHooks>::created_event(&record) {
+                        Some(event) => Some(crate::events::record(&connection, event)?),
+                        None => None,
+                    };
+
+                    Ok((record, event))
+                })#map_create_err
+            }
+
+            fn update(
+                &self,
+                id: uuid::Uuid,
+                form: #update_form
+            ) -> Result<(#name, Option<crate::events::StoredEvent>), crate::common::ApiError> {
+                use diesel::prelude::*;
+
+                let connection = self.connection()?;
+                connection.transaction::<_, diesel::result::Error, _>(|| {
+                    let record = diesel::update(#table.find(&id))
+                        .set(&form)
+                        .get_result::<#name>(&connection)?;
+                    let event = match <#name as crate::common::CrudHooks>::updated_event(&record) {
+                        Some(event) => Some(crate::events::record(&connection, event)?),
+                        None => None,
+                    };
+
+                    Ok((record, event))
+                }).map_err(crate::common::ApiError::not_found(#not_found))
+            }
+
+            fn delete(&self, id: uuid::Uuid) -> Result<Option<crate::events::StoredEvent>, crate::common::ApiError> {
+                use diesel::prelude::*;
+
+                let connection = self.connection()?;
+                connection.transaction::<_, diesel::result::Error, _>(|| {
+                    match diesel::delete(#table.find(&id)).execute(&connection)? {
+                        0 => Ok(None),
+                        _ => match <#name as crate::common::CrudHooks>::deleted_event(id) {
+                            Some(event) => crate::events::record(&connection, event).map(Some),
+                            None => Ok(None),
+                        },
+                    }
+                })#map_delete_err
+            }
+        }
+    };
+
+    gen.into()
+}
+
+/// The `key = "value"` pairs of the `#[name(...)]` attributes
+fn name_values(attrs: &[syn::Attribute], name: &str) -> Vec<(String, String)> {
+    attrs.iter()
+        .filter_map(|attr| attr.interpret_meta())
+        .filter(|meta| meta.name() == name)
+        .flat_map(|meta| match meta {
+            syn::Meta::List(list) => list.nested.into_iter().map(|nested| match nested {
+                syn::NestedMeta::Meta(syn::Meta::NameValue(name_value)) =>
+                    (name_value.ident.to_string(), lit_str(&name_value.lit)),
+                _ => panic!("{} expects key = \"value\"", name),
+            }).collect::<Vec<_>>(),
+            _ => panic!("{} expects key = \"value\"", name),
+        })
+        .collect()
+}
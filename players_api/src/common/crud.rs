@@ -0,0 +1,38 @@
+/// What the handlers and repositories from `#[derive(Crud)]` do besides the
+/// query. Every method has a default that does nothing so a model only
+/// overrides what it needs
+
+use uuid::Uuid;
+
+use crate::cache::{Caches, RecordCache};
+use crate::events::ChangeEvent;
+
+pub trait CrudHooks: Clone {
+    /// The event recorded when the record is created
+    fn created_event(&self) -> Option<ChangeEvent> {
+        None
+    }
+
+    /// The event recorded when the record is updated
+    fn updated_event(&self) -> Option<ChangeEvent> {
+        None
+    }
+
+    /// The event recorded when the record is deleted
+    fn deleted_event(_id: Uuid) -> Option<ChangeEvent> {
+        None
+    }
+
+    /// The cache fetching a record reads through
+    fn cache(_caches: &Caches) -> Option<&RecordCache<Self>> {
+        None
+    }
+
+    /// Evicts the record, and anything cached with it, after it is updated or
+    /// deleted
+    fn evict(caches: &Caches, id: &Uuid) {
+        if let Some(cache) = Self::cache(caches) {
+            cache.evict(id);
+        }
+    }
+}
@@ -0,0 +1,152 @@
+/// The error the handlers return. Each kind of error is sent as a
+/// [Problem](../struct.Problem.html) with its status code and a `code` that
+/// stays the same so clients can match on it
+
+use actix_web::error::BlockingError;
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use diesel::r2d2::PoolError;
+use diesel::result::{DatabaseErrorKind as DbError, Error as DieselError};
+use std::fmt;
+
+use super::{FieldError, Problem};
+
+#[derive(Debug)]
+pub enum ApiError {
+    /// The record was not found. 404
+    NotFound(String),
+    /// The request body, query string or headers are not valid. 400
+    Validation(String),
+    /// The fields of the request body that are not valid. 422
+    InvalidFields(Vec<FieldError>),
+    /// A record the change refers to does not exist, or a record still refers
+    /// to the one being deleted. 400
+    ForeignKeyViolation(String),
+    /// The request has no credential or the credential is not valid. 401
+    Unauthorized(String),
+    /// The credential is not allowed to make the request. 403
+    Forbidden(String),
+    /// The change would make a duplicate. 409
+    Conflict(String),
+    /// The request body is bigger than is allowed. 413
+    PayloadTooLarge(String),
+    /// No database connection could be made. 503
+    Unavailable(PoolError),
+    /// Any other database error. 500
+    Database(DieselError),
+    /// The API is not set up right. 500
+    Internal(String),
+}
+
+impl ApiError {
+    /// The machine-readable code sent with the error
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::NotFound(_) => "not_found",
+            ApiError::Validation(_) | ApiError::InvalidFields(_) => "validation_failed",
+            ApiError::ForeignKeyViolation(_) => "foreign_key_violation",
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::Conflict(_) => "conflict",
+            ApiError::PayloadTooLarge(_) => "payload_too_large",
+            ApiError::Unavailable(_) => "database_unavailable",
+            ApiError::Database(_) => "database_error",
+            ApiError::Internal(_) => "internal_error",
+        }
+    }
+
+    /// Maps a database error, using `message` when the record was not found
+    pub fn not_found(message: impl Into<String>) -> impl FnOnce(DieselError) -> ApiError {
+        move |err| match err {
+            DieselError::NotFound => ApiError::NotFound(message.into()),
+            err => err.into(),
+        }
+    }
+
+    /// Maps a database error, using `message` when there is a foreign key violation
+    pub fn foreign_key_violation(message: impl Into<String>) -> impl FnOnce(DieselError) -> ApiError {
+        move |err| match err {
+            DieselError::DatabaseError(DbError::ForeignKeyViolation, _) => ApiError::ForeignKeyViolation(message.into()),
+            err => err.into(),
+        }
+    }
+
+    /// The problem details sent for the error
+    pub fn to_problem(&self) -> Problem {
+        let problem = Problem::new(self.status_code(), self.code()).with_detail(self.to_string());
+
+        match self {
+            ApiError::InvalidFields(errors) => problem.with_errors(errors.clone()),
+            _ => problem,
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ApiError::NotFound(message)
+            | ApiError::Validation(message)
+            | ApiError::ForeignKeyViolation(message)
+            | ApiError::Unauthorized(message)
+            | ApiError::Forbidden(message)
+            | ApiError::Conflict(message)
+            | ApiError::PayloadTooLarge(message)
+            | ApiError::Internal(message) => write!(f, "{}", message),
+            ApiError::InvalidFields(errors) => {
+                let fields: Vec<_> = errors.iter().map(|error| error.field.as_str()).collect();
+                write!(f, "Invalid fields: {}", fields.join(", "))
+            },
+            ApiError::Unavailable(err) => write!(f, "The database is unavailable: {}", err),
+            ApiError::Database(err) => write!(f, "Something went wrong: {}", err),
+        }
+    }
+}
+
+impl From<DieselError> for ApiError {
+    fn from(err: DieselError) -> Self {
+        match err {
+            DieselError::NotFound => ApiError::NotFound("Not found".to_string()),
+            DieselError::DatabaseError(DbError::ForeignKeyViolation, info) =>
+                ApiError::ForeignKeyViolation(info.message().to_string()),
+            DieselError::DatabaseError(DbError::UniqueViolation, info) =>
+                ApiError::Conflict(info.message().to_string()),
+            err => ApiError::Database(err),
+        }
+    }
+}
+
+impl From<PoolError> for ApiError {
+    fn from(err: PoolError) -> Self {
+        ApiError::Unavailable(err)
+    }
+}
+
+impl From<BlockingError<ApiError>> for ApiError {
+    fn from(err: BlockingError<ApiError>) -> Self {
+        match err {
+            BlockingError::Error(err) => err,
+            BlockingError::Canceled => ApiError::Internal("The database call was canceled".to_string()),
+        }
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Validation(_) | ApiError::ForeignKeyViolation(_) => StatusCode::BAD_REQUEST,
+            ApiError::InvalidFields(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            ApiError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Database(_) | ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        self.to_problem().to_response()
+    }
+}
@@ -1,11 +1,13 @@
 use actix_web::web;
-use serde::Serialize;
 
-#[derive(Serialize)]
-pub struct JsonError<T> {
-    pub message: String,
-    pub data: Option<T>,
-}
+mod crud;
+mod error;
+mod problem;
+mod validate;
+pub use crud::CrudHooks;
+pub use error::ApiError;
+pub use problem::{FieldError, Problem, PROBLEM_CONTENT_TYPE};
+pub use validate::{HasLength, Validate, ValidJson};
 
 pub trait DeserializeErrorHandler {
     fn handle_deserialize(cfg: web::JsonConfig) -> web::JsonConfig;
@@ -0,0 +1,82 @@
+/// Error responses are [RFC 7807](https://tools.ietf.org/html/rfc7807)
+/// problem details, sent as `application/problem+json`
+
+use actix_web::http::StatusCode;
+use actix_web::HttpResponse;
+use serde::{Deserialize, Serialize};
+
+pub const PROBLEM_CONTENT_TYPE: &str = "application/problem+json";
+
+/// A field of the request that is not valid
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &str, message: &str) -> Self {
+        Self { field: field.to_string(), message: message.to_string() }
+    }
+}
+
+/// The problem details of an error. `code` and `errors` are extension
+/// members: `code` is the same as the end of `type` and `errors` has the
+/// fields that are not valid
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct Problem {
+    /// Identifies the kind of problem, i.e. `urn:players-api:problem:not_found`
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    /// The reason phrase of the status code
+    pub title: String,
+    pub status: u16,
+    /// What went wrong with this request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// The path of the request, when it is known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    pub code: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
+}
+
+impl Problem {
+    pub fn new(status: StatusCode, code: &str) -> Self {
+        Self {
+            problem_type: format!("urn:players-api:problem:{}", code),
+            title: status.canonical_reason().unwrap_or("Error").to_string(),
+            status: status.as_u16(),
+            detail: None,
+            instance: None,
+            code: code.to_string(),
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn with_detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    pub fn with_instance(mut self, instance: impl Into<String>) -> Self {
+        self.instance = Some(instance.into());
+        self
+    }
+
+    pub fn with_errors(mut self, errors: Vec<FieldError>) -> Self {
+        self.errors = errors;
+        self
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+
+    pub fn to_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code())
+            .content_type(PROBLEM_CONTENT_TYPE)
+            .json(self)
+    }
+}
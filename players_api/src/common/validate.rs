@@ -0,0 +1,94 @@
+/// Request bodies are checked after they are deserialized. Handlers take a
+/// [ValidJson](./struct.ValidJson.html) instead of `web::Json` and the body is
+/// rejected with 422 and the fields that are not valid when it fails
+
+use actix_web::dev::Payload;
+use actix_web::{web, FromRequest, HttpRequest};
+use futures::future::{FutureExt, LocalBoxFuture};
+use serde::de::DeserializeOwned;
+use std::ops::Deref;
+
+use super::{ApiError, FieldError};
+
+pub trait Validate {
+    /// The fields that are not valid, empty when the value is valid
+    fn validate(&self) -> Vec<FieldError>;
+}
+
+/// Bulk bodies are valid when every item is. The fields of an item are
+/// prefixed with its index, i.e. `[2].overall_rank`
+impl<T: Validate> Validate for Vec<T> {
+    fn validate(&self) -> Vec<FieldError> {
+        self.iter()
+            .enumerate()
+            .flat_map(|(index, item)| {
+                item.validate().into_iter().map(move |err| FieldError {
+                    field: format!("[{}].{}", index, err.field),
+                    message: err.message,
+                })
+            })
+            .collect()
+    }
+}
+
+/// What `#[validate(length(...))]` counts
+pub trait HasLength {
+    fn length(&self) -> usize;
+}
+
+impl HasLength for String {
+    fn length(&self) -> usize {
+        self.chars().count()
+    }
+}
+
+impl<T> HasLength for Vec<T> {
+    fn length(&self) -> usize {
+        self.len()
+    }
+}
+
+/// A JSON body that has been validated. It is set up with the same
+/// `JsonConfig` as `web::Json`
+pub struct ValidJson<T>(pub T);
+
+impl<T> ValidJson<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for ValidJson<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> FromRequest for ValidJson<T>
+where
+    T: DeserializeOwned + Validate + 'static,
+{
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, actix_web::Error>>;
+    type Config = web::JsonConfig;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let path = req.path().to_string();
+
+        web::Json::<T>::from_request(req, payload)
+            .map(move |result| {
+                let value = result?.into_inner();
+                let errors = value.validate();
+                if errors.is_empty() {
+                    return Ok(ValidJson(value));
+                }
+
+                let err = ApiError::InvalidFields(errors);
+                let response = err.to_problem().with_instance(path).to_response();
+                Err(actix_web::error::InternalError::from_response(err, response).into())
+            })
+            .boxed_local()
+    }
+}
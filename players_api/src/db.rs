@@ -0,0 +1,114 @@
+/// Sets up the database pool and runs the queries. Diesel and r2d2 block, so
+/// handlers run their queries with [with_connection](./fn.with_connection.html),
+/// or call their repository with [block](./fn.block.html), which wait for a
+/// connection and query on the thread pool actix keeps for blocking work
+/// instead of on the workers serving the requests
+
+use actix_web::web;
+use diesel::pg::PgConnection;
+use diesel::r2d2::{ConnectionManager, Pool, PoolError};
+use std::env;
+use std::time::Duration;
+
+use crate::PgPool;
+use crate::common::ApiError;
+
+/// How many connections the pool keeps and how long a request waits for one
+#[derive(Clone, Debug)]
+pub struct PoolConfig {
+    /// The most connections that are open at once
+    pub max_size: u32,
+    /// The fewest idle connections kept open. When `None` it is the same as `max_size`
+    pub min_idle: Option<u32>,
+    /// How long to wait for a connection before the request fails with 503
+    pub connection_timeout: Duration,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_size: 10,
+            min_idle: None,
+            connection_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+impl PoolConfig {
+    /// Reads `DATABASE_POOL_SIZE`, `DATABASE_POOL_MIN_IDLE` and
+    /// `DATABASE_POOL_TIMEOUT_SECONDS`. The defaults are used for the ones that
+    /// are not set
+    ///
+    /// # Panics
+    ///
+    /// Panics if one of them is set but is not a number
+    pub fn from_env() -> Self {
+        let default = Self::default();
+
+        Self {
+            max_size: env_var("DATABASE_POOL_SIZE").unwrap_or(default.max_size),
+            min_idle: env_var("DATABASE_POOL_MIN_IDLE").or(default.min_idle),
+            connection_timeout: env_var("DATABASE_POOL_TIMEOUT_SECONDS")
+                .map(Duration::from_secs)
+                .unwrap_or(default.connection_timeout),
+        }
+    }
+}
+
+/// Parses the environment variable. Empty is the same as not set, like in `.env.sample`
+fn env_var<T: std::str::FromStr>(name: &str) -> Option<T> {
+    env::var(name).ok()
+        .filter(|value| !value.trim().is_empty())
+        .map(|value| value.trim().parse().unwrap_or_else(|_| panic!("{} must be a number", name)))
+}
+
+/// Creates the pool. Fails when the first connections cannot be made
+pub fn build_pool(database_url: &str, config: &PoolConfig) -> Result<PgPool, PoolError> {
+    let manager = ConnectionManager::<PgConnection>::new(database_url);
+
+    Pool::builder()
+        .max_size(config.max_size)
+        .min_idle(config.min_idle)
+        .connection_timeout(config.connection_timeout)
+        .build(manager)
+}
+
+/// Creates a pool for when there is no Postgres, i.e. with the `sqlite` feature
+/// or in tests with the in-memory repositories. No connection is made up
+/// front, and asking for one fails with 503 after a second
+pub fn unconnected_pool() -> PgPool {
+    Pool::builder()
+        .min_idle(Some(0))
+        .connection_timeout(Duration::from_secs(1))
+        .build_unchecked(ConnectionManager::<PgConnection>::new("postgres://localhost/unused"))
+}
+
+/// Calls `f` on the blocking thread pool, i.e. to call a
+/// [Repository](../repository/trait.Repository.html)
+pub async fn block<F, T>(f: F) -> Result<T, ApiError>
+where
+    F: FnOnce() -> Result<T, ApiError> + Send + 'static,
+    T: Send + 'static,
+{
+    web::block(f).await.map_err(ApiError::from)
+}
+
+/// Gets a connection from the pool and calls `f` with it on the blocking
+/// thread pool
+///
+/// # Errors
+///
+/// The error from `f`, or [ApiError::Unavailable](../common/enum.ApiError.html)
+/// when no connection is free before the pool's `connection_timeout`
+pub async fn with_connection<F, T>(db_pool: &PgPool, f: F) -> Result<T, ApiError>
+where
+    F: FnOnce(&PgConnection) -> Result<T, ApiError> + Send + 'static,
+    T: Send + 'static,
+{
+    let db_pool = db_pool.clone();
+
+    block(move || {
+        let connection = db_pool.get()?;
+        f(&connection)
+    }).await
+}
@@ -0,0 +1,274 @@
+/// Publishes changes to players and teams to the services listening for them.
+/// Each change is written to the `events` table in the same transaction as the
+/// change itself, so a change is never made without its event. Events are kept
+/// for `RETENTION` so clients can catch up on what they missed
+
+use actix_web::web::Bytes;
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use diesel::dsl::not;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::sql_types::BigInt;
+use futures::channel::mpsc::{channel, Receiver, Sender};
+use futures::future;
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{HashSet, VecDeque};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, SystemTime};
+use uuid::Uuid;
+
+use crate::{AppData, PgPool};
+use crate::common::ApiError;
+use crate::db::with_connection;
+use crate::players::models::Player;
+use crate::schema::{events, webhook_deliveries};
+use crate::teams::models::Team;
+use crate::webhooks::models::DeliveryStatus;
+
+pub mod models;
+use models::{Event, NewEvent};
+
+/// The header clients send the id of the last event they got in when they reconnect
+pub const LAST_EVENT_ID_HEADER: &str = "Last-Event-ID";
+
+/// The advisory lock taken while an event is written. Any number works as
+/// long as nothing else locks it
+const OUTBOX_LOCK: i64 = 0x6576_656e_7473;
+
+/// How long events are kept. Clients that were away for longer miss events
+pub const RETENTION: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// How often the events older than `RETENTION` are deleted
+const PRUNE_EVERY: Duration = Duration::from_secs(60 * 60);
+
+/// The names of every change event, i.e. what webhooks can subscribe to
+pub const EVENT_TYPES: [&str; 6] = [
+    "PlayerCreated",
+    "PlayerUpdated",
+    "PlayerDeleted",
+    "TeamCreated",
+    "TeamUpdated",
+    "TeamDeleted",
+];
+
+/// A change to a player or team. Sent as `{ "type": "PlayerUpdated", "data": Player }`
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(tag = "type", content = "data")]
+pub enum ChangeEvent {
+    PlayerCreated(Player),
+    PlayerUpdated(Player),
+    PlayerDeleted { id: Uuid },
+    TeamCreated(Team),
+    TeamUpdated(Team),
+    TeamDeleted { id: Uuid },
+}
+
+impl ChangeEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChangeEvent::PlayerCreated(_) => "PlayerCreated",
+            ChangeEvent::PlayerUpdated(_) => "PlayerUpdated",
+            ChangeEvent::PlayerDeleted { .. } => "PlayerDeleted",
+            ChangeEvent::TeamCreated(_) => "TeamCreated",
+            ChangeEvent::TeamUpdated(_) => "TeamUpdated",
+            ChangeEvent::TeamDeleted { .. } => "TeamDeleted",
+        }
+    }
+}
+
+/// A change event that has been written to the outbox
+#[derive(Clone, Debug, PartialEq)]
+pub struct StoredEvent {
+    /// Increases with each event. Clients send it back as `Last-Event-ID`
+    pub id: i64,
+    pub event: ChangeEvent,
+}
+
+impl StoredEvent {
+    /// Formats the event as a Server-Sent Event
+    pub fn to_sse(&self) -> String {
+        let data = serde_json::to_string(&self.event).expect("Change events can always be serialized");
+
+        format!("id: {}\nevent: {}\ndata: {}\n\n", self.id, self.event.name(), data)
+    }
+}
+
+/// Writes the event to the outbox. Call this in the transaction that makes the
+/// change and publish the event once the transaction is committed
+///
+/// The ids come from a sequence, so without the lock a transaction could
+/// commit a later id before an earlier one and clients reading past the
+/// later id would never get the earlier one. The lock is held until the
+/// transaction ends, so events are committed in the order of their ids.
+/// Outside of a transaction the event gets one of its own
+pub fn record(connection: &PgConnection, event: ChangeEvent) -> QueryResult<StoredEvent> {
+    connection.transaction(|| {
+        diesel::sql_query("SELECT pg_advisory_xact_lock($1)")
+            .bind::<BigInt, _>(OUTBOX_LOCK)
+            .execute(connection)?;
+
+        let mut value = serde_json::to_value(&event).expect("Change events can always be serialized");
+        let new_event = NewEvent {
+            event_type: event.name().to_string(),
+            data: value["data"].take(),
+        };
+
+        let id = diesel::insert_into(events::table)
+            .values(&new_event)
+            .returning(events::id)
+            .get_result::<i64>(connection)?;
+        let stored = StoredEvent { id, event };
+
+        crate::webhooks::enqueue(connection, &stored)?;
+
+        Ok(stored)
+    })
+}
+
+/// Gets the events written after the given event, oldest first
+pub fn events_after(connection: &PgConnection, last_event_id: i64) -> QueryResult<Vec<StoredEvent>> {
+    let events = events::table
+        .filter(events::id.gt(last_event_id))
+        .order(events::id)
+        .load::<Event>(connection)?;
+
+    // Events written by a newer version that this one does not know are skipped
+    Ok(events
+        .into_iter()
+        .filter_map(|event| {
+            serde_json::from_value(json!({ "type": event.event_type, "data": event.data }))
+                .ok()
+                .map(|change| StoredEvent { id: event.id, event: change })
+        })
+        .collect())
+}
+
+/// Deletes the events written before `older_than`. Events with deliveries
+/// still waiting to be sent are kept, the others' deliveries go with them.
+/// Returns how many were deleted
+pub fn delete_before(connection: &PgConnection, older_than: SystemTime) -> QueryResult<usize> {
+    let pending = webhook_deliveries::table
+        .select(webhook_deliveries::event_id)
+        .filter(webhook_deliveries::status.eq(DeliveryStatus::Pending));
+
+    diesel::delete(
+        events::table
+            .filter(events::created_at.lt(older_than))
+            .filter(not(events::id.eq_any(pending)))
+    ).execute(connection)
+}
+
+/// Keeps deleting the events that are older than `RETENTION`. Blocks forever
+/// so it is meant to be run on its own thread
+pub fn prune(pool: PgPool) {
+    loop {
+        let result = pool
+            .get()
+            .map_err(|err| err.to_string())
+            .and_then(|connection| {
+                delete_before(&connection, SystemTime::now() - RETENTION).map_err(|err| err.to_string())
+            });
+
+        if let Err(err) = result {
+            log::error!("Could not delete the old events: {}", err);
+        }
+
+        thread::sleep(PRUNE_EVERY);
+    }
+}
+
+/// How many of the last published event ids are kept to stop duplicates
+const RECENT_EVENTS: usize = 1024;
+
+/// How many events can wait for a subscriber. Subscribers that fall further
+/// behind are dropped so a slow client cannot use up the memory. Clients that
+/// reconnect with `Last-Event-ID` get the events they missed
+pub const SUBSCRIBER_BUFFER: usize = 100;
+
+/// Sends the events to everyone subscribed in this process
+#[derive(Default)]
+pub struct EventBus {
+    subscribers: Mutex<Vec<Sender<StoredEvent>>>,
+    /// An event is published by the handler that made the change and again by
+    /// the change listener. Only the first one is sent
+    recent: Mutex<VecDeque<i64>>,
+}
+
+impl EventBus {
+    /// The events end when the subscriber falls more than `SUBSCRIBER_BUFFER`
+    /// events behind
+    pub fn subscribe(&self) -> Receiver<StoredEvent> {
+        let (sender, receiver) = channel(SUBSCRIBER_BUFFER);
+        self.subscribers.lock().unwrap().push(sender);
+
+        receiver
+    }
+
+    pub fn publish(&self, event: StoredEvent) {
+        {
+            let mut recent = self.recent.lock().unwrap();
+            if recent.contains(&event.id) {
+                return;
+            }
+            if recent.len() == RECENT_EVENTS {
+                recent.pop_front();
+            }
+            recent.push_back(event.id);
+        }
+
+        // Subscribers that went away or are too far behind are dropped here
+        let mut subscribers = self.subscribers.lock().unwrap();
+        *subscribers = subscribers
+            .drain(..)
+            .filter_map(|mut subscriber| subscriber.try_send(event.clone()).ok().map(|_| subscriber))
+            .collect();
+    }
+}
+
+/// Streams the change events as [Server-Sent Events](https://html.spec.whatwg.org/multipage/server-sent-events.html).
+/// Clients that send a `Last-Event-ID` header get the events they missed
+/// first, otherwise only the events after the request was made are sent.
+/// Missed events older than `RETENTION` have been deleted and are not sent
+///
+/// # Returns
+///
+/// 200 is returned and the events are sent as they happen
+///
+/// 400 is returned when `Last-Event-ID` is not an event id
+///
+/// 500 or 503 is returned when the missed events cannot be read
+pub async fn get_events(req: HttpRequest, data: web::Data<AppData>) -> Result<HttpResponse, ApiError> {
+    // Subscribe before reading the missed events so none are lost in between
+    let live_events = data.events.subscribe();
+
+    let last_event_id = match req.headers().get(LAST_EVENT_ID_HEADER) {
+        Some(value) => match value.to_str().ok().and_then(|value| value.trim().parse::<i64>().ok()) {
+            Some(last_event_id) => Some(last_event_id),
+            None => return Err(ApiError::Validation("Last-Event-ID must be an event id".to_string())),
+        },
+        None => None,
+    };
+
+    let missed_events = match last_event_id {
+        Some(last_event_id) => with_connection(&data.db_pool, move |connection| {
+            Ok(events_after(connection, last_event_id)?)
+        }).await?,
+        None => Vec::new(),
+    };
+
+    // Events published while the missed events were read are in both
+    let missed_ids: HashSet<i64> = missed_events.iter().map(|event| event.id).collect();
+    let live_events = live_events.filter(move |event| future::ready(!missed_ids.contains(&event.id)));
+
+    let events = stream::iter(missed_events)
+        .chain(live_events)
+        .map(|event| Ok::<_, Error>(Bytes::from(event.to_sse())));
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .header("Cache-Control", "no-cache")
+        .streaming(events))
+}
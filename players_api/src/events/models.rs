@@ -0,0 +1,24 @@
+/// The models needed for the change events
+
+use serde_json::Value;
+use std::time::SystemTime;
+
+use crate::schema::events;
+
+/// Event model. Matches the database. The events are the outbox: each one is
+/// written in the same transaction as the change it is for
+#[derive(Debug, Queryable)]
+pub struct Event {
+    pub id: i64,
+    pub event_type: String,
+    /// The player or team that changed, or the id of the one that was deleted
+    pub data: Value,
+    pub created_at: SystemTime,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "events"]
+pub struct NewEvent {
+    pub event_type: String,
+    pub data: Value,
+}
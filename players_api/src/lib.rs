@@ -16,62 +16,147 @@ use diesel::pg::PgConnection;
 use diesel::r2d2::{ConnectionManager, Pool};
 use dotenv::dotenv;
 use std::env;
+use std::sync::Arc;
 
+pub mod auth;
+pub mod cache;
 pub mod common;
+pub mod db;
+pub mod events;
+pub mod notifications;
 pub mod players;
+pub mod projections;
+pub mod rankings;
+pub mod repository;
 pub mod schema;
 pub mod seeds;
+pub mod signing;
 pub mod teams;
+pub mod webhooks;
 
-use common::DeserializeErrorHandler;
+use cache::Caches;
+use common::{DeserializeErrorHandler, ValidJson};
+#[cfg(not(feature = "sqlite"))]
+use db::PoolConfig;
+use events::EventBus;
+#[cfg(not(feature = "sqlite"))]
+use notifications::ChangeListener;
+use repository::Repositories;
+use signing::{RequestSigning, VerifySignature};
 
 pub type PgPool = Pool<ConnectionManager<PgConnection>>;
 
+/// The largest body the bulk imports take, instead of the default 32kb. No
+/// request body can be bigger than this
+pub const BULK_JSON_LIMIT: usize = 4 * 1024 * 1024;
+
 /// Contains data that is passed to every request and is
 /// shared with all requests
 pub struct AppData {
-    /// Pool of postgres database connections
-    pub db_pool: Pool<ConnectionManager<PgConnection>>,
+    /// Pool of postgres database connections. Queries are run with
+    /// [db::with_connection](./db/fn.with_connection.html) so they do not block the workers
+    pub db_pool: PgPool,
+    /// Secret shared with the API gateway to verify service tokens
+    pub service_token_secret: String,
+    /// Where changes to players and teams are published
+    pub events: Arc<EventBus>,
+    /// Players and teams fetched by id
+    pub caches: Arc<Caches>,
+    /// Where the player and team handlers read and write
+    pub repositories: Repositories,
+}
+
+/// Settings that are read once at startup and passed to `register`
+#[derive(Clone)]
+pub struct Config {
+    /// Secret shared with the API gateway to verify service tokens
+    pub service_token_secret: String,
+    /// Keys the other services sign their requests with. Requests are not
+    /// checked when this is `None`, which is only meant for tests
+    pub request_signing: Option<RequestSigning>,
+    /// Shared by all the workers so every `/events` stream gets every change
+    pub events: Arc<EventBus>,
+    /// Shared by all the workers so the change listener evicts for all of them
+    pub caches: Arc<Caches>,
+    /// Postgres, SQLite with the `sqlite` feature, or in memory for tests that
+    /// do not have a database
+    pub repositories: Repositories,
 }
 
-pub fn register(db_pool: PgPool) -> impl Fn(&mut web::ServiceConfig) {
+pub fn register(db_pool: PgPool, app_config: Config) -> impl Fn(&mut web::ServiceConfig) {
     move |config: &mut web::ServiceConfig| {
-        use crate::players::models::{CreatePlayerForm, UpdatePlayerForm};
+        use crate::players::models::Player;
+        use crate::projections::models::UpsertProjectionForm;
+        use crate::rankings::models::ImportRankingForm;
+        use crate::teams::models::Team;
+        use crate::webhooks::models::CreateWebhookForm;
 
-        config
-            .data(AppData { db_pool: db_pool.clone() })
+        config.data(AppData {
+            db_pool: db_pool.clone(),
+            service_token_secret: app_config.service_token_secret.clone(),
+            events: app_config.events.clone(),
+            caches: app_config.caches.clone(),
+            repositories: app_config.repositories.clone(),
+        });
+
+        config.service(
+            web::scope("")
+            .wrap(VerifySignature::new(app_config.request_signing.clone()))
+            .service(
+                web::resource("/events")
+                .route(web::get().to(events::get_events))
+            )
+            .configure(Player::register)
             .service(
-                web::resource("/players")
+                web::resource("/projections")
                 .app_data(
-                    web::Json::<CreatePlayerForm>::configure(CreatePlayerForm::handle_deserialize)
+                    // Projections are imported in bulk so allow a bigger payload
+                    web::Json::<Vec<UpsertProjectionForm>>::configure(|cfg| {
+                        UpsertProjectionForm::handle_deserialize(cfg.limit(BULK_JSON_LIMIT))
+                    })
                 )
-                .route(web::get().to(players::get_players))
-                .route(web::post().to(players::create_player))
+                .route(web::get().to(projections::get_projections))
+                .route(web::put().to(projections::upsert_projections))
             )
             .service(
-                web::resource("/players/{id}")
+                web::resource("/projections/average")
+                .route(web::get().to(projections::get_average_projections))
+            )
+            .service(
+                web::resource("/rankings/{scoring_format}")
                 .app_data(
-                    web::Json::<UpdatePlayerForm>::configure(UpdatePlayerForm::handle_deserialize)
+                    web::Json::<Vec<ImportRankingForm>>::configure(|cfg| {
+                        ImportRankingForm::handle_deserialize(cfg.limit(BULK_JSON_LIMIT))
+                    })
                 )
-                .route(web::get().to(players::get_player))
-                .route(web::put().to(players::update_player))
-                .route(web::delete().to(players::delete_player))
+                .route(web::get().to(rankings::get_rankings))
+                .route(web::put().to(rankings::import_rankings))
             )
+            .configure(Team::register)
             .service(
-                web::resource("/teams")
-                .route(web::get().to(teams::get_teams))
-                .route(web::post().to(teams::create_team))
+                web::resource("/webhooks")
+                .app_data(
+                    ValidJson::<CreateWebhookForm>::configure(CreateWebhookForm::handle_deserialize)
+                )
+                .route(web::get().to(webhooks::get_webhooks))
+                .route(web::post().to(webhooks::create_webhook))
             )
             .service(
-                web::resource("/teams/{id}")
-                .route(web::get().to(teams::get_team))
-                .route(web::put().to(teams::update_team))
-                .route(web::delete().to(teams::delete_team))
-            );
+                web::resource("/webhooks/{id}")
+                .route(web::get().to(webhooks::get_webhook))
+                .route(web::delete().to(webhooks::delete_webhook))
+            )
+            .service(
+                web::resource("/webhooks/{id}/deliveries")
+                .route(web::get().to(webhooks::get_deliveries))
+            )
+        );
     }
 }
 
-/// Sets up the web server
+/// Sets up the web server. With the `sqlite` feature `DATABASE_URL` is the
+/// SQLite file the players and teams are kept in, and the routes that need
+/// Postgres fail with 503
 ///
 /// # Panics
 ///
@@ -79,22 +164,70 @@ pub fn register(db_pool: PgPool) -> impl Fn(&mut web::ServiceConfig) {
 ///
 /// Panics if environment variable `DATABASE_URL` is not set
 ///
-/// Panics if it fails to create database pool
+/// Panics if environment variable `DATABASE_POOL_SIZE`, `DATABASE_POOL_MIN_IDLE`
+/// or `DATABASE_POOL_TIMEOUT_SECONDS` is set but is not a number
+///
+/// Panics if environment variable `SERVICE_TOKEN_SECRET` is not set
+///
+/// Panics if environment variable `REQUEST_SIGNING_KEYS` is not set or cannot be parsed
+///
+/// Panics if it fails to create database pool, or to open the SQLite database
+///
+/// Panics if it fails to create the webhook client
 pub async fn run() -> std::io::Result<()> {
-    std::env::set_var("RUST_LOG", "actix_server=info,actix_web=info");
+    std::env::set_var("RUST_LOG", "actix_server=info,actix_web=info,players_api=info");
     env_logger::init();
 
     // Reads the .env file nad makes sure it is parsable
     dotenv().ok();
 
     let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-    let manager = ConnectionManager::<PgConnection>::new(database_url);
-    let pool = Pool::builder().build(manager).expect("Failed to create pool.");
+    #[cfg(not(feature = "sqlite"))]
+    let (pool, repositories) = {
+        let pool = db::build_pool(&database_url, &PoolConfig::from_env()).expect("Failed to create pool.");
+        let repositories = Repositories::postgres(&pool);
+        (pool, repositories)
+    };
+    #[cfg(feature = "sqlite")]
+    let (pool, repositories) = (
+        db::unconnected_pool(),
+        Repositories::sqlite(&database_url).expect("Failed to open the SQLite database."),
+    );
+    let app_config = Config {
+        service_token_secret: env::var("SERVICE_TOKEN_SECRET").expect("SERVICE_TOKEN_SECRET must be set"),
+        // Created once so every worker shares the same nonces
+        request_signing: Some(RequestSigning::from_keys_str(
+            &env::var("REQUEST_SIGNING_KEYS").expect("REQUEST_SIGNING_KEYS must be set")
+        )),
+        events: Arc::new(EventBus::default()),
+        caches: Arc::new(Caches::default()),
+        repositories,
+    };
+
+    // These need Postgres, so they do not run with the `sqlite` feature
+    #[cfg(not(feature = "sqlite"))]
+    {
+        // Other replicas can change players and teams too
+        let listener = ChangeListener::new(
+            database_url,
+            pool.clone(),
+            app_config.caches.clone(),
+            app_config.events.clone(),
+        );
+        std::thread::spawn(move || listener.listen());
+
+        // Deliveries are sent in the background so the write handlers only queue them
+        let delivery_pool = pool.clone();
+        std::thread::spawn(move || webhooks::delivery::run(delivery_pool));
+
+        let prune_pool = pool.clone();
+        std::thread::spawn(move || events::prune(prune_pool));
+    }
 
     HttpServer::new(move || {
         App::new()
             .wrap(middleware::Logger::default())
-            .configure(register(pool.clone()))
+            .configure(register(pool.clone(), app_config.clone()))
     })
         .bind("0.0.0.0:4000")?
         .workers(2)
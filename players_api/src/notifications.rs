@@ -0,0 +1,128 @@
+/// Listens for the rows Postgres says changed. Replicas share the database but
+/// not their memory, so the triggers on `players` and `teams` are how a replica
+/// finds out about a change made on another one
+
+use diesel::dsl::max;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use fallible_iterator::FallibleIterator;
+use postgres::{Client, NoTls};
+use serde::Deserialize;
+use std::error::Error;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::PgPool;
+use crate::cache::Caches;
+use crate::events::{events_after, EventBus};
+use crate::schema::events;
+
+/// The channel the triggers notify on
+pub const CHANNEL: &str = "table_changes";
+
+/// How long to wait before listening again when the connection is lost
+const RECONNECT_AFTER: Duration = Duration::from_secs(5);
+
+/// A row that was changed. Sent by the triggers as
+/// `{ "table": "players", "operation": "UPDATE", "id": "..." }`
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct TableChange {
+    pub table: String,
+    /// INSERT, UPDATE or DELETE
+    pub operation: String,
+    pub id: Uuid,
+}
+
+impl TableChange {
+    /// Evicts the changed row from the caches
+    pub fn evict(&self, caches: &Caches) {
+        match self.table.as_str() {
+            "players" => caches.evict_player(&self.id),
+            "teams" => caches.evict_team(&self.id),
+            _ => {},
+        }
+    }
+}
+
+/// Gets the id of the newest change event
+fn latest_event_id(connection: &PgConnection) -> QueryResult<i64> {
+    events::table
+        .select(max(events::id))
+        .first::<Option<i64>>(connection)
+        .map(|id| id.unwrap_or(0))
+}
+
+/// Evicts the cache entries for the rows that changed and publishes the
+/// change events written by the other replicas to this one's subscribers
+pub struct ChangeListener {
+    database_url: String,
+    db_pool: PgPool,
+    caches: Arc<Caches>,
+    events: Arc<EventBus>,
+}
+
+impl ChangeListener {
+    pub fn new(database_url: String, db_pool: PgPool, caches: Arc<Caches>, events: Arc<EventBus>) -> Self {
+        Self { database_url, db_pool, caches, events }
+    }
+
+    /// Blocks forever so it is meant to be run on its own thread
+    pub fn listen(&self) {
+        // The newest event published by the listener
+        let mut last_event_id = None;
+
+        loop {
+            if let Err(err) = self.follow_changes(&mut last_event_id) {
+                log::error!("Could not listen for table changes: {}", err);
+            }
+
+            thread::sleep(RECONNECT_AFTER);
+        }
+    }
+
+    fn follow_changes(&self, last_event_id: &mut Option<i64>) -> Result<(), Box<dyn Error>> {
+        let mut client = Client::connect(&self.database_url, NoTls)?;
+        client.batch_execute(&format!("LISTEN {}", CHANNEL))?;
+
+        // Anything could have changed while the listener was not connected
+        self.caches.clear();
+        let mut last_id = match *last_event_id {
+            Some(last_id) => last_id,
+            None => {
+                let connection = self.db_pool.get()?;
+                latest_event_id(&connection)?
+            },
+        };
+        self.publish_events_after(&mut last_id)?;
+        *last_event_id = Some(last_id);
+
+        let mut notifications = client.notifications();
+        let mut notifications = notifications.blocking_iter();
+        while let Some(notification) = notifications.next()? {
+            match serde_json::from_str::<TableChange>(notification.payload()) {
+                Ok(change) => change.evict(&self.caches),
+                Err(err) => log::error!("Could not read the table change {}: {}", notification.payload(), err),
+            }
+
+            self.publish_events_after(&mut last_id)?;
+            *last_event_id = Some(last_id);
+        }
+
+        Ok(())
+    }
+
+    /// The events this replica wrote were already published by its handlers,
+    /// the event bus drops them
+    fn publish_events_after(&self, last_event_id: &mut i64) -> Result<(), Box<dyn Error>> {
+        let connection = self.db_pool.get()?;
+
+        for event in events_after(&connection, *last_event_id)? {
+            *last_event_id = event.id;
+            self.events.publish(event);
+        }
+
+        Ok(())
+    }
+}
@@ -1,54 +1,41 @@
-/// This file will hold our player related routes
+/// This file will hold our player related routes. Creating, updating and
+/// deleting are made by `#[derive(Crud)]` on [Player](./models/struct.Player.html),
+/// getting is written here to join the team
 
-use actix_web::{web, HttpRequest, HttpResponse, Responder};
-use diesel::prelude::*;
-use diesel::result::Error as DieselError;
-use diesel::result::DatabaseErrorKind as DbError;
+use actix_web::{web, HttpRequest, HttpResponse};
 use uuid::Uuid;
 
 // AppData is defined in src/lib.rs, which is our entrypoint
 use crate::AppData;
-use crate::common::JsonError;
-use crate::schema::{players, teams};
+use crate::cache::Caches;
+use crate::common::{ApiError, CrudHooks};
+use crate::db::block;
+use crate::events::ChangeEvent;
 
 // Re-export models. Right now this is only for the tests. Ideally this could
 // remain encapsulated within the module
 pub mod models;
-use models::{CreatePlayerForm, Player, PlayerWithTeam, UpdatePlayerForm};
-
-use crate::teams::models::Team;
+use models::{Player, PlayersQuery};
 
 /// Gets all the players and their team from the database
 ///
+/// Pass `?rookie=true` to only get rookies, i.e. for a rookie-only draft
+///
 /// # Returns
 ///
 /// 200 is returned and sends an array of [PlayerWithTeam](./models/struct.PlayerWithTeam.html)
 ///
-/// # Panics
-///
-/// Panics when it fails to get a database connection
-///
-/// Panics when it fails to query the database
+/// 500 or 503 is returned when there is a database error
 pub async fn get_players(
     data: web::Data<AppData>,
+    query: web::Query<PlayersQuery>,
     _req: HttpRequest
-) -> impl Responder {
-    let connection = data.db_pool.get().expect("Could not get db connection from pool");
-    let players_with_teams = players::table
-        .left_join(teams::table)
-        .load::<(Player, Option<Team>)>(&connection)
-        .expect("error with the query")
-        .into_iter()
-        .fold(Vec::new(), |mut result, (player, mut team)| {
-            result.push(PlayerWithTeam {
-                player,
-                team: team.take(),
-            });
-
-            result
-        });
+) -> Result<HttpResponse, ApiError> {
+    let query = query.into_inner();
+    let repository = data.repositories.players.clone();
+    let players_with_teams = block(move || repository.all_with_teams(&query)).await?;
 
-    HttpResponse::Ok().json(players_with_teams)
+    Ok(HttpResponse::Ok().json(players_with_teams))
 }
 
 /// Fetches a player
@@ -60,150 +47,41 @@ pub async fn get_players(
 ///
 /// 404 is returned when the player is not found by the given id
 ///
-/// 500 is returned when there is any other database error
-///
-/// # Panics
-///
-/// Panics when it fails to get a database connection
+/// 500 or 503 is returned when there is any other database error
 pub async fn get_player(
     data: web::Data<AppData>,
     path: web::Path<Uuid>,
     _req: HttpRequest
-) -> impl Responder {
+) -> Result<HttpResponse, ApiError> {
     let id = path.into_inner();
-
-    let connection = data.db_pool.get().expect("Could not get db connection from pool");
-    let result = players::table
-        .find(id)
-        .left_join(teams::table)
-        .first::<(Player, Option<Team>)>(&connection);
-
-    match result {
-        Ok((player, team)) => {
-            HttpResponse::Ok().json(PlayerWithTeam {
-                player,
-                team,
-            })
-        },
-        Err(err) => match err {
-            DieselError::NotFound => HttpResponse::NotFound().json(JsonError::<bool> {
-                message: "Player not found".to_string(),
-                data: None,
-            }),
-            _ => HttpResponse::InternalServerError().json(JsonError {
-                message: "Something went wrong".to_string(),
-                data: Some(err.to_string()),
-            }),
-        },
+    if let Some(player) = data.caches.players.get(&id) {
+        return Ok(HttpResponse::Ok().json(player));
     }
-}
-
-/// Creates a player
-///
-/// # Returns
-///
-/// 200 is returned when the creation is successful and sends the created
-///     [Player](./models/struct.Player.html)
-///
-/// 400 is returned when there is a foreign key violation i.e. the team does not exist
-///
-/// 500 is returned when there is any other database error
-///
-/// # Panics
-///
-/// Panics when it fails to get a database connection
-pub async fn create_player(
-    data: web::Data<AppData>,
-    player: web::Json<CreatePlayerForm>
-) -> impl Responder {
-    let connection = data.db_pool.get().expect("Could not get db connection from pool");
-    let player = player.into_inner();
-    let team_not_found_err = format!("Team {} not found", &player.team_id.map(|id| id.to_string()).unwrap_or_else(|| "".to_string()));
 
-    let result = diesel::insert_into(players::table)
-        .values(player)
-        .get_result::<Player>(&connection);
+    // A change made while the player is read evicts it before this inserts it
+    let generation = data.caches.players.generation();
+    let repository = data.repositories.players.clone();
+    let player = block(move || repository.find_with_team(id)).await?;
+    data.caches.players.insert_unless_evicted(id, player.clone(), generation);
 
-    match result {
-        Ok(player) => HttpResponse::Ok().json(player),
-        Err(err) => match err {
-            DieselError::DatabaseError(DbError::ForeignKeyViolation, _) => 
-                HttpResponse::BadRequest().json(JsonError::<bool> {
-                    message: team_not_found_err,
-                    data: None,
-                }),
-            _ => HttpResponse::InternalServerError().json(JsonError {
-                message: "Something went wrong".to_string(),
-                data: Some(err.to_string()),
-            }),
-        },
-    }
+    Ok(HttpResponse::Ok().json(player))
 }
 
-/// Updates a player
-///
-/// # Returns
-///
-/// 200 is returned when the update was successful and sends the updated
-///     [Player](./models/struct.Player.html)
-///
-/// 500 is returned when there is any other database error
-///
-/// # Panics
-///
-/// Panics when it fails to get a database connection
-pub async fn update_player(
-    data: web::Data<AppData>,
-    path: web::Path<Uuid>,
-    player: web::Json<UpdatePlayerForm>
-) -> impl Responder {
-    let connection = data.db_pool.get().expect("Could not get db connection from pool");
-    let player = player.into_inner();
-    let id = path.into_inner();
-
-    let result = diesel::update(players::table.find(&id)).set(&player).get_result::<Player>(&connection);
+impl CrudHooks for Player {
+    fn created_event(&self) -> Option<ChangeEvent> {
+        Some(ChangeEvent::PlayerCreated(self.clone()))
+    }
 
-    match result {
-        Ok(player) => HttpResponse::Ok().json(player),
-        Err(err) => match err {
-            DieselError::NotFound => HttpResponse::NotFound().json(JsonError {
-                message: "Player not found".to_string(),
-                data: Some(id),
-            }),
-            _ => HttpResponse::InternalServerError().json(JsonError {
-                message: "Something went wrong".to_string(),
-                data: Some(err.to_string()),
-            }),
-        },
+    fn updated_event(&self) -> Option<ChangeEvent> {
+        Some(ChangeEvent::PlayerUpdated(self.clone()))
     }
-}
 
-/// Deletes a player
-///
-/// # Returns
-///
-/// 204 is returned when the delete was successful or the player does not exist
-///
-/// 500 is returned when there is any other database error
-///
-/// # Panics
-///
-/// Panics when it fais to get a database connection
-pub async fn delete_player(
-    data: web::Data<AppData>,
-    path: web::Path<Uuid>,
-    _req: HttpRequest
-) -> impl Responder {
-    let connection = data.db_pool.get().expect("Could not get db connection from pool");
-    let id = path.into_inner();
+    fn deleted_event(id: Uuid) -> Option<ChangeEvent> {
+        Some(ChangeEvent::PlayerDeleted { id })
+    }
 
-    match diesel::delete(players::table.find(&id)).execute(&connection) {
-        Ok(_) => HttpResponse::NoContent().finish(),
-        Err(err) => {
-            HttpResponse::InternalServerError().json(JsonError {
-                message: "Something went wrong".to_string(),
-                data: Some(err.to_string()),
-            })
-        }
+    /// Players are cached with their team by `get_player`, not by themselves
+    fn evict(caches: &Caches, id: &Uuid) {
+        caches.evict_player(id);
     }
 }
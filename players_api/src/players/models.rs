@@ -1,18 +1,30 @@
 /// The models needed for the players APIs
 
+use diesel::dsl;
+use diesel::query_builder::AsChangeset;
+use diesel::prelude::*;
 // Deserialize and Serialize help translate to and from JSON
 use serde::{Deserialize, Serialize};
 use std::time::SystemTime;
 use uuid::Uuid;
 
-use common_derive::DeserializeErrorHandler;
+use common_derive::{Crud, DeserializeErrorHandler, Validate};
 use crate::schema::players;
 use crate::teams::models::Team;
 
 /// Player model. Matches the database.
-#[derive(Associations, Debug, Deserialize, Identifiable, Insertable, Serialize, Queryable)]
+#[derive(Associations, Clone, Crud, Debug, Deserialize, Identifiable, Insertable, Serialize, Queryable)]
 #[belongs_to(Team)]
 #[table_name = "players"]
+#[crud(
+    path = "/players",
+    repository = "players",
+    create_form = "CreatePlayerForm",
+    update_form = "UpdatePlayerForm",
+    get_all = "crate::players::get_players",
+    get_one = "crate::players::get_player",
+    foreign_key_violation = "Team not found"
+)]
 pub struct Player {
     pub id: Uuid, 
     pub first_name: String,
@@ -22,6 +34,8 @@ pub struct Player {
     #[serde(skip)]
     pub updated_at: Option<SystemTime>,
     pub team_id: Option<Uuid>,
+    /// Whether the player is in their first season. Used for rookie-only drafts
+    pub rookie: bool,
 }
 
 impl PartialEq for Player {
@@ -30,25 +44,60 @@ impl PartialEq for Player {
     }
 }
 
-#[derive(Insertable, Debug, Deserialize, DeserializeErrorHandler, Serialize)]
+#[derive(Insertable, Debug, Deserialize, DeserializeErrorHandler, Serialize, Validate)]
 #[table_name = "players"]
 pub struct CreatePlayerForm {
+    #[validate(length(min = 1, max = 64))]
     pub first_name: String,
+    #[validate(length(min = 1, max = 64))]
     pub last_name: String,
     pub team_id: Option<Uuid>,
+    #[serde(default)]
+    pub rookie: bool,
 }
 
-#[changeset_options(treat_none_as_null="true")]
-#[derive(AsChangeset, Debug, Deserialize, DeserializeErrorHandler, Serialize)]
-#[table_name = "players"]
+#[derive(Debug, Deserialize, DeserializeErrorHandler, Serialize, Validate)]
 pub struct UpdatePlayerForm {
+    #[validate(length(min = 1, max = 64))]
     pub first_name: String,
+    #[validate(length(min = 1, max = 64))]
     pub last_name: String,
     pub team_id: Option<Uuid>,
+    /// Left out by clients that do not know about rookies, which keeps the
+    /// player's current value
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rookie: Option<bool>,
+}
+
+/// Not derived because a missing `team_id` takes the player off their team
+/// while a missing `rookie` is not changed
+impl<'a> AsChangeset for &'a UpdatePlayerForm {
+    type Target = players::table;
+    type Changeset = <(
+        dsl::Eq<players::first_name, &'a String>,
+        dsl::Eq<players::last_name, &'a String>,
+        dsl::Eq<players::team_id, &'a Option<Uuid>>,
+        Option<dsl::Eq<players::rookie, &'a bool>>,
+    ) as AsChangeset>::Changeset;
+
+    fn as_changeset(self) -> Self::Changeset {
+        (
+            players::first_name.eq(&self.first_name),
+            players::last_name.eq(&self.last_name),
+            players::team_id.eq(&self.team_id),
+            self.rookie.as_ref().map(|rookie| players::rookie.eq(rookie)),
+        ).as_changeset()
+    }
+}
+
+/// Query string filters for listing players
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PlayersQuery {
+    pub rookie: Option<bool>,
 }
 
 /// The DTO for returning a player
-#[derive(Debug, Deserialize, PartialEq, Serialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct PlayerWithTeam {
     pub player: Player,
     pub team: Option<Team>,
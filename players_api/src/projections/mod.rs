@@ -0,0 +1,150 @@
+/// This file will hold our projection related routes
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use diesel::dsl::{now, sql};
+use diesel::pg::Pg;
+use diesel::pg::upsert::excluded;
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Double, Integer, Uuid as SqlUuid};
+use std::collections::HashSet;
+use uuid::Uuid;
+
+use crate::AppData;
+use crate::auth::AdminCredential;
+use crate::common::ApiError;
+use crate::db::with_connection;
+use crate::schema::projections;
+
+pub mod models;
+use models::{AverageProjection, Projection, ProjectionsQuery, UpsertProjectionForm};
+
+/// Builds the query for projections matching the given filters
+fn filter_projections(query: &ProjectionsQuery) -> projections::BoxedQuery<'_, Pg> {
+    let mut projections_query = projections::table.into_boxed();
+
+    if let Some(player_id) = query.player_id {
+        projections_query = projections_query.filter(projections::player_id.eq(player_id));
+    }
+    if let Some(season) = query.season {
+        projections_query = projections_query.filter(projections::season.eq(season));
+    }
+    if let Some(week) = query.week {
+        projections_query = projections_query.filter(projections::week.eq(week));
+    }
+    if let Some(source) = query.source.as_ref() {
+        projections_query = projections_query.filter(projections::source.eq(source));
+    }
+
+    projections_query
+}
+
+/// Gets the projections matching the `player_id`, `season`, `week` and `source`
+/// query string filters
+///
+/// # Returns
+///
+/// 200 is returned and sends an array of [Projection](./models/struct.Projection.html)
+///
+/// 500 or 503 is returned when there is a database error
+pub async fn get_projections(
+    data: web::Data<AppData>,
+    query: web::Query<ProjectionsQuery>,
+    _req: HttpRequest
+) -> Result<HttpResponse, ApiError> {
+    let query = query.into_inner();
+    let result = with_connection(&data.db_pool, move |connection| {
+        Ok(filter_projections(&query)
+            .order((projections::player_id, projections::season, projections::week, projections::source))
+            .load::<Projection>(connection)?)
+    }).await?;
+
+    Ok(HttpResponse::Ok().json(result))
+}
+
+/// Gets the projections matching the query string filters averaged across their
+/// sources, one per player, season and week
+///
+/// # Returns
+///
+/// 200 is returned and sends an array of
+///     [AverageProjection](./models/struct.AverageProjection.html)
+///
+/// 500 or 503 is returned when there is a database error
+pub async fn get_average_projections(
+    data: web::Data<AppData>,
+    query: web::Query<ProjectionsQuery>,
+    _req: HttpRequest
+) -> Result<HttpResponse, ApiError> {
+    let query = query.into_inner();
+    let averages: Vec<_> = with_connection(&data.db_pool, move |connection| {
+        let key = (projections::player_id, projections::season, projections::week);
+
+        // Diesel does not allow aggregates next to the grouped columns, and
+        // every group has a projection so the average is never null
+        Ok(filter_projections(&query)
+            .select(sql::<(SqlUuid, Integer, Integer, Double, BigInt)>("player_id, season, week, avg(points), count(*)"))
+            .group_by(key)
+            .order(key)
+            .load::<(Uuid, i32, i32, f64, i64)>(connection)?)
+    }).await?
+        .into_iter()
+        .map(|(player_id, season, week, points, sources)| AverageProjection {
+            player_id,
+            season,
+            week,
+            points,
+            sources: sources as usize,
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(averages))
+}
+
+/// Creates or replaces projections in bulk. A projection is replaced when one
+/// already exists for the same player, season, week and source
+///
+/// # Returns
+///
+/// 200 is returned when the upsert is successful and sends the array of saved
+///     [Projection](./models/struct.Projection.html)
+///
+/// 400 is returned when there is a foreign key violation i.e. a player does not exist
+///
+/// 400 is returned when the same player, season, week and source is given more than once
+///
+/// 401 or 403 is returned when the request is not made for an admin
+///
+/// 500 or 503 is returned when there is any other database error
+pub async fn upsert_projections(
+    data: web::Data<AppData>,
+    _admin: AdminCredential,
+    projections: web::Json<Vec<UpsertProjectionForm>>
+) -> Result<HttpResponse, ApiError> {
+    let projections = projections.into_inner();
+    if projections.is_empty() {
+        return Ok(HttpResponse::Ok().json(Vec::<Projection>::new()));
+    }
+
+    // Postgres cannot update the same row twice in one upsert
+    let mut keys = HashSet::new();
+    if !projections.iter().all(|projection| keys.insert((projection.player_id, projection.season, projection.week, &projection.source))) {
+        return Err(ApiError::Validation(
+            "A projection can only be given once per player, season, week and source".to_string()
+        ));
+    }
+
+    let projections = with_connection(&data.db_pool, move |connection| {
+        diesel::insert_into(projections::table)
+            .values(&projections)
+            .on_conflict((projections::player_id, projections::season, projections::week, projections::source))
+            .do_update()
+            .set((
+                projections::points.eq(excluded(projections::points)),
+                projections::updated_at.eq(now.nullable()),
+            ))
+            .get_results::<Projection>(connection)
+            .map_err(ApiError::foreign_key_violation("Player not found"))
+    }).await?;
+
+    Ok(HttpResponse::Ok().json(projections))
+}
@@ -0,0 +1,54 @@
+/// The models needed for the projections APIs
+
+use serde::{Deserialize, Serialize};
+use std::time::SystemTime;
+use uuid::Uuid;
+
+use common_derive::DeserializeErrorHandler;
+use crate::schema::projections;
+
+/// Projection model. Matches the database. A projection is keyed by the player,
+/// season, week and the source it came from. Week 0 is the season long projection
+#[derive(Debug, Deserialize, Insertable, PartialEq, Serialize, Queryable)]
+#[table_name = "projections"]
+pub struct Projection {
+    pub player_id: Uuid,
+    pub season: i32,
+    pub week: i32,
+    pub source: String,
+    pub points: f64,
+    #[serde(skip)]
+    pub created_at: Option<SystemTime>,
+    #[serde(skip)]
+    pub updated_at: Option<SystemTime>,
+}
+
+#[derive(Insertable, Debug, Deserialize, DeserializeErrorHandler, Serialize)]
+#[table_name = "projections"]
+pub struct UpsertProjectionForm {
+    pub player_id: Uuid,
+    pub season: i32,
+    pub week: i32,
+    pub source: String,
+    pub points: f64,
+}
+
+/// Query string filters for listing projections
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct ProjectionsQuery {
+    pub player_id: Option<Uuid>,
+    pub season: Option<i32>,
+    pub week: Option<i32>,
+    pub source: Option<String>,
+}
+
+/// The DTO for returning the projection of a player averaged across all sources
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct AverageProjection {
+    pub player_id: Uuid,
+    pub season: i32,
+    pub week: i32,
+    pub points: f64,
+    /// Number of sources that went into the average
+    pub sources: usize,
+}
@@ -0,0 +1,128 @@
+/// This file will hold our ranking related routes
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use diesel::prelude::*;
+use diesel::result::DatabaseErrorKind as DbError;
+use diesel::result::Error as DieselError;
+use std::collections::HashSet;
+
+use crate::AppData;
+use crate::auth::AdminCredential;
+use crate::common::{ApiError, ValidJson};
+use crate::db::with_connection;
+use crate::schema::{players, rankings};
+
+pub mod models;
+use models::{ImportRankingForm, Ranking, RankingsQuery, RankingWithPlayer, ScoringFormat};
+
+use crate::players::models::Player;
+
+/// How many rankings are inserted at once. Each ranking is 6 parameters and
+/// Postgres takes at most 65535 parameters in a query
+const INSERT_CHUNK: usize = 1000;
+
+/// Gets the rankings for a scoring format ordered by overall rank. Pass
+/// `?position=` to only get the rankings for one position
+///
+/// # Returns
+///
+/// 200 is returned and sends an array of
+///     [RankingWithPlayer](./models/struct.RankingWithPlayer.html)
+///
+/// 500 or 503 is returned when there is a database error
+pub async fn get_rankings(
+    data: web::Data<AppData>,
+    path: web::Path<ScoringFormat>,
+    query: web::Query<RankingsQuery>,
+    _req: HttpRequest
+) -> Result<HttpResponse, ApiError> {
+    let scoring_format = path.into_inner();
+    let query = query.into_inner();
+
+    let rankings_with_players: Vec<_> = with_connection(&data.db_pool, move |connection| {
+        let mut rankings_query = rankings::table
+            .inner_join(players::table)
+            .filter(rankings::scoring_format.eq(scoring_format))
+            .order(rankings::overall_rank)
+            .into_boxed();
+
+        if let Some(position) = query.position.as_ref() {
+            rankings_query = rankings_query.filter(rankings::position.eq(position));
+        }
+
+        Ok(rankings_query.load::<(Ranking, Player)>(connection)?)
+    }).await?
+        .into_iter()
+        .map(|(ranking, player)| RankingWithPlayer { ranking, player })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(rankings_with_players))
+}
+
+/// Imports the rankings for a scoring format. The previous rankings for the
+/// scoring format are replaced
+///
+/// # Returns
+///
+/// 200 is returned when the import is successful and sends the array of imported
+///     [Ranking](./models/struct.Ranking.html)
+///
+/// 400 is returned when there is a foreign key violation i.e. a player does not exist
+///
+/// 400 is returned when a player or an overall rank is given more than once
+///
+/// 422 is returned when a rank or tier is less than 1. The fields that are
+///     not valid are in `errors`
+///
+/// 401 or 403 is returned when the request is not made for an admin
+///
+/// 500 or 503 is returned when there is any other database error
+pub async fn import_rankings(
+    data: web::Data<AppData>,
+    _admin: AdminCredential,
+    path: web::Path<ScoringFormat>,
+    rankings: ValidJson<Vec<ImportRankingForm>>
+) -> Result<HttpResponse, ApiError> {
+    let scoring_format = path.into_inner();
+
+    let mut player_ids = HashSet::new();
+    if !rankings.iter().all(|ranking| player_ids.insert(ranking.player_id)) {
+        return Err(ApiError::Validation("A player can only be ranked once per scoring format".to_string()));
+    }
+    let mut overall_ranks = HashSet::new();
+    if !rankings.iter().all(|ranking| overall_ranks.insert(ranking.overall_rank)) {
+        return Err(ApiError::Validation("An overall rank can only be given once per scoring format".to_string()));
+    }
+
+    let rankings: Vec<_> = rankings
+        .into_inner()
+        .into_iter()
+        .map(|ranking| ranking.into_ranking(scoring_format))
+        .collect();
+
+    let rankings = with_connection(&data.db_pool, move |connection| {
+        connection.transaction::<_, DieselError, _>(|| {
+            diesel::delete(rankings::table.filter(rankings::scoring_format.eq(scoring_format)))
+                .execute(connection)?;
+
+            let mut imported = Vec::with_capacity(rankings.len());
+            for chunk in rankings.chunks(INSERT_CHUNK) {
+                imported.extend(
+                    diesel::insert_into(rankings::table)
+                        .values(chunk)
+                        .get_results::<Ranking>(connection)?
+                );
+            }
+
+            Ok(imported)
+        }).map_err(|err| match err {
+            DieselError::DatabaseError(DbError::ForeignKeyViolation, _) =>
+                ApiError::ForeignKeyViolation("Player not found".to_string()),
+            DieselError::DatabaseError(DbError::UniqueViolation, _) =>
+                ApiError::Validation("A player or an overall rank can only be given once per scoring format".to_string()),
+            err => err.into(),
+        })
+    }).await?;
+
+    Ok(HttpResponse::Ok().json(rankings))
+}
@@ -0,0 +1,111 @@
+/// The models needed for the rankings APIs
+
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::Pg;
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use diesel::sql_types::Text;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::time::SystemTime;
+use uuid::Uuid;
+
+use common_derive::{DeserializeErrorHandler, Validate};
+use crate::players::models::Player;
+use crate::schema::rankings;
+
+/// The scoring formats players are ranked for. Stored as text in the database
+#[derive(AsExpression, Clone, Copy, Debug, Deserialize, Eq, FromSqlRow, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+#[sql_type = "Text"]
+pub enum ScoringFormat {
+    Standard,
+    /// Half a point per reception
+    Half,
+    /// A point per reception
+    Ppr,
+}
+
+impl ScoringFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScoringFormat::Standard => "standard",
+            ScoringFormat::Half => "half",
+            ScoringFormat::Ppr => "ppr",
+        }
+    }
+}
+
+impl ToSql<Text, Pg> for ScoringFormat {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        out.write_all(self.as_str().as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Text, Pg> for ScoringFormat {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match not_none!(bytes) {
+            b"standard" => Ok(ScoringFormat::Standard),
+            b"half" => Ok(ScoringFormat::Half),
+            b"ppr" => Ok(ScoringFormat::Ppr),
+            _ => Err("Unrecognized scoring format".into()),
+        }
+    }
+}
+
+/// Ranking model. Matches the database. A player has one ranking per scoring format
+#[derive(Debug, Deserialize, Insertable, PartialEq, Serialize, Queryable)]
+#[table_name = "rankings"]
+pub struct Ranking {
+    pub player_id: Uuid,
+    pub scoring_format: ScoringFormat,
+    /// The position the player is ranked at, i.e. QB or RB
+    pub position: String,
+    pub overall_rank: i32,
+    pub position_rank: i32,
+    pub tier: i32,
+    #[serde(skip)]
+    pub created_at: Option<SystemTime>,
+    #[serde(skip)]
+    pub updated_at: Option<SystemTime>,
+}
+
+#[derive(Debug, Deserialize, DeserializeErrorHandler, Serialize, Validate)]
+pub struct ImportRankingForm {
+    pub player_id: Uuid,
+    pub position: String,
+    #[validate(range(min = 1))]
+    pub overall_rank: i32,
+    #[validate(range(min = 1))]
+    pub position_rank: i32,
+    #[validate(range(min = 1))]
+    pub tier: i32,
+}
+
+impl ImportRankingForm {
+    pub fn into_ranking(self, scoring_format: ScoringFormat) -> Ranking {
+        Ranking {
+            player_id: self.player_id,
+            scoring_format,
+            position: self.position,
+            overall_rank: self.overall_rank,
+            position_rank: self.position_rank,
+            tier: self.tier,
+            created_at: None,
+            updated_at: None,
+        }
+    }
+}
+
+/// Query string filters for listing rankings
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct RankingsQuery {
+    pub position: Option<String>,
+}
+
+/// The DTO for returning a ranking
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct RankingWithPlayer {
+    pub ranking: Ranking,
+    pub player: Player,
+}
@@ -0,0 +1,208 @@
+/// Players and teams kept in memory, for tests of the handlers that do not
+/// have a database. The same foreign keys as the database are checked and the
+/// change events are numbered like the outbox, but they are not stored
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+use uuid::Uuid;
+
+use crate::common::{ApiError, CrudHooks};
+use crate::events::{ChangeEvent, StoredEvent};
+use crate::players::models::{CreatePlayerForm, Player, PlayersQuery, PlayerWithTeam, UpdatePlayerForm};
+use crate::teams::models::{CreateTeamForm, Team, UpdateTeamForm};
+use super::{PlayerRepository, Repository};
+
+/// The records shared by [InMemoryPlayers](./struct.InMemoryPlayers.html) and
+/// [InMemoryTeams](./struct.InMemoryTeams.html). Teams are always locked
+/// before players
+#[derive(Default)]
+pub struct InMemoryStore {
+    players: RwLock<HashMap<Uuid, Player>>,
+    teams: RwLock<HashMap<Uuid, Team>>,
+    last_event_id: AtomicI64,
+}
+
+impl InMemoryStore {
+    fn stored(&self, event: Option<ChangeEvent>) -> Option<StoredEvent> {
+        event.map(|event| StoredEvent {
+            id: self.last_event_id.fetch_add(1, Ordering::SeqCst) + 1,
+            event,
+        })
+    }
+}
+
+/// Fails like the `players.team_id` foreign key when the team does not exist
+fn check_team(teams: &HashMap<Uuid, Team>, team_id: Option<Uuid>) -> Result<(), ApiError> {
+    match team_id {
+        Some(team_id) if !teams.contains_key(&team_id) => Err(ApiError::ForeignKeyViolation("Team not found".to_string())),
+        _ => Ok(()),
+    }
+}
+
+pub struct InMemoryPlayers {
+    store: Arc<InMemoryStore>,
+}
+
+impl InMemoryPlayers {
+    pub fn new(store: Arc<InMemoryStore>) -> Self {
+        Self { store }
+    }
+}
+
+impl Repository for InMemoryPlayers {
+    type Record = Player;
+    type CreateForm = CreatePlayerForm;
+    type UpdateForm = UpdatePlayerForm;
+
+    fn all(&self) -> Result<Vec<Player>, ApiError> {
+        Ok(self.store.players.read().unwrap().values().cloned().collect())
+    }
+
+    fn find(&self, id: Uuid) -> Result<Player, ApiError> {
+        self.store.players.read().unwrap()
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| ApiError::NotFound("Player not found".to_string()))
+    }
+
+    fn create(&self, form: CreatePlayerForm) -> Result<(Player, Option<StoredEvent>), ApiError> {
+        let teams = self.store.teams.read().unwrap();
+        check_team(&teams, form.team_id)?;
+
+        let now = SystemTime::now();
+        let player = Player {
+            id: Uuid::new_v4(),
+            first_name: form.first_name,
+            last_name: form.last_name,
+            created_at: Some(now),
+            updated_at: Some(now),
+            team_id: form.team_id,
+            rookie: form.rookie,
+        };
+        self.store.players.write().unwrap().insert(player.id, player.clone());
+
+        let event = self.store.stored(player.created_event());
+        Ok((player, event))
+    }
+
+    fn update(&self, id: Uuid, form: UpdatePlayerForm) -> Result<(Player, Option<StoredEvent>), ApiError> {
+        let teams = self.store.teams.read().unwrap();
+        let mut players = self.store.players.write().unwrap();
+        let player = players.get_mut(&id).ok_or_else(|| ApiError::NotFound("Player not found".to_string()))?;
+        check_team(&teams, form.team_id)?;
+
+        player.first_name = form.first_name;
+        player.last_name = form.last_name;
+        player.team_id = form.team_id;
+        if let Some(rookie) = form.rookie {
+            player.rookie = rookie;
+        }
+        player.updated_at = Some(SystemTime::now());
+        let player = player.clone();
+
+        let event = self.store.stored(player.updated_event());
+        Ok((player, event))
+    }
+
+    fn delete(&self, id: Uuid) -> Result<Option<StoredEvent>, ApiError> {
+        match self.store.players.write().unwrap().remove(&id) {
+            Some(_) => Ok(self.store.stored(Player::deleted_event(id))),
+            None => Ok(None),
+        }
+    }
+}
+
+impl PlayerRepository for InMemoryPlayers {
+    fn all_with_teams(&self, query: &PlayersQuery) -> Result<Vec<PlayerWithTeam>, ApiError> {
+        let teams = self.store.teams.read().unwrap();
+        let players = self.store.players.read().unwrap();
+
+        let players_with_teams = players.values()
+            .filter(|player| query.rookie.map_or(true, |rookie| player.rookie == rookie))
+            .map(|player| PlayerWithTeam {
+                player: player.clone(),
+                team: player.team_id.and_then(|team_id| teams.get(&team_id).cloned()),
+            })
+            .collect();
+
+        Ok(players_with_teams)
+    }
+
+    fn find_with_team(&self, id: Uuid) -> Result<PlayerWithTeam, ApiError> {
+        let teams = self.store.teams.read().unwrap();
+        let player = self.find(id)?;
+        let team = player.team_id.and_then(|team_id| teams.get(&team_id).cloned());
+
+        Ok(PlayerWithTeam { player, team })
+    }
+}
+
+pub struct InMemoryTeams {
+    store: Arc<InMemoryStore>,
+}
+
+impl InMemoryTeams {
+    pub fn new(store: Arc<InMemoryStore>) -> Self {
+        Self { store }
+    }
+}
+
+impl Repository for InMemoryTeams {
+    type Record = Team;
+    type CreateForm = CreateTeamForm;
+    type UpdateForm = UpdateTeamForm;
+
+    fn all(&self) -> Result<Vec<Team>, ApiError> {
+        Ok(self.store.teams.read().unwrap().values().cloned().collect())
+    }
+
+    fn find(&self, id: Uuid) -> Result<Team, ApiError> {
+        self.store.teams.read().unwrap()
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| ApiError::NotFound("Team not found".to_string()))
+    }
+
+    fn create(&self, form: CreateTeamForm) -> Result<(Team, Option<StoredEvent>), ApiError> {
+        let now = SystemTime::now();
+        let team = Team {
+            id: Uuid::new_v4(),
+            display_name: form.display_name,
+            abbreviation: form.abbreviation,
+            created_at: Some(now),
+            updated_at: Some(now),
+        };
+        self.store.teams.write().unwrap().insert(team.id, team.clone());
+
+        let event = self.store.stored(team.created_event());
+        Ok((team, event))
+    }
+
+    fn update(&self, id: Uuid, form: UpdateTeamForm) -> Result<(Team, Option<StoredEvent>), ApiError> {
+        let mut teams = self.store.teams.write().unwrap();
+        let team = teams.get_mut(&id).ok_or_else(|| ApiError::NotFound("Team not found".to_string()))?;
+
+        team.display_name = form.display_name;
+        team.abbreviation = form.abbreviation;
+        team.updated_at = Some(SystemTime::now());
+        let team = team.clone();
+
+        let event = self.store.stored(team.updated_event());
+        Ok((team, event))
+    }
+
+    fn delete(&self, id: Uuid) -> Result<Option<StoredEvent>, ApiError> {
+        let mut teams = self.store.teams.write().unwrap();
+        let players = self.store.players.read().unwrap();
+        if players.values().any(|player| player.team_id == Some(id)) {
+            return Err(ApiError::ForeignKeyViolation("Cannot delete team: players still exist in the team".to_string()));
+        }
+
+        match teams.remove(&id) {
+            Some(_) => Ok(self.store.stored(Team::deleted_event(id))),
+            None => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,97 @@
+/// Where the player and team handlers read and write. Handlers get the
+/// repositories from `AppData` so they can be backed by Postgres, SQLite with
+/// the `sqlite` feature, or kept in memory for tests that do not have a
+/// database. Repositories block, so handlers call them with
+/// [db::block](../db/fn.block.html)
+
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::PgPool;
+use crate::common::ApiError;
+use crate::events::StoredEvent;
+use crate::players::models::{CreatePlayerForm, Player, PlayersQuery, PlayerWithTeam, UpdatePlayerForm};
+use crate::teams::models::{CreateTeamForm, Team, UpdateTeamForm};
+
+mod memory;
+mod postgres;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+pub use memory::{InMemoryPlayers, InMemoryStore, InMemoryTeams};
+pub use postgres::PgRepository;
+#[cfg(feature = "sqlite")]
+pub use sqlite::{SqlitePlayers, SqlitePool, SqliteStore, SqliteTeams};
+
+/// Reads and writes one kind of record. The writes send back the change event
+/// they recorded so the handler can publish it
+pub trait Repository: Send + Sync {
+    type Record;
+    type CreateForm;
+    type UpdateForm;
+
+    fn all(&self) -> Result<Vec<Self::Record>, ApiError>;
+
+    /// Fails with `ApiError::NotFound` when there is no record with the id
+    fn find(&self, id: Uuid) -> Result<Self::Record, ApiError>;
+
+    fn create(&self, form: Self::CreateForm) -> Result<(Self::Record, Option<StoredEvent>), ApiError>;
+
+    /// Fails with `ApiError::NotFound` when there is no record with the id
+    fn update(&self, id: Uuid, form: Self::UpdateForm) -> Result<(Self::Record, Option<StoredEvent>), ApiError>;
+
+    /// Deleting a record that does not exist is not an error, there is just no event
+    fn delete(&self, id: Uuid) -> Result<Option<StoredEvent>, ApiError>;
+}
+
+pub trait TeamRepository: Repository<Record = Team, CreateForm = CreateTeamForm, UpdateForm = UpdateTeamForm> {}
+
+impl<R> TeamRepository for R
+where
+    R: Repository<Record = Team, CreateForm = CreateTeamForm, UpdateForm = UpdateTeamForm>,
+{}
+
+pub trait PlayerRepository: Repository<Record = Player, CreateForm = CreatePlayerForm, UpdateForm = UpdatePlayerForm> {
+    /// The players matching the query with their team
+    fn all_with_teams(&self, query: &PlayersQuery) -> Result<Vec<PlayerWithTeam>, ApiError>;
+
+    /// Fails with `ApiError::NotFound` when there is no player with the id
+    fn find_with_team(&self, id: Uuid) -> Result<PlayerWithTeam, ApiError>;
+}
+
+/// The repositories the handlers use
+#[derive(Clone)]
+pub struct Repositories {
+    pub players: Arc<dyn PlayerRepository>,
+    pub teams: Arc<dyn TeamRepository>,
+}
+
+impl Repositories {
+    pub fn postgres(db_pool: &PgPool) -> Self {
+        Self {
+            players: Arc::new(PgRepository::<Player>::new(db_pool.clone())),
+            teams: Arc::new(PgRepository::<Team>::new(db_pool.clone())),
+        }
+    }
+
+    /// Repositories that share an empty [InMemoryStore](./struct.InMemoryStore.html)
+    pub fn in_memory() -> Self {
+        let store = Arc::new(InMemoryStore::default());
+
+        Self {
+            players: Arc::new(InMemoryPlayers::new(store.clone())),
+            teams: Arc::new(InMemoryTeams::new(store)),
+        }
+    }
+
+    /// Repositories that share the SQLite database file at `database_url`.
+    /// See [SqliteStore::open](./struct.SqliteStore.html#method.open)
+    #[cfg(feature = "sqlite")]
+    pub fn sqlite(database_url: &str) -> Result<Self, ApiError> {
+        let store = Arc::new(SqliteStore::open(database_url)?);
+
+        Ok(Self {
+            players: Arc::new(SqlitePlayers::new(store.clone())),
+            teams: Arc::new(SqliteTeams::new(store)),
+        })
+    }
+}
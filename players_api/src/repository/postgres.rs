@@ -0,0 +1,65 @@
+/// The repositories backed by Postgres. The `Repository` implementations are
+/// made by `#[derive(Crud)]` on the models, the queries that join players and
+/// teams are here
+
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, PooledConnection};
+use std::marker::PhantomData;
+use uuid::Uuid;
+
+use crate::PgPool;
+use crate::common::ApiError;
+use crate::players::models::{Player, PlayersQuery, PlayerWithTeam};
+use crate::schema::{players, teams};
+use crate::teams::models::Team;
+use super::PlayerRepository;
+
+pub struct PgRepository<T> {
+    db_pool: PgPool,
+    record: PhantomData<fn() -> T>,
+}
+
+impl<T> PgRepository<T> {
+    pub fn new(db_pool: PgPool) -> Self {
+        Self { db_pool, record: PhantomData }
+    }
+
+    /// Waits for a connection. Fails with `ApiError::Unavailable` when none is
+    /// free before the pool's `connection_timeout`
+    pub fn connection(&self) -> Result<PooledConnection<ConnectionManager<PgConnection>>, ApiError> {
+        Ok(self.db_pool.get()?)
+    }
+}
+
+impl PlayerRepository for PgRepository<Player> {
+    fn all_with_teams(&self, query: &PlayersQuery) -> Result<Vec<PlayerWithTeam>, ApiError> {
+        let connection = self.connection()?;
+        let mut players_query = players::table
+            .left_join(teams::table)
+            .into_boxed();
+
+        if let Some(rookie) = query.rookie {
+            players_query = players_query.filter(players::rookie.eq(rookie));
+        }
+
+        let players_with_teams = players_query
+            .load::<(Player, Option<Team>)>(&connection)?
+            .into_iter()
+            .map(|(player, team)| PlayerWithTeam { player, team })
+            .collect();
+
+        Ok(players_with_teams)
+    }
+
+    fn find_with_team(&self, id: Uuid) -> Result<PlayerWithTeam, ApiError> {
+        let connection = self.connection()?;
+        let (player, team) = players::table
+            .find(id)
+            .left_join(teams::table)
+            .first::<(Player, Option<Team>)>(&connection)
+            .map_err(ApiError::not_found("Player not found"))?;
+
+        Ok(PlayerWithTeam { player, team })
+    }
+}
@@ -0,0 +1,403 @@
+/// Players and teams kept in SQLite, so the player and team handlers can run
+/// without Postgres. Only built with the `sqlite` feature. The ids are uuids
+/// stored as text and the timestamps are left to the database, so they are
+/// not read back. The change events are written to an `events` table in the
+/// same transaction as the change, like the Postgres outbox, so their ids
+/// keep going up when the database is opened again
+
+use diesel::connection::SimpleConnection;
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::r2d2::{self, ConnectionManager, CustomizeConnection, Pool, PooledConnection};
+use diesel::result::DatabaseErrorKind as DbError;
+use diesel::result::Error as DieselError;
+use diesel::sql_types::BigInt;
+use diesel::sqlite::SqliteConnection;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::common::{ApiError, CrudHooks};
+use crate::events::{ChangeEvent, StoredEvent};
+use crate::players::models::{CreatePlayerForm, Player, PlayersQuery, PlayerWithTeam, UpdatePlayerForm};
+use crate::teams::models::{CreateTeamForm, Team, UpdateTeamForm};
+use super::{PlayerRepository, Repository};
+
+use self::schema::{events, players, teams};
+
+pub type SqlitePool = Pool<ConnectionManager<SqliteConnection>>;
+
+/// Creates the tables when they do not exist yet, so a new database file can
+/// be used without the diesel CLI
+const CREATE_TABLES: &str = include_str!("../../migrations_sqlite/2020-03-09-150000_create_players_and_teams/up.sql");
+
+/// The columns of the tables in `migrations_sqlite` that are read and written
+mod schema {
+    table! {
+        events (id) {
+            id -> BigInt,
+            event_type -> Text,
+            data -> Text,
+        }
+    }
+
+    table! {
+        players (id) {
+            id -> Text,
+            first_name -> Text,
+            last_name -> Text,
+            team_id -> Nullable<Text>,
+            rookie -> Bool,
+        }
+    }
+
+    table! {
+        teams (id) {
+            id -> Text,
+            display_name -> Text,
+            abbreviation -> Text,
+        }
+    }
+
+    joinable!(players -> teams (team_id));
+
+    allow_tables_to_appear_in_same_query!(
+        players,
+        teams,
+    );
+}
+
+#[derive(Insertable, Queryable)]
+#[table_name = "players"]
+struct PlayerRow {
+    id: String,
+    first_name: String,
+    last_name: String,
+    team_id: Option<String>,
+    rookie: bool,
+}
+
+impl PlayerRow {
+    fn into_player(self) -> Result<Player, ApiError> {
+        Ok(Player {
+            id: parse_id(&self.id)?,
+            first_name: self.first_name,
+            last_name: self.last_name,
+            created_at: None,
+            updated_at: None,
+            team_id: self.team_id.as_deref().map(parse_id).transpose()?,
+            rookie: self.rookie,
+        })
+    }
+}
+
+#[derive(Insertable, Queryable)]
+#[table_name = "teams"]
+struct TeamRow {
+    id: String,
+    display_name: String,
+    abbreviation: String,
+}
+
+impl TeamRow {
+    fn into_team(self) -> Result<Team, ApiError> {
+        Ok(Team {
+            id: parse_id(&self.id)?,
+            display_name: self.display_name,
+            abbreviation: self.abbreviation,
+            created_at: None,
+            updated_at: None,
+        })
+    }
+}
+
+fn parse_id(id: &str) -> Result<Uuid, ApiError> {
+    Uuid::parse_str(id).map_err(|err| ApiError::Internal(format!("{} is not a uuid: {}", id, err)))
+}
+
+fn into_player_with_team((player, team): (PlayerRow, Option<TeamRow>)) -> Result<PlayerWithTeam, ApiError> {
+    Ok(PlayerWithTeam {
+        player: player.into_player()?,
+        team: team.map(TeamRow::into_team).transpose()?,
+    })
+}
+
+/// Sends back a foreign key violation with the message, like the Postgres repositories
+fn foreign_key_violation(message: &'static str) -> impl FnOnce(DieselError) -> ApiError {
+    move |err| match err {
+        DieselError::DatabaseError(DbError::ForeignKeyViolation, _) => ApiError::ForeignKeyViolation(message.to_string()),
+        err => err.into(),
+    }
+}
+
+/// SQLite only checks foreign keys when they are turned on for the connection.
+/// Connections wait for each other's writes instead of failing with `database is locked`
+#[derive(Debug)]
+struct ConnectionOptions;
+
+impl CustomizeConnection<SqliteConnection, r2d2::Error> for ConnectionOptions {
+    fn on_acquire(&self, connection: &mut SqliteConnection) -> Result<(), r2d2::Error> {
+        connection
+            .batch_execute("PRAGMA foreign_keys = ON; PRAGMA busy_timeout = 5000;")
+            .map_err(r2d2::Error::QueryError)
+    }
+}
+
+/// The database shared by [SqlitePlayers](./struct.SqlitePlayers.html) and
+/// [SqliteTeams](./struct.SqliteTeams.html)
+pub struct SqliteStore {
+    db_pool: SqlitePool,
+}
+
+impl SqliteStore {
+    /// Opens the database file at `database_url`, creating it and its tables
+    /// when they do not exist
+    pub fn open(database_url: &str) -> Result<Self, ApiError> {
+        let db_pool = Pool::builder()
+            .connection_customizer(Box::new(ConnectionOptions))
+            .build(ConnectionManager::<SqliteConnection>::new(database_url))?;
+        db_pool.get()?.batch_execute(CREATE_TABLES)?;
+
+        Ok(Self { db_pool })
+    }
+
+    fn connection(&self) -> Result<PooledConnection<ConnectionManager<SqliteConnection>>, ApiError> {
+        Ok(self.db_pool.get()?)
+    }
+
+    /// Writes the event to the `events` table. Call this in the transaction
+    /// that makes the change
+    fn record(connection: &SqliteConnection, event: Option<ChangeEvent>) -> QueryResult<Option<StoredEvent>> {
+        let event = match event {
+            Some(event) => event,
+            None => return Ok(None),
+        };
+        let mut value = serde_json::to_value(&event).expect("Change events can always be serialized");
+
+        diesel::insert_into(events::table)
+            .values((
+                events::event_type.eq(event.name()),
+                events::data.eq(value["data"].take().to_string()),
+            ))
+            .execute(connection)?;
+        // SQLite cannot send back the id with RETURNING
+        let id = diesel::select(sql::<BigInt>("last_insert_rowid()")).get_result::<i64>(connection)?;
+
+        Ok(Some(StoredEvent { id, event }))
+    }
+}
+
+pub struct SqlitePlayers {
+    store: Arc<SqliteStore>,
+}
+
+impl SqlitePlayers {
+    pub fn new(store: Arc<SqliteStore>) -> Self {
+        Self { store }
+    }
+}
+
+impl Repository for SqlitePlayers {
+    type Record = Player;
+    type CreateForm = CreatePlayerForm;
+    type UpdateForm = UpdatePlayerForm;
+
+    fn all(&self) -> Result<Vec<Player>, ApiError> {
+        let connection = self.store.connection()?;
+
+        players::table
+            .load::<PlayerRow>(&connection)?
+            .into_iter()
+            .map(PlayerRow::into_player)
+            .collect()
+    }
+
+    fn find(&self, id: Uuid) -> Result<Player, ApiError> {
+        let connection = self.store.connection()?;
+
+        players::table
+            .find(id.to_string())
+            .first::<PlayerRow>(&connection)
+            .map_err(ApiError::not_found("Player not found"))?
+            .into_player()
+    }
+
+    fn create(&self, form: CreatePlayerForm) -> Result<(Player, Option<StoredEvent>), ApiError> {
+        let connection = self.store.connection()?;
+        let row = PlayerRow {
+            id: Uuid::new_v4().to_string(),
+            first_name: form.first_name,
+            last_name: form.last_name,
+            team_id: form.team_id.map(|team_id| team_id.to_string()),
+            rookie: form.rookie,
+        };
+
+        connection.transaction(|| {
+            diesel::insert_into(players::table)
+                .values(&row)
+                .execute(&connection)
+                .map_err(foreign_key_violation("Team not found"))?;
+
+            let player = row.into_player()?;
+            let event = SqliteStore::record(&connection, player.created_event())?;
+            Ok((player, event))
+        })
+    }
+
+    fn update(&self, id: Uuid, form: UpdatePlayerForm) -> Result<(Player, Option<StoredEvent>), ApiError> {
+        let connection = self.store.connection()?;
+        let id = id.to_string();
+
+        connection.transaction(|| {
+            let updated = diesel::update(players::table.find(&id))
+                .set((
+                    players::first_name.eq(&form.first_name),
+                    players::last_name.eq(&form.last_name),
+                    players::team_id.eq(form.team_id.map(|team_id| team_id.to_string())),
+                    form.rookie.map(|rookie| players::rookie.eq(rookie)),
+                ))
+                .execute(&connection)
+                .map_err(foreign_key_violation("Team not found"))?;
+            if updated == 0 {
+                return Err(ApiError::NotFound("Player not found".to_string()));
+            }
+
+            // Read back as the rookie flag is only changed when it was sent
+            let player = players::table.find(&id).first::<PlayerRow>(&connection)?.into_player()?;
+            let event = SqliteStore::record(&connection, player.updated_event())?;
+            Ok((player, event))
+        })
+    }
+
+    fn delete(&self, id: Uuid) -> Result<Option<StoredEvent>, ApiError> {
+        let connection = self.store.connection()?;
+
+        connection.transaction(|| {
+            match diesel::delete(players::table.find(id.to_string())).execute(&connection)? {
+                0 => Ok(None),
+                _ => Ok(SqliteStore::record(&connection, Player::deleted_event(id))?),
+            }
+        })
+    }
+}
+
+impl PlayerRepository for SqlitePlayers {
+    fn all_with_teams(&self, query: &PlayersQuery) -> Result<Vec<PlayerWithTeam>, ApiError> {
+        let connection = self.store.connection()?;
+        let mut players_query = players::table
+            .left_join(teams::table)
+            .into_boxed();
+
+        if let Some(rookie) = query.rookie {
+            players_query = players_query.filter(players::rookie.eq(rookie));
+        }
+
+        players_query
+            .load::<(PlayerRow, Option<TeamRow>)>(&connection)?
+            .into_iter()
+            .map(into_player_with_team)
+            .collect()
+    }
+
+    fn find_with_team(&self, id: Uuid) -> Result<PlayerWithTeam, ApiError> {
+        let connection = self.store.connection()?;
+        let player_with_team = players::table
+            .find(id.to_string())
+            .left_join(teams::table)
+            .first::<(PlayerRow, Option<TeamRow>)>(&connection)
+            .map_err(ApiError::not_found("Player not found"))?;
+
+        into_player_with_team(player_with_team)
+    }
+}
+
+pub struct SqliteTeams {
+    store: Arc<SqliteStore>,
+}
+
+impl SqliteTeams {
+    pub fn new(store: Arc<SqliteStore>) -> Self {
+        Self { store }
+    }
+}
+
+impl Repository for SqliteTeams {
+    type Record = Team;
+    type CreateForm = CreateTeamForm;
+    type UpdateForm = UpdateTeamForm;
+
+    fn all(&self) -> Result<Vec<Team>, ApiError> {
+        let connection = self.store.connection()?;
+
+        teams::table
+            .load::<TeamRow>(&connection)?
+            .into_iter()
+            .map(TeamRow::into_team)
+            .collect()
+    }
+
+    fn find(&self, id: Uuid) -> Result<Team, ApiError> {
+        let connection = self.store.connection()?;
+
+        teams::table
+            .find(id.to_string())
+            .first::<TeamRow>(&connection)
+            .map_err(ApiError::not_found("Team not found"))?
+            .into_team()
+    }
+
+    fn create(&self, form: CreateTeamForm) -> Result<(Team, Option<StoredEvent>), ApiError> {
+        let connection = self.store.connection()?;
+        let row = TeamRow {
+            id: Uuid::new_v4().to_string(),
+            display_name: form.display_name,
+            abbreviation: form.abbreviation,
+        };
+
+        connection.transaction(|| {
+            diesel::insert_into(teams::table).values(&row).execute(&connection)?;
+
+            let team = row.into_team()?;
+            let event = SqliteStore::record(&connection, team.created_event())?;
+            Ok((team, event))
+        })
+    }
+
+    fn update(&self, id: Uuid, form: UpdateTeamForm) -> Result<(Team, Option<StoredEvent>), ApiError> {
+        let connection = self.store.connection()?;
+        let row = TeamRow {
+            id: id.to_string(),
+            display_name: form.display_name,
+            abbreviation: form.abbreviation,
+        };
+
+        connection.transaction(|| {
+            let updated = diesel::update(teams::table.find(&row.id))
+                .set((
+                    teams::display_name.eq(&row.display_name),
+                    teams::abbreviation.eq(&row.abbreviation),
+                ))
+                .execute(&connection)?;
+            if updated == 0 {
+                return Err(ApiError::NotFound("Team not found".to_string()));
+            }
+
+            let team = row.into_team()?;
+            let event = SqliteStore::record(&connection, team.updated_event())?;
+            Ok((team, event))
+        })
+    }
+
+    fn delete(&self, id: Uuid) -> Result<Option<StoredEvent>, ApiError> {
+        let connection = self.store.connection()?;
+        connection.transaction(|| {
+            let deleted = diesel::delete(teams::table.find(id.to_string()))
+                .execute(&connection)
+                .map_err(foreign_key_violation("Cannot delete team: players still exist in the team"))?;
+
+            match deleted {
+                0 => Ok(None),
+                _ => Ok(SqliteStore::record(&connection, Team::deleted_event(id))?),
+            }
+        })
+    }
+}
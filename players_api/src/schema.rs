@@ -1,3 +1,12 @@
+table! {
+    events (id) {
+        id -> Int8,
+        event_type -> Varchar,
+        data -> Jsonb,
+        created_at -> Timestamp,
+    }
+}
+
 table! {
     players (id) {
         id -> Uuid,
@@ -6,6 +15,32 @@ table! {
         created_at -> Nullable<Timestamp>,
         updated_at -> Nullable<Timestamp>,
         team_id -> Nullable<Uuid>,
+        rookie -> Bool,
+    }
+}
+
+table! {
+    projections (player_id, season, week, source) {
+        player_id -> Uuid,
+        season -> Int4,
+        week -> Int4,
+        source -> Varchar,
+        points -> Float8,
+        created_at -> Nullable<Timestamp>,
+        updated_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    rankings (player_id, scoring_format) {
+        player_id -> Uuid,
+        scoring_format -> Varchar,
+        position -> Varchar,
+        overall_rank -> Int4,
+        position_rank -> Int4,
+        tier -> Int4,
+        created_at -> Nullable<Timestamp>,
+        updated_at -> Nullable<Timestamp>,
     }
 }
 
@@ -19,9 +54,61 @@ table! {
     }
 }
 
+table! {
+    webhook_dead_letters (id) {
+        id -> Int8,
+        delivery_id -> Nullable<Int8>,
+        webhook_id -> Uuid,
+        event_id -> Nullable<Int8>,
+        last_error -> Varchar,
+        created_at -> Timestamp,
+        event_type -> Varchar,
+        data -> Jsonb,
+    }
+}
+
+table! {
+    webhook_deliveries (id) {
+        id -> Int8,
+        webhook_id -> Uuid,
+        event_id -> Int8,
+        status -> Varchar,
+        attempts -> Int4,
+        next_attempt_at -> Timestamp,
+        response_status -> Nullable<Int4>,
+        last_error -> Nullable<Varchar>,
+        created_at -> Timestamp,
+        updated_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    webhooks (id) {
+        id -> Uuid,
+        url -> Varchar,
+        event_types -> Array<Text>,
+        secret -> Varchar,
+        created_at -> Nullable<Timestamp>,
+        updated_at -> Nullable<Timestamp>,
+    }
+}
+
 joinable!(players -> teams (team_id));
+joinable!(projections -> players (player_id));
+joinable!(rankings -> players (player_id));
+joinable!(webhook_dead_letters -> events (event_id));
+joinable!(webhook_dead_letters -> webhook_deliveries (delivery_id));
+joinable!(webhook_dead_letters -> webhooks (webhook_id));
+joinable!(webhook_deliveries -> events (event_id));
+joinable!(webhook_deliveries -> webhooks (webhook_id));
 
 allow_tables_to_appear_in_same_query!(
+    events,
     players,
+    projections,
+    rankings,
     teams,
+    webhook_dead_letters,
+    webhook_deliveries,
+    webhooks,
 );
@@ -15,6 +15,7 @@ pub fn get_players(teams: &HashMap<&str, Team>) -> Vec<Player> {
             first_name: "Christian".to_string(),
             last_name: "Kirk".to_string(),
             team_id: Some(get_team_id("cardinals")),
+            rookie: false,
             created_at: None,
             updated_at: None,
         },
@@ -23,6 +24,7 @@ pub fn get_players(teams: &HashMap<&str, Team>) -> Vec<Player> {
             first_name: "Kyler".to_string(),
             last_name: "Murray".to_string(),
             team_id: Some(get_team_id("cardinals")),
+            rookie: false,
             created_at: None,
             updated_at: None,
         },
@@ -31,6 +33,7 @@ pub fn get_players(teams: &HashMap<&str, Team>) -> Vec<Player> {
             first_name: "Dak".to_string(),
             last_name: "Prescott".to_string(),
             team_id: Some(get_team_id("cowboys")),
+            rookie: false,
             created_at: None,
             updated_at: None,
         },
@@ -39,6 +42,7 @@ pub fn get_players(teams: &HashMap<&str, Team>) -> Vec<Player> {
             first_name: "Amari".to_string(),
             last_name: "Cooper".to_string(),
             team_id: Some(get_team_id("cowboys")),
+            rookie: false,
             created_at: None,
             updated_at: None,
         },
@@ -47,6 +51,7 @@ pub fn get_players(teams: &HashMap<&str, Team>) -> Vec<Player> {
             first_name: "Eddie".to_string(),
             last_name: "Lacy".to_string(),
             team_id: None,
+            rookie: false,
             created_at: None,
             updated_at: None,
         },
@@ -0,0 +1,299 @@
+/// Verifies that requests were signed by a service we share a key with
+
+use actix_service::{Service, Transform};
+use actix_web::dev::{Body, MessageBody, ResponseBody, ServiceRequest, ServiceResponse};
+use actix_web::http::header::CONTENT_LENGTH;
+use actix_web::web::BytesMut;
+use actix_web::{Error, HttpMessage};
+use futures::future::{ok, Ready};
+use futures::stream::StreamExt;
+use futures::Future;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::BULK_JSON_LIMIT;
+use crate::common::ApiError;
+
+pub const KEY_ID_HEADER: &str = "X-Signature-Key-Id";
+pub const TIMESTAMP_HEADER: &str = "X-Signature-Timestamp";
+pub const NONCE_HEADER: &str = "X-Signature-Nonce";
+pub const SIGNATURE_HEADER: &str = "X-Signature";
+
+/// How far the timestamp of a request can be from now, in seconds
+const MAX_CLOCK_SKEW: u64 = 5 * 60;
+
+/// Nonces that have been used recently, so a signed request cannot be replayed
+#[derive(Default)]
+struct NonceCache {
+    seen: Mutex<SeenNonces>,
+}
+
+#[derive(Default)]
+struct SeenNonces {
+    nonces: HashSet<String>,
+    /// The same nonces, oldest first, with the unix timestamp they can be
+    /// forgotten at. Every nonce is kept as long so they expire in this order
+    expiries: VecDeque<(u64, String)>,
+}
+
+impl NonceCache {
+    /// Remembers the nonce. Returns false when the nonce was already used
+    fn insert(&self, nonce: &str, now: u64) -> bool {
+        let mut seen = self.seen.lock().unwrap();
+        while seen.expiries.front().map_or(false, |(expires_at, _)| *expires_at <= now) {
+            if let Some((_, expired)) = seen.expiries.pop_front() {
+                seen.nonces.remove(&expired);
+            }
+        }
+
+        if !seen.nonces.insert(nonce.to_string()) {
+            return false;
+        }
+
+        seen.expiries.push_back((now + 2 * MAX_CLOCK_SKEW, nonce.to_string()));
+        true
+    }
+}
+
+/// The keys requests can be signed with. Shared by all the workers so a nonce
+/// cannot be replayed against another worker
+///
+/// Requests carry a key id, a unix timestamp, a random nonce and an HMAC-SHA256
+/// signature of the string
+///
+/// ```text
+/// METHOD\nPATH?QUERY\nTIMESTAMP\nNONCE\nHEX(SHA256(BODY))
+/// ```
+///
+/// Several keys can be accepted at once so keys can be rotated without downtime:
+/// add the new key, switch the callers to it, then remove the old key
+#[derive(Clone)]
+pub struct RequestSigning {
+    /// Key id to secret
+    keys: Arc<HashMap<String, String>>,
+    nonces: Arc<NonceCache>,
+}
+
+impl RequestSigning {
+    pub fn new(keys: HashMap<String, String>) -> Self {
+        Self {
+            keys: Arc::new(keys),
+            nonces: Arc::new(NonceCache::default()),
+        }
+    }
+
+    /// Parses keys in the format `key_id:secret,other_key_id:other_secret`
+    ///
+    /// # Panics
+    ///
+    /// Panics when a key is missing its id or secret
+    pub fn from_keys_str(keys: &str) -> Self {
+        let keys = keys
+            .split(',')
+            .map(str::trim)
+            .filter(|key| !key.is_empty())
+            .map(|key| {
+                let mut parts = key.splitn(2, ':');
+                match (parts.next(), parts.next()) {
+                    (Some(id), Some(secret)) if !id.is_empty() && !secret.is_empty() => {
+                        (id.to_string(), secret.to_string())
+                    },
+                    _ => panic!("Signing keys must be in the format key_id:secret"),
+                }
+            })
+            .collect();
+
+        Self::new(keys)
+    }
+
+    /// Signs a request with the key. Used by the callers and the tests
+    pub fn sign(secret: &str, method: &str, path_and_query: &str, timestamp: u64, nonce: &str, body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_varkey(secret.as_bytes()).expect("HMAC can take a key of any size");
+        mac.input(canonical_request(method, path_and_query, timestamp, nonce, body).as_bytes());
+
+        to_hex(&mac.result().code())
+    }
+
+    fn verify(&self, req: &ServiceRequest, body: &[u8]) -> Result<(), &'static str> {
+        let header = |name: &str| {
+            req.headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .ok_or("Missing request signature")
+        };
+
+        let secret = self.keys.get(header(KEY_ID_HEADER)?).ok_or("Unknown signing key")?;
+        let timestamp = header(TIMESTAMP_HEADER)?.parse::<u64>().map_err(|_| "Invalid signature timestamp")?;
+        let nonce = header(NONCE_HEADER)?;
+        let signature = from_hex(header(SIGNATURE_HEADER)?).ok_or("Invalid request signature")?;
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|now| now.as_secs()).unwrap_or(0);
+        let skew = if now > timestamp { now - timestamp } else { timestamp - now };
+        if skew > MAX_CLOCK_SKEW {
+            return Err("Request signature expired");
+        }
+
+        let path_and_query = req.uri().path_and_query().map(|p| p.as_str()).unwrap_or("/");
+        let mut mac = Hmac::<Sha256>::new_varkey(secret.as_bytes()).expect("HMAC can take a key of any size");
+        mac.input(canonical_request(req.method().as_str(), path_and_query, timestamp, nonce, body).as_bytes());
+        mac.verify(&signature).map_err(|_| "Invalid request signature")?;
+
+        // Only remember the nonce once the signature is valid so it cannot be
+        // used up by someone without the key
+        if !self.nonces.insert(nonce, now) {
+            return Err("Request signature was already used");
+        }
+
+        Ok(())
+    }
+}
+
+fn canonical_request(method: &str, path_and_query: &str, timestamp: u64, nonce: &str, body: &[u8]) -> String {
+    format!(
+        "{}\n{}\n{}\n{}\n{}",
+        method.to_uppercase(),
+        path_and_query,
+        timestamp,
+        nonce,
+        to_hex(&Sha256::digest(body)),
+    )
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| hex.get(i..i + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+        .collect()
+}
+
+/// Middleware that rejects requests without a valid signature with a 401, and
+/// bodies bigger than `BULK_JSON_LIMIT` with a 413. Requests are not checked
+/// when there is no [RequestSigning](./struct.RequestSigning.html), which is
+/// only meant for tests
+pub struct VerifySignature {
+    signing: Option<RequestSigning>,
+}
+
+impl VerifySignature {
+    pub fn new(signing: Option<RequestSigning>) -> Self {
+        Self { signing }
+    }
+}
+
+impl<S, B> Transform<S> for VerifySignature
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + Unpin + 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<Body>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = VerifySignatureMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(VerifySignatureMiddleware {
+            service: Rc::new(RefCell::new(service)),
+            signing: self.signing.clone(),
+        })
+    }
+}
+
+pub struct VerifySignatureMiddleware<S> {
+    service: Rc<RefCell<S>>,
+    signing: Option<RequestSigning>,
+}
+
+impl<S, B> Service for VerifySignatureMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + Unpin + 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<Body>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.borrow_mut().poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let signing = match self.signing.clone() {
+            Some(signing) => signing,
+            None => {
+                let fut = service.borrow_mut().call(req);
+                return Box::pin(async move { fut.await.map(into_body_response) });
+            },
+        };
+
+        Box::pin(async move {
+            // The body is part of the signature so read it and put it back
+            // for the handlers. Bodies bigger than any handler takes are
+            // rejected before they are read in full
+            let content_length = req.headers()
+                .get(CONTENT_LENGTH)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse::<usize>().ok());
+            if content_length.map_or(false, |length| length > BULK_JSON_LIMIT) {
+                return Ok(error_response(req, ApiError::PayloadTooLarge(PAYLOAD_TOO_LARGE.to_string())));
+            }
+
+            let mut body = BytesMut::new();
+            let mut payload = req.take_payload();
+            while let Some(chunk) = payload.next().await {
+                let chunk = chunk?;
+                if body.len() + chunk.len() > BULK_JSON_LIMIT {
+                    return Ok(error_response(req, ApiError::PayloadTooLarge(PAYLOAD_TOO_LARGE.to_string())));
+                }
+                body.extend_from_slice(&chunk);
+            }
+            let body = body.freeze();
+
+            if let Err(message) = signing.verify(&req, &body) {
+                return Ok(error_response(req, ApiError::Unauthorized(message.to_string())));
+            }
+
+            let (_, mut new_payload) = actix_http::h1::Payload::create(true);
+            new_payload.unread_data(body);
+            req.set_payload(new_payload.into());
+
+            let fut = service.borrow_mut().call(req);
+            fut.await.map(into_body_response)
+        })
+    }
+}
+
+const PAYLOAD_TOO_LARGE: &str = "Request body is too large";
+
+/// Sends the error as problem details without calling the handler
+fn error_response(req: ServiceRequest, err: ApiError) -> ServiceResponse<Body> {
+    let problem = err.to_problem().with_instance(req.path());
+
+    req.into_response(problem.to_response())
+}
+
+/// The error responses have a different body type than the wrapped service so both
+/// are turned into a `Body`
+fn into_body_response<B: MessageBody + Unpin + 'static>(res: ServiceResponse<B>) -> ServiceResponse<Body> {
+    res.map_body(|_, body| ResponseBody::Other(Body::from_message(body)))
+}
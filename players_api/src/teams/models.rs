@@ -2,12 +2,19 @@ use serde::{Deserialize, Serialize};
 use std::time::SystemTime;
 use uuid::Uuid;
 
-use common_derive::DeserializeErrorHandler;
+use common_derive::{Crud, DeserializeErrorHandler, Validate};
 use crate::schema::teams;
 
 /// Team model. Represents a team a player can be on
-#[derive(Identifiable, Insertable, Debug, Deserialize, Serialize, Queryable)]
+#[derive(Clone, Crud, Identifiable, Insertable, Debug, Deserialize, Serialize, Queryable)]
 #[table_name = "teams"]
+#[crud(
+    path = "/teams",
+    repository = "teams",
+    create_form = "CreateTeamForm",
+    update_form = "UpdateTeamForm",
+    delete_restricted = "Cannot delete team: players still exist in the team"
+)]
 pub struct Team {
     pub id: Uuid,
     pub display_name: String,
@@ -24,16 +31,22 @@ impl PartialEq for Team {
     }
 }
 
-#[derive(Insertable, Debug, Deserialize, DeserializeErrorHandler, Serialize)]
+#[derive(Insertable, Debug, Deserialize, DeserializeErrorHandler, Serialize, Validate)]
 #[table_name = "teams"]
 pub struct CreateTeamForm {
+    #[validate(length(min = 1, max = 64))]
     pub display_name: String,
+    #[validate(length(min = 2, max = 4))]
+    #[validate(regex = "^[A-Z]+$", message = "abbreviation must be upper case letters")]
     pub abbreviation: String,
 }
 
-#[derive(AsChangeset, Debug, Deserialize, DeserializeErrorHandler, Serialize)]
+#[derive(AsChangeset, Debug, Deserialize, DeserializeErrorHandler, Serialize, Validate)]
 #[table_name = "teams"]
 pub struct UpdateTeamForm {
+    #[validate(length(min = 1, max = 64))]
     pub display_name: String,
+    #[validate(length(min = 2, max = 4))]
+    #[validate(regex = "^[A-Z]+$", message = "abbreviation must be upper case letters")]
     pub abbreviation: String,
 }
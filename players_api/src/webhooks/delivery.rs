@@ -0,0 +1,202 @@
+/// Sends the queued deliveries to the webhooks. Failed deliveries are retried
+/// with exponential backoff and moved to the dead letters once they run out
+/// of attempts
+
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::result::Error as DieselError;
+use hmac::{Hmac, Mac};
+use serde_json::json;
+use sha2::Sha256;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::PgPool;
+use crate::events::models::Event;
+use crate::schema::{events, webhook_dead_letters, webhook_deliveries, webhooks};
+use crate::signing::to_hex;
+use super::check_public_url;
+use super::models::{DeliveryAttempt, DeliveryStatus, NewDeadLetter, Webhook, WebhookDelivery};
+
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Webhook-Timestamp";
+pub const EVENT_HEADER: &str = "X-Webhook-Event";
+pub const DELIVERY_HEADER: &str = "X-Webhook-Delivery";
+
+/// How many times a delivery is tried before it goes to the dead letters
+pub const MAX_ATTEMPTS: i32 = 8;
+
+/// How long to wait before the first retry. Each retry after waits twice as long
+const FIRST_RETRY_AFTER: Duration = Duration::from_secs(30);
+
+/// How long a webhook has to respond
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How many deliveries are sent at a time
+const BATCH_SIZE: i64 = 10;
+
+/// How long claimed deliveries are left to the replica that claimed them.
+/// Longer than it takes to send a whole batch, so a delivery is only claimed
+/// again when the replica sending it went away
+pub const LEASE: Duration = Duration::from_secs(5 * 60);
+
+/// How often the queue is checked for deliveries that are due
+const POLL_EVERY: Duration = Duration::from_secs(1);
+
+/// Signs the body of a delivery. Partners compute the same HMAC-SHA256 of
+/// `<timestamp>.<body>` with their secret to check the delivery came from us
+pub fn sign(secret: &str, timestamp: u64, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_varkey(secret.as_bytes()).expect("HMAC can take a key of any size");
+    mac.input(format!("{}.{}", timestamp, body).as_bytes());
+
+    to_hex(&mac.result().code())
+}
+
+/// How long to wait before trying again after the given number of attempts
+pub fn retry_after(attempts: i32) -> Duration {
+    let doublings = (attempts.max(1) - 1).min(16) as u32;
+
+    FIRST_RETRY_AFTER * 2u32.pow(doublings)
+}
+
+/// Saves the outcome of an attempt. The outcome is the status code of the
+/// response, or why there was no response
+pub fn record_attempt(
+    connection: &PgConnection,
+    delivery: &WebhookDelivery,
+    outcome: Result<u16, String>,
+    now: SystemTime,
+) -> QueryResult<WebhookDelivery> {
+    let attempts = delivery.attempts + 1;
+    let (response_status, error) = match outcome {
+        Ok(status) if (200..300).contains(&status) => (Some(i32::from(status)), None),
+        Ok(status) => (Some(i32::from(status)), Some(format!("Webhook responded with {}", status))),
+        Err(err) => (None, Some(err)),
+    };
+
+    let status = match &error {
+        None => DeliveryStatus::Delivered,
+        Some(_) if attempts >= MAX_ATTEMPTS => DeliveryStatus::Failed,
+        Some(_) => DeliveryStatus::Pending,
+    };
+
+    connection.transaction::<_, DieselError, _>(|| {
+        if status == DeliveryStatus::Failed {
+            // Pending deliveries keep their event from being deleted so it is still there
+            let event = events::table.find(delivery.event_id).first::<Event>(connection)?;
+            diesel::insert_into(webhook_dead_letters::table)
+                .values(NewDeadLetter {
+                    delivery_id: delivery.id,
+                    webhook_id: delivery.webhook_id,
+                    event_id: delivery.event_id,
+                    last_error: error.clone().unwrap_or_default(),
+                    event_type: event.event_type,
+                    data: event.data,
+                })
+                .execute(connection)?;
+        }
+
+        diesel::update(delivery)
+            .set(DeliveryAttempt {
+                status,
+                attempts,
+                next_attempt_at: now + retry_after(attempts),
+                response_status,
+                last_error: error,
+                updated_at: Some(now),
+            })
+            .get_result::<WebhookDelivery>(connection)
+    })
+}
+
+/// Sends the delivery. Returns the status code of the response
+fn send(client: &reqwest::blocking::Client, webhook: &Webhook, delivery: &WebhookDelivery, event: &Event) -> Result<u16, String> {
+    check_public_url(&webhook.url)?;
+
+    let body = json!({
+        "id": event.id,
+        "type": event.event_type,
+        "data": event.data,
+    }).to_string();
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|now| now.as_secs()).unwrap_or(0);
+
+    client
+        .post(&webhook.url)
+        .header("Content-Type", "application/json")
+        .header(EVENT_HEADER, event.event_type.as_str())
+        .header(DELIVERY_HEADER, delivery.id.to_string())
+        .header(TIMESTAMP_HEADER, timestamp.to_string())
+        .header(SIGNATURE_HEADER, sign(&webhook.secret, timestamp, &body))
+        .body(body)
+        .send()
+        .map(|response| response.status().as_u16())
+        .map_err(|err| err.to_string())
+}
+
+/// Claims the deliveries that are due by pushing their next attempt back by
+/// `LEASE`, so other replicas skip them while they are sent. The rows are
+/// only locked while they are claimed
+pub fn claim_due(connection: &PgConnection, now: SystemTime) -> QueryResult<Vec<WebhookDelivery>> {
+    connection.transaction::<_, DieselError, _>(|| {
+        let due = webhook_deliveries::table
+            .select(webhook_deliveries::id)
+            .filter(webhook_deliveries::status.eq(DeliveryStatus::Pending))
+            .filter(webhook_deliveries::next_attempt_at.le(now))
+            .order(webhook_deliveries::next_attempt_at)
+            .limit(BATCH_SIZE)
+            .for_update()
+            .skip_locked()
+            .load::<i64>(connection)?;
+
+        diesel::update(webhook_deliveries::table.filter(webhook_deliveries::id.eq_any(due)))
+            .set(webhook_deliveries::next_attempt_at.eq(now + LEASE))
+            .get_results::<WebhookDelivery>(connection)
+    })
+}
+
+/// Sends the deliveries that are due. Nothing is locked while the webhooks
+/// are called, and each attempt is saved on its own. Returns how many were sent
+pub fn deliver_due(connection: &PgConnection, client: &reqwest::blocking::Client) -> QueryResult<usize> {
+    let due = claim_due(connection, SystemTime::now())?;
+
+    for delivery in &due {
+        let webhook = webhooks::table.find(delivery.webhook_id).first::<Webhook>(connection).optional()?;
+        let event = events::table.find(delivery.event_id).first::<Event>(connection).optional()?;
+
+        // The webhook or the event was deleted after the delivery was claimed,
+        // which deleted the delivery too
+        if let (Some(webhook), Some(event)) = (webhook, event) {
+            let outcome = send(client, &webhook, delivery, &event);
+            record_attempt(connection, delivery, outcome, SystemTime::now()).optional()?;
+        }
+    }
+
+    Ok(due.len())
+}
+
+/// Keeps sending the deliveries as they are due. Blocks forever so it is
+/// meant to be run on its own thread
+pub fn run(pool: PgPool) {
+    // Redirects are not followed since they could go to an address that is not public
+    let client = reqwest::blocking::Client::builder()
+        .timeout(TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .expect("Could not create the webhook client");
+
+    loop {
+        let result = pool
+            .get()
+            .map_err(|err| err.to_string())
+            .and_then(|connection| deliver_due(&connection, &client).map_err(|err| err.to_string()));
+
+        match result {
+            // Keep going while there is a backlog
+            Ok(sent) if sent as i64 == BATCH_SIZE => continue,
+            Ok(_) => {},
+            Err(err) => log::error!("Could not send the webhook deliveries: {}", err),
+        }
+
+        thread::sleep(POLL_EVERY);
+    }
+}
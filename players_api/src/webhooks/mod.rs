@@ -0,0 +1,262 @@
+/// This file will hold our webhook related routes. Partners register a URL and
+/// the change events they want, and each change is queued as a delivery to
+/// every webhook subscribed to it
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::PgArrayExpressionMethods;
+use std::net::IpAddr;
+use uuid::Uuid;
+
+use crate::AppData;
+use crate::auth::AdminCredential;
+use crate::common::{ApiError, FieldError, Validate, ValidJson};
+use crate::db::with_connection;
+use crate::events::{StoredEvent, EVENT_TYPES};
+use crate::schema::{webhook_deliveries, webhooks};
+
+pub mod delivery;
+pub mod models;
+use models::{CreateWebhookForm, DeliveriesQuery, NewDelivery, Webhook, WebhookDelivery};
+
+/// The most deliveries the delivery log returns
+const DELIVERY_LOG_LIMIT: i64 = 100;
+
+/// Queues a delivery of the event to every webhook subscribed to it. Called
+/// when the event is recorded so the deliveries are written in the same
+/// transaction as the change. The webhooks are locked so they cannot be
+/// deleted before the deliveries referencing them are written
+pub fn enqueue(connection: &PgConnection, event: &StoredEvent) -> QueryResult<usize> {
+    let webhook_ids = webhooks::table
+        .filter(webhooks::event_types.contains(vec![event.event.name().to_string()]))
+        .select(webhooks::id)
+        .for_key_share()
+        .load::<Uuid>(connection)?;
+
+    if webhook_ids.is_empty() {
+        return Ok(0);
+    }
+
+    let deliveries: Vec<_> = webhook_ids
+        .into_iter()
+        .map(|webhook_id| NewDelivery { webhook_id, event_id: event.id })
+        .collect();
+
+    diesel::insert_into(webhook_deliveries::table)
+        .values(&deliveries)
+        .execute(connection)
+}
+
+/// Resolves the host of the URL and checks every address it resolves to is
+/// public, so webhooks cannot be used to call the services next to this one.
+/// Checked when the webhook is created and again before each delivery since
+/// the addresses can change
+pub fn check_public_url(url: &str) -> Result<(), String> {
+    let url = reqwest::Url::parse(url).map_err(|err| err.to_string())?;
+    let addresses = url
+        .socket_addrs(|| None)
+        .map_err(|err| format!("Could not resolve the URL's host: {}", err))?;
+
+    match addresses.iter().find(|address| !is_public(address.ip())) {
+        Some(address) => Err(format!("URL resolves to {} which is not a public address", address.ip())),
+        None if addresses.is_empty() => Err("URL's host has no addresses".to_string()),
+        None => Ok(()),
+    }
+}
+
+/// Loopback, private, link-local and other addresses that are not reachable
+/// from the internet are not public
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let octets = ip.octets();
+            let shared = octets[0] == 100 && (octets[1] & 0xc0) == 64;
+            !(ip.is_private() || ip.is_loopback() || ip.is_link_local() || ip.is_unspecified()
+                || ip.is_broadcast() || ip.is_multicast() || octets[0] == 0 || shared)
+        },
+        IpAddr::V6(ip) => {
+            let unique_local = (ip.segments()[0] & 0xfe00) == 0xfc00;
+            let link_local = (ip.segments()[0] & 0xffc0) == 0xfe80;
+            if ip.is_loopback() || ip.is_unspecified() || ip.is_multicast() || unique_local || link_local {
+                return false;
+            }
+
+            // IPv4 addresses written as IPv6 addresses are checked as IPv4
+            match ip.to_ipv4() {
+                Some(ip) => is_public(IpAddr::V4(ip)),
+                None => true,
+            }
+        },
+    }
+}
+
+/// Checks the webhook can be delivered to. The addresses of the URL are
+/// checked by `create_webhook` since resolving them blocks
+impl Validate for CreateWebhookForm {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+
+        match reqwest::Url::parse(&self.url) {
+            Ok(url) if url.scheme() == "http" || url.scheme() == "https" => {},
+            _ => errors.push(FieldError::new("url", "URL must be an http or https URL")),
+        }
+
+        if self.event_types.is_empty() {
+            errors.push(FieldError::new("event_types", "At least one event type is required"));
+        }
+        if let Some(event_type) = self.event_types.iter().find(|event_type| !EVENT_TYPES.contains(&event_type.as_str())) {
+            errors.push(FieldError::new("event_types", &format!("Unknown event type {}", event_type)));
+        }
+
+        if self.secret.trim().is_empty() {
+            errors.push(FieldError::new("secret", "Secret is required"));
+        }
+
+        errors
+    }
+}
+
+/// Gets all the webhooks
+///
+/// # Returns
+///
+/// 200 is returned and sends an array of [Webhook](./models/struct.Webhook.html)
+///
+/// 401 or 403 is returned when the request is not made for an admin
+///
+/// 500 or 503 is returned when there is a database error
+pub async fn get_webhooks(
+    data: web::Data<AppData>,
+    _admin: AdminCredential,
+    _req: HttpRequest
+) -> Result<HttpResponse, ApiError> {
+    let result = with_connection(&data.db_pool, |connection| {
+        Ok(webhooks::table.order(webhooks::created_at).load::<Webhook>(connection)?)
+    }).await?;
+
+    Ok(HttpResponse::Ok().json(result))
+}
+
+/// Fetches a webhook
+///
+/// # Returns
+///
+/// 200 is returned if the webhook is found and sends a [Webhook](./models/struct.Webhook.html)
+///
+/// 401 or 403 is returned when the request is not made for an admin
+///
+/// 404 is returned when the webhook is not found by the given id
+///
+/// 500 or 503 is returned when there is any other database error
+pub async fn get_webhook(
+    data: web::Data<AppData>,
+    _admin: AdminCredential,
+    path: web::Path<Uuid>
+) -> Result<HttpResponse, ApiError> {
+    let id = path.into_inner();
+
+    let webhook = with_connection(&data.db_pool, move |connection| {
+        webhooks::table
+            .find(id)
+            .first::<Webhook>(connection)
+            .map_err(ApiError::not_found("Webhook not found"))
+    }).await?;
+
+    Ok(HttpResponse::Ok().json(webhook))
+}
+
+/// Creates a webhook. The events in `event_types` are sent to the URL as they
+/// happen, signed with the secret
+///
+/// # Returns
+///
+/// 200 is returned when the creation is successful and sends the created
+///     [Webhook](./models/struct.Webhook.html) without the secret
+///
+/// 422 is returned when the URL is not an http URL or does not resolve to
+///     public addresses, an event type is unknown or the secret is empty. The
+///     fields that are not valid are in `errors`
+///
+/// 401 or 403 is returned when the request is not made for an admin
+///
+/// 500 or 503 is returned when there is any other database error
+pub async fn create_webhook(
+    data: web::Data<AppData>,
+    _admin: AdminCredential,
+    webhook: ValidJson<CreateWebhookForm>
+) -> Result<HttpResponse, ApiError> {
+    let webhook = webhook.into_inner();
+
+    let url = webhook.url.clone();
+    web::block(move || {
+        check_public_url(&url).map_err(|err| ApiError::InvalidFields(vec![FieldError::new("url", &err)]))
+    }).await?;
+
+    let webhook = with_connection(&data.db_pool, move |connection| {
+        Ok(diesel::insert_into(webhooks::table).values(webhook).get_result::<Webhook>(connection)?)
+    }).await?;
+
+    Ok(HttpResponse::Ok().json(webhook))
+}
+
+/// Deletes a webhook along with its deliveries
+///
+/// # Returns
+///
+/// 204 is returned when the delete was successful or the webhook does not exist
+///
+/// 401 or 403 is returned when the request is not made for an admin
+///
+/// 500 or 503 is returned when there is any other database error
+pub async fn delete_webhook(
+    data: web::Data<AppData>,
+    _admin: AdminCredential,
+    path: web::Path<Uuid>
+) -> Result<HttpResponse, ApiError> {
+    let id = path.into_inner();
+
+    with_connection(&data.db_pool, move |connection| {
+        Ok(diesel::delete(webhooks::table.find(&id)).execute(connection)?)
+    }).await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Gets the delivery log of a webhook, newest first. Pass `?status=` to only
+/// get the deliveries that are `pending`, `delivered` or `failed`
+///
+/// # Returns
+///
+/// 200 is returned and sends an array of the last 100
+///     [WebhookDelivery](./models/struct.WebhookDelivery.html)
+///
+/// 401 or 403 is returned when the request is not made for an admin
+///
+/// 500 or 503 is returned when there is a database error
+pub async fn get_deliveries(
+    data: web::Data<AppData>,
+    _admin: AdminCredential,
+    path: web::Path<Uuid>,
+    query: web::Query<DeliveriesQuery>
+) -> Result<HttpResponse, ApiError> {
+    let id = path.into_inner();
+    let status = query.status;
+
+    let deliveries = with_connection(&data.db_pool, move |connection| {
+        let mut deliveries_query = webhook_deliveries::table
+            .filter(webhook_deliveries::webhook_id.eq(id))
+            .into_boxed();
+
+        if let Some(status) = status {
+            deliveries_query = deliveries_query.filter(webhook_deliveries::status.eq(status));
+        }
+
+        Ok(deliveries_query
+            .order(webhook_deliveries::id.desc())
+            .limit(DELIVERY_LOG_LIMIT)
+            .load::<WebhookDelivery>(connection)?)
+    }).await?;
+
+    Ok(HttpResponse::Ok().json(deliveries))
+}
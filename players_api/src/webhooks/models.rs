@@ -0,0 +1,139 @@
+/// The models needed for the webhooks APIs
+
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::Pg;
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use diesel::sql_types::Text;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::Write;
+use std::time::SystemTime;
+use uuid::Uuid;
+
+use common_derive::DeserializeErrorHandler;
+use crate::schema::{webhook_dead_letters, webhook_deliveries, webhooks};
+
+/// Webhook model. Matches the database. A partner's URL that is sent the
+/// change events it is subscribed to
+#[derive(Clone, Debug, Deserialize, Identifiable, Queryable, Serialize)]
+#[table_name = "webhooks"]
+pub struct Webhook {
+    pub id: Uuid,
+    pub url: String,
+    /// The change events to send, i.e. PlayerUpdated
+    pub event_types: Vec<String>,
+    /// Deliveries are signed with the secret. It is never sent back
+    #[serde(skip_serializing, default)]
+    pub secret: String,
+    #[serde(skip)]
+    pub created_at: Option<SystemTime>,
+    #[serde(skip)]
+    pub updated_at: Option<SystemTime>,
+}
+
+#[derive(Debug, Deserialize, DeserializeErrorHandler, Insertable, Serialize)]
+#[table_name = "webhooks"]
+pub struct CreateWebhookForm {
+    pub url: String,
+    pub event_types: Vec<String>,
+    pub secret: String,
+}
+
+/// Where a delivery is at. Stored as text in the database
+#[derive(AsExpression, Clone, Copy, Debug, Deserialize, Eq, FromSqlRow, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+#[sql_type = "Text"]
+pub enum DeliveryStatus {
+    /// Not sent yet or waiting to be retried
+    Pending,
+    Delivered,
+    /// Every attempt failed. The delivery is in the dead letters
+    Failed,
+}
+
+impl DeliveryStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryStatus::Pending => "pending",
+            DeliveryStatus::Delivered => "delivered",
+            DeliveryStatus::Failed => "failed",
+        }
+    }
+}
+
+impl ToSql<Text, Pg> for DeliveryStatus {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        out.write_all(self.as_str().as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Text, Pg> for DeliveryStatus {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match not_none!(bytes) {
+            b"pending" => Ok(DeliveryStatus::Pending),
+            b"delivered" => Ok(DeliveryStatus::Delivered),
+            b"failed" => Ok(DeliveryStatus::Failed),
+            _ => Err("Unrecognized delivery status".into()),
+        }
+    }
+}
+
+/// Delivery model. Matches the database. One change event being sent to one webhook
+#[derive(Clone, Debug, Identifiable, Queryable, Serialize)]
+#[table_name = "webhook_deliveries"]
+pub struct WebhookDelivery {
+    pub id: i64,
+    pub webhook_id: Uuid,
+    pub event_id: i64,
+    pub status: DeliveryStatus,
+    pub attempts: i32,
+    #[serde(skip)]
+    pub next_attempt_at: SystemTime,
+    /// The status code of the last response. None when there was no response
+    pub response_status: Option<i32>,
+    pub last_error: Option<String>,
+    #[serde(skip)]
+    pub created_at: SystemTime,
+    #[serde(skip)]
+    pub updated_at: Option<SystemTime>,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "webhook_deliveries"]
+pub struct NewDelivery {
+    pub webhook_id: Uuid,
+    pub event_id: i64,
+}
+
+/// What happened on an attempt to deliver. Saved on the delivery
+#[derive(AsChangeset, Debug)]
+#[table_name = "webhook_deliveries"]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct DeliveryAttempt {
+    pub status: DeliveryStatus,
+    pub attempts: i32,
+    pub next_attempt_at: SystemTime,
+    pub response_status: Option<i32>,
+    pub last_error: Option<String>,
+    pub updated_at: Option<SystemTime>,
+}
+
+/// A delivery that ran out of attempts. The event is copied so the dead letter
+/// is kept when the event and the delivery are deleted
+#[derive(Debug, Insertable)]
+#[table_name = "webhook_dead_letters"]
+pub struct NewDeadLetter {
+    pub delivery_id: i64,
+    pub webhook_id: Uuid,
+    pub event_id: i64,
+    pub last_error: String,
+    pub event_type: String,
+    pub data: Value,
+}
+
+/// Query string filters for the delivery log
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct DeliveriesQuery {
+    pub status: Option<DeliveryStatus>,
+}
@@ -0,0 +1,54 @@
+mod common;
+
+#[cfg(test)]
+mod cache_test {
+    use std::thread;
+    use std::time::Duration;
+    use uuid::Uuid;
+
+    use players_api::cache::RecordCache;
+
+    #[test]
+    fn test_insert_unless_evicted_skips_records_read_before_an_eviction() {
+        let cache = RecordCache::default();
+        let id = Uuid::new_v4();
+
+        let generation = cache.generation();
+        cache.evict(&id);
+        cache.insert_unless_evicted(id, "stale", generation);
+        assert_eq!(cache.get(&id), None);
+
+        let generation = cache.generation();
+        cache.insert_unless_evicted(id, "fresh", generation);
+        assert_eq!(cache.get(&id), Some("fresh"));
+    }
+
+    #[test]
+    fn test_records_expire() {
+        let cache = RecordCache::new(10, Duration::from_millis(50));
+        let id = Uuid::new_v4();
+
+        cache.insert(id, "record");
+        assert_eq!(cache.get(&id), Some("record"));
+
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(cache.get(&id), None);
+    }
+
+    #[test]
+    fn test_oldest_record_is_evicted_when_full() {
+        let cache = RecordCache::new(2, Duration::from_secs(60));
+        let ids: Vec<Uuid> = (0..3).map(|_| Uuid::new_v4()).collect();
+
+        for id in &ids {
+            cache.insert(*id, *id);
+            // Each record is inserted at a different instant
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&ids[0]), None);
+        assert_eq!(cache.get(&ids[1]), Some(ids[1]));
+        assert_eq!(cache.get(&ids[2]), Some(ids[2]));
+    }
+}
@@ -1,10 +1,21 @@
 use actix_http::Request;
-use actix_web::{test, App};
+use actix_web::{test, App, HttpMessage};
 use actix_web::http::StatusCode;
+use actix_web::http::header::{HeaderName, HeaderValue};
 use actix_web::dev::ServiceResponse;
+use jsonwebtoken::{encode, EncodingKey, Header};
 use serde::de::DeserializeOwned;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
 
-use players_api::register;
+use players_api::auth::{ServiceClaims, SERVICE_TOKEN_HEADER};
+use players_api::cache::Caches;
+use players_api::db::unconnected_pool;
+use players_api::events::EventBus;
+use players_api::repository::Repositories;
+use players_api::signing::RequestSigning;
+use players_api::{register, Config};
 use players_api::PgPool;
 
 // Re-export the db connection
@@ -17,8 +28,54 @@ async fn get_body<T>(response: ServiceResponse) -> T
     serde_json::from_str(&body).unwrap()
 }
 
-async fn call_request(db_pool: &PgPool, request: Request) -> ServiceResponse {
-    let mut app = test::init_service(App::new().configure(register(db_pool.clone()))).await;
+const SERVICE_TOKEN_SECRET: &str = "test secret";
+
+/// Creates a service token like the one the gateway sends for a user with the role
+pub fn service_token(role: &str) -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    let claims = ServiceClaims {
+        sub: Uuid::new_v4(),
+        role: role.to_string(),
+        exp: now + 60,
+    };
+
+    encode(&Header::default(), &claims, &EncodingKey::from_secret(SERVICE_TOKEN_SECRET.as_bytes())).unwrap()
+}
+
+/// Calls the request with the players and teams in the database
+async fn call_request(
+    db_pool: &PgPool,
+    request: Request,
+    request_signing: Option<RequestSigning>,
+    events: Arc<EventBus>,
+) -> ServiceResponse {
+    call_request_with_repositories(db_pool, request, request_signing, events, Repositories::postgres(db_pool)).await
+}
+
+/// Calls the request. Requests are made for an admin unless they already
+/// have a service token. Signatures are only checked when there is a `request_signing`
+async fn call_request_with_repositories(
+    db_pool: &PgPool,
+    mut request: Request,
+    request_signing: Option<RequestSigning>,
+    events: Arc<EventBus>,
+    repositories: Repositories,
+) -> ServiceResponse {
+    let header = HeaderName::from_bytes(SERVICE_TOKEN_HEADER.as_bytes()).unwrap();
+    if !request.headers().contains_key(&header) {
+        let token = HeaderValue::from_str(&service_token("admin")).unwrap();
+        request.headers_mut().insert(header, token);
+    }
+
+    let mut app = test::init_service(
+        App::new().configure(register(db_pool.clone(), Config {
+            service_token_secret: SERVICE_TOKEN_SECRET.to_string(),
+            request_signing,
+            events,
+            caches: Arc::new(Caches::default()),
+            repositories,
+        }))
+    ).await;
 
     test::call_service(&mut app, request).await
 }
@@ -26,7 +83,7 @@ async fn call_request(db_pool: &PgPool, request: Request) -> ServiceResponse {
 /// Calls the request and gets the status and response
 pub async fn get_response<T>(db_pool: &PgPool, request: Request) -> (StatusCode, T) 
     where T: DeserializeOwned {
-        let response = call_request(db_pool, request).await;
+        let response = call_request(db_pool, request, None, Arc::new(EventBus::default())).await;
         let status = response.status();
         let body = get_body(response).await;
 
@@ -36,6 +93,35 @@ pub async fn get_response<T>(db_pool: &PgPool, request: Request) -> (StatusCode,
 /// Calls the request and just returns the status. For requests
 /// that do not have any response body so we don't try to deserialize it
 pub async fn get_status(db_pool: &PgPool, request: Request) -> StatusCode {
-    let response = call_request(db_pool, request).await;
+    let response = call_request(db_pool, request, None, Arc::new(EventBus::default())).await;
+    response.status()
+}
+
+/// Calls the request with the signatures checked and just returns the status
+pub async fn get_signed_status(db_pool: &PgPool, request: Request, request_signing: RequestSigning) -> StatusCode {
+    let response = call_request(db_pool, request, Some(request_signing), Arc::new(EventBus::default())).await;
     response.status()
 }
+
+/// Calls the request with the events published to `events`
+pub async fn call_request_with_events(db_pool: &PgPool, request: Request, events: Arc<EventBus>) -> ServiceResponse {
+    call_request(db_pool, request, None, events).await
+}
+
+/// Calls the request with the players and teams kept in `repositories`, i.e.
+/// in memory or in SQLite. Postgres is never connected to so only the player
+/// and team routes can be called
+pub async fn get_repositories_response<T>(repositories: &Repositories, request: Request) -> (StatusCode, T)
+    where T: DeserializeOwned {
+        let response = call_request_with_repositories(
+            &unconnected_pool(),
+            request,
+            None,
+            Arc::new(EventBus::default()),
+            repositories.clone(),
+        ).await;
+        let status = response.status();
+        let body = get_body(response).await;
+
+        (status, body)
+}
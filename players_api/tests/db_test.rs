@@ -0,0 +1,31 @@
+mod common;
+
+#[cfg(test)]
+mod db_test {
+    use actix_web::{http, test};
+    use dotenv::dotenv;
+    use std::env;
+    use std::time::Duration;
+
+    use players_api::db::{build_pool, PoolConfig};
+    use crate::common::get_response;
+
+    #[actix_rt::test]
+    async fn test_request_returns_503_when_pool_is_exhausted() {
+        dotenv().ok();
+        let database_url = env::var("DATABASE_URL_TEST").expect("DATABASE_URL_TEST must be set");
+        let db_pool = build_pool(&database_url, &PoolConfig {
+            max_size: 1,
+            min_idle: None,
+            connection_timeout: Duration::from_millis(100),
+        }).unwrap();
+        // Holds the only connection so the request times out waiting for one
+        let _connection = db_pool.get().unwrap();
+
+        let req = test::TestRequest::get().uri("/teams").to_request();
+        let (status, body): (http::StatusCode, serde_json::Value) = get_response(&db_pool, req).await;
+
+        assert_eq!(status, http::StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["code"], "database_unavailable");
+    }
+}
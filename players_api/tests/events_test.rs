@@ -0,0 +1,273 @@
+mod common;
+
+#[cfg(test)]
+mod events_test {
+    use actix_web::test;
+    use diesel::{Connection, ExpressionMethods, RunQueryDsl};
+    use diesel::query_dsl::methods::FindDsl;
+    use futures::stream::StreamExt;
+    use std::sync::{mpsc, Arc};
+    use std::thread;
+    use std::time::{Duration, SystemTime};
+    use uuid::Uuid;
+
+    use players_api::common::PROBLEM_CONTENT_TYPE;
+    use players_api::events::{self, ChangeEvent, EventBus, StoredEvent, LAST_EVENT_ID_HEADER};
+    use players_api::events::models::Event;
+    use players_api::players::models::{CreatePlayerForm, Player, UpdatePlayerForm};
+    use players_api::schema::events::table as events_table;
+    use players_api::schema::players::table as players_table;
+    use players_api::schema::teams::table as teams_table;
+    use players_api::teams::models::{Team, UpdateTeamForm};
+    use crate::common::call_request_with_events;
+    use crate::common::db_connection::get_pool;
+
+    fn saints(id: Uuid) -> Team {
+        Team {
+            id,
+            display_name: "New Orleans Saints".to_string(),
+            abbreviation: "NO".to_string(),
+            created_at: None,
+            updated_at: None,
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_create_player_records_and_publishes_player_created() {
+        let db_pool = get_pool();
+        let connection = db_pool.get().unwrap();
+        let events = Arc::new(EventBus::default());
+        let mut subscriber = events.subscribe();
+
+        let req = test::TestRequest::post().uri("/players").set_json(
+            &CreatePlayerForm {
+                first_name: "Alvin".to_string(),
+                last_name: "Kamara".to_string(),
+                team_id: None,
+                rookie: false,
+            }
+        ).to_request();
+        let response = call_request_with_events(&db_pool, req, events).await;
+        assert!(response.status().is_success());
+
+        let event = subscriber.next().await.unwrap();
+        match &event.event {
+            ChangeEvent::PlayerCreated(player) => assert_eq!(player.last_name, "Kamara"),
+            event => panic!("Expected a PlayerCreated event, got {:?}", event),
+        }
+
+        let stored = events_table.find(event.id).first::<Event>(&connection).unwrap();
+        assert_eq!(stored.event_type, "PlayerCreated");
+    }
+
+    #[actix_rt::test]
+    async fn test_update_player_publishes_player_updated() {
+        let db_pool = get_pool();
+        let connection = db_pool.get().unwrap();
+        let events = Arc::new(EventBus::default());
+        let mut subscriber = events.subscribe();
+
+        let id = Uuid::new_v4();
+        diesel::insert_into(players_table)
+            .values(Player {
+                id,
+                first_name: "Drew".to_string(),
+                last_name: "Bress".to_string(),
+                created_at: None,
+                updated_at: None,
+                team_id: None,
+                rookie: false,
+            })
+            .execute(&connection).unwrap();
+
+        let req = test::TestRequest::put().uri(format!("/players/{}", id).as_str()).set_json(
+            &UpdatePlayerForm {
+                first_name: "Drew".to_string(),
+                last_name: "Brees".to_string(),
+                team_id: None,
+                rookie: Some(false),
+            }
+        ).to_request();
+        let response = call_request_with_events(&db_pool, req, events).await;
+        assert!(response.status().is_success());
+
+        match subscriber.next().await.map(|event| event.event) {
+            Some(ChangeEvent::PlayerUpdated(player)) => {
+                assert_eq!(player.id, id);
+                assert_eq!(player.last_name, "Brees");
+            },
+            event => panic!("Expected a PlayerUpdated event, got {:?}", event),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_delete_team_publishes_team_deleted() {
+        let db_pool = get_pool();
+        let connection = db_pool.get().unwrap();
+        let events = Arc::new(EventBus::default());
+        let mut subscriber = events.subscribe();
+
+        let id = Uuid::new_v4();
+        diesel::insert_into(teams_table)
+            .values(saints(id))
+            .execute(&connection).unwrap();
+
+        let req = test::TestRequest::delete().uri(format!("/teams/{}", id).as_str()).to_request();
+        let response = call_request_with_events(&db_pool, req, events).await;
+        assert!(response.status().is_success());
+
+        assert_eq!(subscriber.next().await.map(|event| event.event), Some(ChangeEvent::TeamDeleted { id }));
+    }
+
+    #[actix_rt::test]
+    async fn test_update_team_not_found_does_not_publish() {
+        let db_pool = get_pool();
+        let events = Arc::new(EventBus::default());
+        let mut subscriber = events.subscribe();
+
+        let req = test::TestRequest::put().uri(format!("/teams/{}", Uuid::new_v4()).as_str()).set_json(
+            &UpdateTeamForm {
+                display_name: "This doesn't matter".to_string(),
+                abbreviation: "TDM".to_string()
+            }
+        ).to_request();
+        let response = call_request_with_events(&db_pool, req, events.clone()).await;
+        assert_eq!(response.status(), actix_web::http::StatusCode::NOT_FOUND);
+
+        // The event would have been published before the response was sent
+        assert!(subscriber.try_next().is_err());
+    }
+
+    #[actix_rt::test]
+    async fn test_lagging_subscriber_is_dropped() {
+        let events = EventBus::default();
+        let subscriber = events.subscribe();
+
+        for id in 0..events::SUBSCRIBER_BUFFER * 2 {
+            events.publish(StoredEvent { id: id as i64, event: ChangeEvent::TeamDeleted { id: Uuid::new_v4() } });
+        }
+        drop(events);
+
+        // The buffered events are still sent, then the events end
+        let received = subscriber.collect::<Vec<_>>().await;
+        assert!(received.len() < events::SUBSCRIBER_BUFFER * 2);
+    }
+
+    #[actix_rt::test]
+    async fn test_get_events_streams_changes() {
+        let db_pool = get_pool();
+        let connection = db_pool.get().unwrap();
+        let events = Arc::new(EventBus::default());
+
+        let req = test::TestRequest::get().uri("/events").to_request();
+        let mut response = call_request_with_events(&db_pool, req, events.clone()).await;
+        assert!(response.status().is_success());
+        assert_eq!(response.headers().get("content-type").unwrap(), "text/event-stream");
+
+        let team: Team = diesel::insert_into(teams_table)
+            .values(saints(Uuid::new_v4()))
+            .get_result(&connection).unwrap();
+        let event = events::record(&connection, ChangeEvent::TeamUpdated(team.clone())).unwrap();
+        events.publish(event.clone());
+
+        let chunk = response.take_body().next().await.unwrap().unwrap();
+        let chunk = std::str::from_utf8(&chunk).unwrap();
+        let expected = serde_json::to_string(&ChangeEvent::TeamUpdated(team)).unwrap();
+        assert_eq!(chunk, format!("id: {}\nevent: TeamUpdated\ndata: {}\n\n", event.id, expected));
+    }
+
+    #[actix_rt::test]
+    async fn test_get_events_sends_missed_events_after_last_event_id() {
+        let db_pool = get_pool();
+        let connection = db_pool.get().unwrap();
+
+        let first = events::record(&connection, ChangeEvent::PlayerDeleted { id: Uuid::new_v4() }).unwrap();
+        let second = events::record(&connection, ChangeEvent::TeamDeleted { id: Uuid::new_v4() }).unwrap();
+
+        let req = test::TestRequest::get()
+            .uri("/events")
+            .header(LAST_EVENT_ID_HEADER, first.id.to_string())
+            .to_request();
+        let mut response = call_request_with_events(&db_pool, req, Arc::new(EventBus::default())).await;
+        assert!(response.status().is_success());
+
+        // Other tests write events at the same time so read until the second
+        // event, checking the first is not sent again
+        let mut body = response.take_body();
+        loop {
+            let chunk = body.next().await.unwrap().unwrap();
+            let chunk = std::str::from_utf8(&chunk).unwrap();
+            assert!(!chunk.starts_with(&format!("id: {}\n", first.id)));
+
+            if chunk == second.to_sse() {
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn test_events_are_committed_in_the_order_of_their_ids() {
+        let db_pool = get_pool();
+        let connection = db_pool.get().unwrap();
+        let (sender, receiver) = mpsc::channel();
+
+        let (first, writer) = connection.transaction::<_, diesel::result::Error, _>(|| {
+            let first = events::record(&connection, ChangeEvent::PlayerDeleted { id: Uuid::new_v4() })?;
+
+            // Writes another event while the first transaction is still open
+            let writer_pool = db_pool.clone();
+            let writer = thread::spawn(move || {
+                let connection = writer_pool.get().unwrap();
+                let second = connection.transaction::<_, diesel::result::Error, _>(|| {
+                    events::record(&connection, ChangeEvent::TeamDeleted { id: Uuid::new_v4() })
+                }).unwrap();
+                sender.send(second).unwrap();
+            });
+
+            // The second event waits for the first to be committed
+            assert!(receiver.recv_timeout(Duration::from_millis(500)).is_err());
+            Ok((first, writer))
+        }).unwrap();
+
+        let second = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        writer.join().unwrap();
+        assert!(second.id > first.id);
+
+        let after_first = events::events_after(&connection, first.id - 1).unwrap();
+        let first_index = after_first.iter().position(|event| event.id == first.id).unwrap();
+        let second_index = after_first.iter().position(|event| event.id == second.id).unwrap();
+        assert!(first_index < second_index);
+    }
+
+    #[test]
+    fn test_delete_before_keeps_newer_events() {
+        let db_pool = get_pool();
+        let connection = db_pool.get().unwrap();
+
+        let old = events::record(&connection, ChangeEvent::PlayerDeleted { id: Uuid::new_v4() }).unwrap();
+        diesel::update(events_table.find(old.id))
+            .set(players_api::schema::events::created_at.eq(SystemTime::now() - events::RETENTION * 2))
+            .execute(&connection)
+            .unwrap();
+        let new = events::record(&connection, ChangeEvent::TeamDeleted { id: Uuid::new_v4() }).unwrap();
+
+        events::delete_before(&connection, SystemTime::now() - events::RETENTION).unwrap();
+
+        assert!(events_table.find(old.id).first::<Event>(&connection).is_err());
+        assert!(events_table.find(new.id).first::<Event>(&connection).is_ok());
+    }
+
+    #[actix_rt::test]
+    async fn test_get_events_rejects_bad_last_event_id() {
+        let db_pool = get_pool();
+
+        let req = test::TestRequest::get()
+            .uri("/events")
+            .header(LAST_EVENT_ID_HEADER, "not an id")
+            .to_request();
+        let response = call_request_with_events(&db_pool, req, Arc::new(EventBus::default())).await;
+
+        assert_eq!(response.status(), actix_web::http::StatusCode::BAD_REQUEST);
+        assert_eq!(response.headers().get("content-type").unwrap(), PROBLEM_CONTENT_TYPE);
+    }
+}
@@ -0,0 +1,112 @@
+mod common;
+
+// Calls the player and team handlers with the in-memory repositories, so
+// these do not need a database
+#[cfg(test)]
+mod in_memory_test {
+    use actix_web::{http, test};
+    use uuid::Uuid;
+
+    use players_api::players::models::{CreatePlayerForm, UpdatePlayerForm};
+    use players_api::repository::Repositories;
+    use players_api::teams::models::{CreateTeamForm, Team};
+    use crate::common::get_repositories_response;
+
+    fn create_team(repositories: &Repositories) -> Team {
+        let (team, _) = repositories.teams.create(CreateTeamForm {
+            display_name: "Buffalo Bills".to_string(),
+            abbreviation: "BUF".to_string(),
+        }).unwrap();
+
+        team
+    }
+
+    fn player_form(first_name: &str, team_id: Option<Uuid>, rookie: bool) -> CreatePlayerForm {
+        CreatePlayerForm {
+            first_name: first_name.to_string(),
+            last_name: "Allen".to_string(),
+            team_id,
+            rookie,
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_create_team_then_get_team() {
+        let repositories = Repositories::in_memory();
+
+        let req = test::TestRequest::post().uri("/teams").set_json(
+            &CreateTeamForm {
+                display_name: "Buffalo Bills".to_string(),
+                abbreviation: "BUF".to_string(),
+            }
+        ).to_request();
+        let (status, created): (http::StatusCode, Team) = get_repositories_response(&repositories, req).await;
+        assert!(status.is_success());
+
+        let req = test::TestRequest::get().uri(format!("/teams/{}", created.id).as_str()).to_request();
+        let (status, team): (http::StatusCode, Team) = get_repositories_response(&repositories, req).await;
+        assert!(status.is_success());
+        assert_eq!(team.display_name, "Buffalo Bills");
+    }
+
+    #[actix_rt::test]
+    async fn test_get_players_filters_rookies() {
+        let repositories = Repositories::in_memory();
+        let team = create_team(&repositories);
+        repositories.players.create(player_form("Josh", Some(team.id), false)).unwrap();
+        let (rookie, _) = repositories.players.create(player_form("Devin", Some(team.id), true)).unwrap();
+
+        let req = test::TestRequest::get().uri("/players?rookie=true").to_request();
+        let (status, body): (http::StatusCode, serde_json::Value) = get_repositories_response(&repositories, req).await;
+
+        assert!(status.is_success());
+        assert_eq!(body.as_array().unwrap().len(), 1);
+        assert_eq!(body[0]["player"]["id"], rookie.id.to_string());
+        assert_eq!(body[0]["team"]["id"], team.id.to_string());
+    }
+
+    #[actix_rt::test]
+    async fn test_create_player_with_nonexistent_team_returns_400() {
+        let repositories = Repositories::in_memory();
+
+        let req = test::TestRequest::post().uri("/players")
+            .set_json(&player_form("Josh", Some(Uuid::new_v4()), false))
+            .to_request();
+        let (status, body): (http::StatusCode, serde_json::Value) = get_repositories_response(&repositories, req).await;
+
+        assert_eq!(status, http::StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "foreign_key_violation");
+    }
+
+    #[actix_rt::test]
+    async fn test_update_player_returns_404() {
+        let repositories = Repositories::in_memory();
+
+        let req = test::TestRequest::put().uri(format!("/players/{}", Uuid::new_v4()).as_str()).set_json(
+            &UpdatePlayerForm {
+                first_name: "Josh".to_string(),
+                last_name: "Allen".to_string(),
+                team_id: None,
+                rookie: Some(false),
+            }
+        ).to_request();
+        let (status, body): (http::StatusCode, serde_json::Value) = get_repositories_response(&repositories, req).await;
+
+        assert_eq!(status, http::StatusCode::NOT_FOUND);
+        assert_eq!(body["detail"], "Player not found");
+    }
+
+    #[actix_rt::test]
+    async fn test_delete_team_with_players_returns_400() {
+        let repositories = Repositories::in_memory();
+        let team = create_team(&repositories);
+        repositories.players.create(player_form("Josh", Some(team.id), false)).unwrap();
+
+        let req = test::TestRequest::delete().uri(format!("/teams/{}", team.id).as_str()).to_request();
+        let (status, body): (http::StatusCode, serde_json::Value) = get_repositories_response(&repositories, req).await;
+
+        assert_eq!(status, http::StatusCode::BAD_REQUEST);
+        assert_eq!(body["detail"], "Cannot delete team: players still exist in the team");
+        assert!(repositories.teams.find(team.id).is_ok());
+    }
+}
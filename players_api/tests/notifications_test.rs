@@ -0,0 +1,114 @@
+mod common;
+
+#[cfg(test)]
+mod notifications_test {
+    use diesel::RunQueryDsl;
+    use dotenv::dotenv;
+    use fallible_iterator::FallibleIterator;
+    use postgres::{Client, NoTls};
+    use std::env;
+    use std::time::Duration;
+    use uuid::Uuid;
+
+    use players_api::cache::Caches;
+    use players_api::notifications::{TableChange, CHANNEL};
+    use players_api::players::models::{Player, PlayerWithTeam};
+    use players_api::schema::teams::table as teams_table;
+    use players_api::teams::models::Team;
+    use crate::common::db_connection::get_pool;
+
+    fn team(id: Uuid) -> Team {
+        Team {
+            id,
+            display_name: "New Orleans Saints".to_string(),
+            abbreviation: "NO".to_string(),
+            created_at: None,
+            updated_at: None,
+        }
+    }
+
+    fn player_with_team(team: Option<Team>) -> PlayerWithTeam {
+        PlayerWithTeam {
+            player: Player {
+                id: Uuid::new_v4(),
+                first_name: "Alvin".to_string(),
+                last_name: "Kamara".to_string(),
+                created_at: None,
+                updated_at: None,
+                team_id: team.as_ref().map(|team| team.id),
+                rookie: false,
+            },
+            team,
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_team_change_evicts_team_and_its_players() {
+        let caches = Caches::default();
+        let saints = team(Uuid::new_v4());
+        let on_team = player_with_team(Some(saints.clone()));
+        let free_agent = player_with_team(None);
+
+        caches.teams.insert(saints.id, saints.clone());
+        caches.players.insert(on_team.player.id, on_team.clone());
+        caches.players.insert(free_agent.player.id, free_agent.clone());
+
+        TableChange {
+            table: "teams".to_string(),
+            operation: "UPDATE".to_string(),
+            id: saints.id,
+        }.evict(&caches);
+
+        assert_eq!(caches.teams.get(&saints.id), None);
+        assert_eq!(caches.players.get(&on_team.player.id), None);
+        assert_eq!(caches.players.get(&free_agent.player.id), Some(free_agent));
+    }
+
+    #[actix_rt::test]
+    async fn test_player_change_only_evicts_player() {
+        let caches = Caches::default();
+        let first = player_with_team(None);
+        let second = player_with_team(None);
+
+        caches.players.insert(first.player.id, first.clone());
+        caches.players.insert(second.player.id, second.clone());
+
+        TableChange {
+            table: "players".to_string(),
+            operation: "DELETE".to_string(),
+            id: first.player.id,
+        }.evict(&caches);
+
+        assert_eq!(caches.players.get(&first.player.id), None);
+        assert_eq!(caches.players.get(&second.player.id), Some(second));
+    }
+
+    // The blocking postgres client starts its own runtime so this cannot run on actix's
+    #[test]
+    fn test_changing_team_notifies_listeners() {
+        dotenv().ok();
+        let database_url = env::var("DATABASE_URL_TEST").expect("DATABASE_URL_TEST must be set");
+        let mut client = Client::connect(&database_url, NoTls).unwrap();
+        client.batch_execute(&format!("LISTEN {}", CHANNEL)).unwrap();
+
+        let connection = get_pool().get().unwrap();
+        let id = Uuid::new_v4();
+        diesel::insert_into(teams_table)
+            .values(team(id))
+            .execute(&connection).unwrap();
+
+        // Other tests change teams at the same time so look for this one
+        let mut notifications = client.notifications();
+        let change = notifications
+            .timeout_iter(Duration::from_secs(5))
+            .map(|notification| Ok(serde_json::from_str::<TableChange>(notification.payload()).unwrap()))
+            .find(|change| Ok(change.id == id))
+            .unwrap();
+
+        assert_eq!(change, Some(TableChange {
+            table: "teams".to_string(),
+            operation: "INSERT".to_string(),
+            id,
+        }));
+    }
+}
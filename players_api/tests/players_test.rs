@@ -41,6 +41,7 @@ mod players_tests {
                 created_at: None,
                 updated_at: None,
                 team_id: None,
+                rookie: false,
             }
         };
         diesel::insert_into(players_table)
@@ -57,6 +58,44 @@ mod players_tests {
         });
     }
 
+    #[actix_rt::test]
+    async fn test_get_players_filters_rookies() {
+        let db_pool = get_pool();
+        let connection = db_pool.get().unwrap();
+
+        let rookie_id = Uuid::new_v4();
+        let veteran_id = Uuid::new_v4();
+        diesel::insert_into(players_table)
+            .values(vec![
+                Player {
+                    id: rookie_id,
+                    first_name: "Joe".to_string(),
+                    last_name: "Burrow".to_string(),
+                    created_at: None,
+                    updated_at: None,
+                    team_id: None,
+                    rookie: true,
+                },
+                Player {
+                    id: veteran_id,
+                    first_name: "Tom".to_string(),
+                    last_name: "Brady".to_string(),
+                    created_at: None,
+                    updated_at: None,
+                    team_id: None,
+                    rookie: false,
+                },
+            ])
+            .execute(&connection).unwrap();
+
+        let req = test::TestRequest::get().uri("/players?rookie=true").to_request();
+        let (_, result): (_, Vec<PlayerWithTeam>) = get_response(&db_pool, req).await;
+
+        assert!(result.iter().all(|p| p.player.rookie));
+        assert!(result.iter().any(|p| p.player.id == rookie_id));
+        assert!(!result.iter().any(|p| p.player.id == veteran_id));
+    }
+
     #[actix_rt::test]
     async fn test_get_player_returns_player() {
         let db_pool = get_pool();
@@ -81,6 +120,7 @@ mod players_tests {
                 created_at: None,
                 updated_at: None,
                 team_id: Some(team_id),
+                rookie: false,
             }
         };
 
@@ -131,6 +171,7 @@ mod players_tests {
                 first_name: "Jace".to_string(),
                 last_name: "Sternberger".to_string(),
                 team_id: Some(team_id),
+                rookie: false,
             }
         ).to_request();
         let status = get_status(&db_pool, req).await;
@@ -154,6 +195,7 @@ mod players_tests {
                 first_name: "Jace".to_string(),
                 last_name: "Sternberger".to_string(),
                 team_id: Some(random_id),
+                rookie: false,
             }
         ).to_request();
         let status = get_status(&db_pool, req).await;
@@ -170,6 +212,7 @@ mod players_tests {
                 first_name: "Christine".to_string(),
                 last_name: "Michael".to_string(),
                 team_id: None,
+                rookie: false,
             }
         ).to_request();
         let (status, _): (_, Player) = get_response(&db_pool, req).await;
@@ -196,6 +239,7 @@ mod players_tests {
                 created_at: None,
                 updated_at: None,
                 team_id: None,
+                rookie: false,
             }
         };
         diesel::insert_into(players_table)
@@ -207,6 +251,7 @@ mod players_tests {
                 first_name: "Kyle".to_string(),
                 last_name: "Allen".to_string(),
                 team_id: None,
+                rookie: Some(false),
             }
         ).to_request();
         let status = get_status(&db_pool, req).await;
@@ -225,6 +270,7 @@ mod players_tests {
                 first_name: "Kyle".to_string(),
                 last_name: "Allen".to_string(),
                 team_id: None,
+                rookie: Some(false),
             }
         ).to_request();
         let status = get_status(&db_pool, req).await;
@@ -253,6 +299,7 @@ mod players_tests {
             created_at: None,
             updated_at: None,
             team_id: Some(browns.id),
+            rookie: false,
         };
         diesel::insert_into(teams_table) 
             .values(browns)
@@ -266,6 +313,7 @@ mod players_tests {
                 first_name: "Johnny".to_string(),
                 last_name: "Manziel".to_string(),
                 team_id: None,
+                rookie: Some(false),
             }
         ).to_request();
         let status = get_status(&db_pool, req).await;
@@ -276,6 +324,43 @@ mod players_tests {
         assert_eq!(player.team_id, None);
     }
 
+    #[actix_rt::test]
+    async fn test_update_player_without_rookie_keeps_rookie() {
+        let db_pool = get_pool();
+        let connection = db_pool.get().unwrap();
+
+        let id = Uuid::new_v4();
+        diesel::insert_into(players_table)
+            .values(Player {
+                id,
+                first_name: "Joe".to_string(),
+                last_name: "Burrow".to_string(),
+                created_at: None,
+                updated_at: None,
+                team_id: None,
+                rookie: true,
+            })
+            .execute(&connection).unwrap();
+
+        // rookie is left out of the request body
+        let req = test::TestRequest::put().uri(format!("/players/{}", id).as_str()).set_json(
+            &UpdatePlayerForm {
+                first_name: "Joey".to_string(),
+                last_name: "Burrow".to_string(),
+                team_id: None,
+                rookie: None,
+            }
+        ).to_request();
+        let (status, player): (_, Player) = get_response(&db_pool, req).await;
+
+        assert!(status.is_success());
+        assert_eq!(player.first_name, "Joey");
+        assert!(player.rookie);
+
+        let player: Player = players_table.find(id).first(&connection).expect("Expected to find a player");
+        assert!(player.rookie);
+    }
+
     #[actix_rt::test]
     async fn test_delete_player_deletes_player() {
         let db_pool = get_pool();
@@ -289,6 +374,7 @@ mod players_tests {
             created_at: None,
             updated_at: None,
             team_id: None,
+            rookie: false,
         };
         diesel::insert_into(players_table)
             .values(christine)
@@ -302,4 +388,25 @@ mod players_tests {
         let player = players_table.find(id).first::<Player>(&connection);
         assert_eq!(player, Err(DieselError::NotFound));
     }
+
+    #[actix_rt::test]
+    async fn test_create_player_with_empty_name_returns_422() {
+        let db_pool = get_pool();
+
+        let req = test::TestRequest::post().uri("/players").set_json(
+            &CreatePlayerForm {
+                first_name: "".to_string(),
+                last_name: "Bell".to_string(),
+                team_id: None,
+                rookie: false,
+            }
+        ).to_request();
+        let (status, body): (http::StatusCode, serde_json::Value) = get_response(&db_pool, req).await;
+
+        assert_eq!(status, http::StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["code"], "validation_failed");
+        assert_eq!(body["errors"], serde_json::json!([
+            { "field": "first_name", "message": "first_name must be between 1 and 64 characters long" },
+        ]));
+    }
 }
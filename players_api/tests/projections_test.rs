@@ -0,0 +1,127 @@
+mod common;
+
+#[cfg(test)]
+mod projections_test {
+    use actix_web::{http, test};
+    use diesel::RunQueryDsl;
+    use uuid::Uuid;
+
+    use players_api;
+    use players_api::schema::players::table as players_table;
+    use players_api::players::models::Player;
+    use players_api::projections::models::{AverageProjection, Projection, UpsertProjectionForm};
+    use crate::common::{get_response, get_status};
+    use crate::common::db_connection::get_pool;
+
+    fn insert_player() -> Uuid {
+        let connection = get_pool().get().unwrap();
+
+        let id = Uuid::new_v4();
+        diesel::insert_into(players_table)
+            .values(Player {
+                id,
+                first_name: "Derrick".to_string(),
+                last_name: "Henry".to_string(),
+                created_at: None,
+                updated_at: None,
+                team_id: None,
+                rookie: false,
+            })
+            .execute(&connection).unwrap();
+
+        id
+    }
+
+    fn projection(player_id: Uuid, source: &str, points: f64) -> UpsertProjectionForm {
+        UpsertProjectionForm {
+            player_id,
+            season: 2020,
+            week: 1,
+            source: source.to_string(),
+            points,
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_upsert_projections_creates_and_replaces_projections() {
+        let db_pool = get_pool();
+        let player_id = insert_player();
+
+        let req = test::TestRequest::put().uri("/projections").set_json(
+            &vec![projection(player_id, "espn", 18.5), projection(player_id, "yahoo", 20.0)]
+        ).to_request();
+        let status = get_status(&db_pool, req).await;
+        assert!(status.is_success());
+
+        let req = test::TestRequest::put().uri("/projections").set_json(
+            &vec![projection(player_id, "espn", 12.0)]
+        ).to_request();
+        let status = get_status(&db_pool, req).await;
+        assert!(status.is_success());
+
+        let req = test::TestRequest::get()
+            .uri(format!("/projections?player_id={}&season=2020&week=1", player_id).as_str())
+            .to_request();
+        let (_, result): (_, Vec<Projection>) = get_response(&db_pool, req).await;
+
+        assert_eq!(
+            result.iter().map(|p| (p.source.as_str(), p.points)).collect::<Vec<_>>(),
+            vec![("espn", 12.0), ("yahoo", 20.0)]
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_upsert_projections_returns_400_for_nonexistent_player() {
+        let db_pool = get_pool();
+
+        let req = test::TestRequest::put().uri("/projections").set_json(
+            &vec![projection(Uuid::new_v4(), "espn", 10.0)]
+        ).to_request();
+        let status = get_status(&db_pool, req).await;
+
+        assert_eq!(status, http::StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_rt::test]
+    async fn test_upsert_projections_returns_400_for_duplicate_projections() {
+        let db_pool = get_pool();
+        let player_id = insert_player();
+
+        let req = test::TestRequest::put().uri("/projections").set_json(
+            &vec![projection(player_id, "espn", 10.0), projection(player_id, "espn", 12.0)]
+        ).to_request();
+        let (status, body): (http::StatusCode, serde_json::Value) = get_response(&db_pool, req).await;
+
+        assert_eq!(status, http::StatusCode::BAD_REQUEST);
+        assert_eq!(body["detail"], "A projection can only be given once per player, season, week and source");
+    }
+
+    #[actix_rt::test]
+    async fn test_get_average_projections_averages_sources() {
+        let db_pool = get_pool();
+        let player_id = insert_player();
+
+        let req = test::TestRequest::put().uri("/projections").set_json(
+            &vec![
+                projection(player_id, "espn", 10.0),
+                projection(player_id, "yahoo", 20.0),
+                projection(player_id, "fantasypros", 15.0),
+            ]
+        ).to_request();
+        let status = get_status(&db_pool, req).await;
+        assert!(status.is_success());
+
+        let req = test::TestRequest::get()
+            .uri(format!("/projections/average?player_id={}&season=2020&week=1", player_id).as_str())
+            .to_request();
+        let (_, result): (_, Vec<AverageProjection>) = get_response(&db_pool, req).await;
+
+        assert_eq!(result, vec![AverageProjection {
+            player_id,
+            season: 2020,
+            week: 1,
+            points: 15.0,
+            sources: 3,
+        }]);
+    }
+}
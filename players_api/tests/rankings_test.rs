@@ -0,0 +1,174 @@
+mod common;
+
+#[cfg(test)]
+mod rankings_test {
+    use actix_web::{http, test};
+    use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+    use uuid::Uuid;
+
+    use players_api;
+    use players_api::schema::players::{id as players_id, table as players_table};
+    use players_api::players::models::Player;
+    use players_api::rankings::models::{ImportRankingForm, RankingWithPlayer};
+    use crate::common::{get_response, get_status};
+    use crate::common::db_connection::get_pool;
+
+    // Importing replaces every ranking of a scoring format so each test uses its
+    // own scoring format to be able to run in parallel
+
+    fn insert_player(first_name: &str, last_name: &str) -> Uuid {
+        let connection = get_pool().get().unwrap();
+
+        let id = Uuid::new_v4();
+        diesel::insert_into(players_table)
+            .values(Player {
+                id,
+                first_name: first_name.to_string(),
+                last_name: last_name.to_string(),
+                created_at: None,
+                updated_at: None,
+                team_id: None,
+                rookie: false,
+            })
+            .execute(&connection).unwrap();
+
+        id
+    }
+
+    fn ranking(player_id: Uuid, position: &str, overall_rank: i32, position_rank: i32) -> ImportRankingForm {
+        ImportRankingForm {
+            player_id,
+            position: position.to_string(),
+            overall_rank,
+            position_rank,
+            tier: 1,
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_get_rankings_filters_by_position() {
+        let db_pool = get_pool();
+        let mccaffrey = insert_player("Christian", "McCaffrey");
+        let thomas = insert_player("Michael", "Thomas");
+        let barkley = insert_player("Saquon", "Barkley");
+
+        let req = test::TestRequest::put().uri("/rankings/ppr").set_json(
+            &vec![
+                ranking(barkley, "RB", 2, 2),
+                ranking(thomas, "WR", 3, 1),
+                ranking(mccaffrey, "RB", 1, 1),
+            ]
+        ).to_request();
+        let status = get_status(&db_pool, req).await;
+        assert!(status.is_success());
+
+        let req = test::TestRequest::get().uri("/rankings/ppr?position=RB").to_request();
+        let (_, result): (_, Vec<RankingWithPlayer>) = get_response(&db_pool, req).await;
+
+        assert_eq!(
+            result.iter().map(|r| (r.player.id, r.ranking.position_rank)).collect::<Vec<_>>(),
+            vec![(mccaffrey, 1), (barkley, 2)]
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_import_rankings_replaces_rankings() {
+        let db_pool = get_pool();
+        let jackson = insert_player("Lamar", "Jackson");
+        let mahomes = insert_player("Patrick", "Mahomes");
+
+        let req = test::TestRequest::put().uri("/rankings/half").set_json(
+            &vec![ranking(jackson, "QB", 1, 1)]
+        ).to_request();
+        let status = get_status(&db_pool, req).await;
+        assert!(status.is_success());
+
+        let req = test::TestRequest::put().uri("/rankings/half").set_json(
+            &vec![ranking(mahomes, "QB", 1, 1)]
+        ).to_request();
+        let status = get_status(&db_pool, req).await;
+        assert!(status.is_success());
+
+        let req = test::TestRequest::get().uri("/rankings/half").to_request();
+        let (_, result): (_, Vec<RankingWithPlayer>) = get_response(&db_pool, req).await;
+
+        assert_eq!(result.iter().map(|r| r.player.id).collect::<Vec<_>>(), vec![mahomes]);
+    }
+
+    #[actix_rt::test]
+    async fn test_import_rankings_returns_400_for_nonexistent_player() {
+        let db_pool = get_pool();
+
+        let req = test::TestRequest::put().uri("/rankings/standard").set_json(
+            &vec![ranking(Uuid::new_v4(), "TE", 1, 1)]
+        ).to_request();
+        let status = get_status(&db_pool, req).await;
+
+        assert_eq!(status, http::StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_rt::test]
+    async fn test_import_rankings_returns_422_for_rank_below_1() {
+        let db_pool = get_pool();
+
+        let req = test::TestRequest::put().uri("/rankings/standard").set_json(
+            &vec![ranking(Uuid::new_v4(), "QB", 1, 1), ranking(Uuid::new_v4(), "QB", 0, 2)]
+        ).to_request();
+        let (status, body): (http::StatusCode, serde_json::Value) = get_response(&db_pool, req).await;
+
+        assert_eq!(status, http::StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["errors"], serde_json::json!([
+            { "field": "[1].overall_rank", "message": "overall_rank must be at least 1" },
+        ]));
+    }
+
+    #[actix_rt::test]
+    async fn test_import_rankings_returns_400_for_repeated_overall_rank() {
+        let db_pool = get_pool();
+        let kelce = insert_player("Travis", "Kelce");
+        let kittle = insert_player("George", "Kittle");
+
+        let req = test::TestRequest::put().uri("/rankings/standard").set_json(
+            &vec![ranking(kelce, "TE", 1, 1), ranking(kittle, "TE", 1, 2)]
+        ).to_request();
+        let status = get_status(&db_pool, req).await;
+
+        assert_eq!(status, http::StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_rt::test]
+    async fn test_import_rankings_imports_more_rankings_than_fit_in_one_query() {
+        let db_pool = get_pool();
+        let connection = db_pool.get().unwrap();
+
+        // 11,000 rankings are 66,000 parameters, more than Postgres takes in one query
+        let players: Vec<_> = (0..11_000)
+            .map(|number| Player {
+                id: Uuid::new_v4(),
+                first_name: "Rookie".to_string(),
+                last_name: number.to_string(),
+                created_at: None,
+                updated_at: None,
+                team_id: None,
+                rookie: true,
+            })
+            .collect();
+        for chunk in players.chunks(1000) {
+            diesel::insert_into(players_table).values(chunk).execute(&connection).unwrap();
+        }
+
+        let rankings: Vec<_> = players
+            .iter()
+            .enumerate()
+            .map(|(index, player)| ranking(player.id, "WR", index as i32 + 1, index as i32 + 1))
+            .collect();
+        let req = test::TestRequest::put().uri("/rankings/standard").set_json(&rankings).to_request();
+        let (status, imported): (_, Vec<serde_json::Value>) = get_response(&db_pool, req).await;
+
+        let ids: Vec<_> = players.iter().map(|player| player.id).collect();
+        diesel::delete(players_table.filter(players_id.eq_any(ids))).execute(&connection).unwrap();
+
+        assert!(status.is_success());
+        assert_eq!(imported.len(), 11_000);
+    }
+}
@@ -0,0 +1,105 @@
+mod common;
+
+#[cfg(test)]
+mod signing_test {
+    use actix_http::Request;
+    use actix_web::{http, test};
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use uuid::Uuid;
+
+    use players_api::BULK_JSON_LIMIT;
+    use players_api::signing::{
+        RequestSigning, KEY_ID_HEADER, NONCE_HEADER, SIGNATURE_HEADER, TIMESTAMP_HEADER,
+    };
+    use crate::common::get_signed_status;
+    use crate::common::db_connection::get_pool;
+
+    const KEY_ID: &str = "gateway";
+    const SECRET: &str = "signing secret";
+
+    fn signed_request(key_id: &str, secret: &str, nonce: &str) -> Request {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let signature = RequestSigning::sign(secret, "GET", "/teams?limit=1", timestamp, nonce, b"");
+
+        test::TestRequest::get()
+            .uri("/teams?limit=1")
+            .header(KEY_ID_HEADER, key_id)
+            .header(TIMESTAMP_HEADER, timestamp.to_string())
+            .header(NONCE_HEADER, nonce)
+            .header(SIGNATURE_HEADER, signature)
+            .to_request()
+    }
+
+    fn request_signing() -> RequestSigning {
+        RequestSigning::from_keys_str(&format!("old:old secret,{}:{}", KEY_ID, SECRET))
+    }
+
+    #[actix_rt::test]
+    async fn test_signed_request_is_accepted() {
+        let db_pool = get_pool();
+        let nonce = Uuid::new_v4().to_string();
+
+        let status = get_signed_status(&db_pool, signed_request(KEY_ID, SECRET, &nonce), request_signing()).await;
+
+        assert_eq!(status, http::StatusCode::OK);
+    }
+
+    #[actix_rt::test]
+    async fn test_request_with_bad_signature_is_unauthorized() {
+        let db_pool = get_pool();
+        let nonce = Uuid::new_v4().to_string();
+
+        let unsigned = test::TestRequest::get().uri("/teams").to_request();
+        let status = get_signed_status(&db_pool, unsigned, request_signing()).await;
+        assert_eq!(status, http::StatusCode::UNAUTHORIZED);
+
+        let wrong_secret = signed_request(KEY_ID, "wrong secret", &nonce);
+        let status = get_signed_status(&db_pool, wrong_secret, request_signing()).await;
+        assert_eq!(status, http::StatusCode::UNAUTHORIZED);
+
+        let unknown_key = signed_request("unknown", SECRET, &nonce);
+        let status = get_signed_status(&db_pool, unknown_key, request_signing()).await;
+        assert_eq!(status, http::StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_rt::test]
+    async fn test_replayed_request_is_unauthorized() {
+        let db_pool = get_pool();
+        let nonce = Uuid::new_v4().to_string();
+        let request_signing = request_signing();
+
+        let status = get_signed_status(&db_pool, signed_request(KEY_ID, SECRET, &nonce), request_signing.clone()).await;
+        assert_eq!(status, http::StatusCode::OK);
+
+        let status = get_signed_status(&db_pool, signed_request(KEY_ID, SECRET, &nonce), request_signing).await;
+        assert_eq!(status, http::StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_rt::test]
+    async fn test_signature_matches_the_gateway() {
+        // The gateway's players_api_test checks the same signature
+        let signature = RequestSigning::sign(
+            "test secret",
+            "post",
+            "/players?rookie=true",
+            1_583_798_400,
+            "00112233445566778899aabbccddeeff",
+            br#"{"first_name":"Josh"}"#,
+        );
+        assert_eq!(signature, "966679230345861bd3d78e3dc335c560431e182214a1d9c6ff3c398fb6cf8c34");
+    }
+
+    #[actix_rt::test]
+    async fn test_body_bigger_than_the_limit_is_rejected() {
+        let db_pool = get_pool();
+
+        let req = test::TestRequest::put()
+            .uri("/projections")
+            .header("Content-Type", "application/json")
+            .set_payload(vec![b' '; BULK_JSON_LIMIT + 1])
+            .to_request();
+
+        let status = get_signed_status(&db_pool, req, request_signing()).await;
+        assert_eq!(status, http::StatusCode::PAYLOAD_TOO_LARGE);
+    }
+}
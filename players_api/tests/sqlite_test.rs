@@ -0,0 +1,165 @@
+#![cfg(feature = "sqlite")]
+
+mod common;
+
+// Calls the player and team handlers with the players and teams kept in
+// SQLite, so these do not need Postgres. Run with `--features sqlite`
+#[cfg(test)]
+mod sqlite_test {
+    use actix_web::{http, test};
+    use uuid::Uuid;
+
+    use players_api::players::models::{CreatePlayerForm, UpdatePlayerForm};
+    use players_api::repository::Repositories;
+    use players_api::teams::models::{CreateTeamForm, Team};
+    use crate::common::get_repositories_response;
+
+    /// Each test gets its own database file so they can run in parallel
+    fn database_url() -> String {
+        let path = std::env::temp_dir().join(format!("players_api_test_{}.db", Uuid::new_v4()));
+
+        path.to_str().unwrap().to_string()
+    }
+
+    fn repositories() -> Repositories {
+        Repositories::sqlite(&database_url()).unwrap()
+    }
+
+    fn create_team(repositories: &Repositories) -> Team {
+        let (team, _) = repositories.teams.create(CreateTeamForm {
+            display_name: "Miami Dolphins".to_string(),
+            abbreviation: "MIA".to_string(),
+        }).unwrap();
+
+        team
+    }
+
+    fn player_form(first_name: &str, team_id: Option<Uuid>, rookie: bool) -> CreatePlayerForm {
+        CreatePlayerForm {
+            first_name: first_name.to_string(),
+            last_name: "Tagovailoa".to_string(),
+            team_id,
+            rookie,
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_create_team_then_get_team() {
+        let repositories = repositories();
+
+        let req = test::TestRequest::post().uri("/teams").set_json(
+            &CreateTeamForm {
+                display_name: "Miami Dolphins".to_string(),
+                abbreviation: "MIA".to_string(),
+            }
+        ).to_request();
+        let (status, created): (http::StatusCode, Team) = get_repositories_response(&repositories, req).await;
+        assert!(status.is_success());
+
+        let req = test::TestRequest::get().uri(format!("/teams/{}", created.id).as_str()).to_request();
+        let (status, team): (http::StatusCode, Team) = get_repositories_response(&repositories, req).await;
+        assert!(status.is_success());
+        assert_eq!(team, created);
+        assert_eq!(team.abbreviation, "MIA");
+    }
+
+    #[actix_rt::test]
+    async fn test_get_players_filters_rookies() {
+        let repositories = repositories();
+        let team = create_team(&repositories);
+        repositories.players.create(player_form("Ryan", Some(team.id), false)).unwrap();
+        let (rookie, _) = repositories.players.create(player_form("Tua", Some(team.id), true)).unwrap();
+
+        let req = test::TestRequest::get().uri("/players?rookie=true").to_request();
+        let (status, body): (http::StatusCode, serde_json::Value) = get_repositories_response(&repositories, req).await;
+
+        assert!(status.is_success());
+        assert_eq!(body.as_array().unwrap().len(), 1);
+        assert_eq!(body[0]["player"]["id"], rookie.id.to_string());
+        assert_eq!(body[0]["team"]["id"], team.id.to_string());
+    }
+
+    #[actix_rt::test]
+    async fn test_create_player_with_nonexistent_team_returns_400() {
+        let repositories = repositories();
+
+        let req = test::TestRequest::post().uri("/players")
+            .set_json(&player_form("Tua", Some(Uuid::new_v4()), true))
+            .to_request();
+        let (status, body): (http::StatusCode, serde_json::Value) = get_repositories_response(&repositories, req).await;
+
+        assert_eq!(status, http::StatusCode::BAD_REQUEST);
+        assert_eq!(body["detail"], "Team not found");
+        assert!(repositories.players.all().unwrap().is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn test_update_player_returns_404() {
+        let repositories = repositories();
+
+        let req = test::TestRequest::put().uri(format!("/players/{}", Uuid::new_v4()).as_str()).set_json(
+            &UpdatePlayerForm {
+                first_name: "Tua".to_string(),
+                last_name: "Tagovailoa".to_string(),
+                team_id: None,
+                rookie: Some(true),
+            }
+        ).to_request();
+        let (status, body): (http::StatusCode, serde_json::Value) = get_repositories_response(&repositories, req).await;
+
+        assert_eq!(status, http::StatusCode::NOT_FOUND);
+        assert_eq!(body["detail"], "Player not found");
+    }
+
+    #[actix_rt::test]
+    async fn test_update_player_without_rookie_keeps_rookie() {
+        let repositories = repositories();
+        let (player, _) = repositories.players.create(player_form("Tua", None, true)).unwrap();
+
+        let req = test::TestRequest::put().uri(format!("/players/{}", player.id).as_str()).set_json(
+            &UpdatePlayerForm {
+                first_name: "Tua".to_string(),
+                last_name: "Tagovailoa".to_string(),
+                team_id: None,
+                rookie: None,
+            }
+        ).to_request();
+        let (status, body): (http::StatusCode, serde_json::Value) = get_repositories_response(&repositories, req).await;
+
+        assert!(status.is_success());
+        assert_eq!(body["last_name"], "Tagovailoa");
+        assert_eq!(body["rookie"], true);
+        assert!(repositories.players.find(player.id).unwrap().rookie);
+    }
+
+    #[actix_rt::test]
+    async fn test_event_ids_keep_going_up_when_the_database_is_opened_again() {
+        let database_url = database_url();
+
+        let repositories = Repositories::sqlite(&database_url).unwrap();
+        let team = create_team(&repositories);
+        let (_, event) = repositories.players.create(player_form("Tua", Some(team.id), true)).unwrap();
+        let last_event_id = event.unwrap().id;
+        // A change that fails does not write an event
+        assert!(repositories.players.create(player_form("Tua", Some(Uuid::new_v4()), true)).is_err());
+        drop(repositories);
+
+        let repositories = Repositories::sqlite(&database_url).unwrap();
+        let (_, event) = repositories.players.create(player_form("Ryan", Some(team.id), false)).unwrap();
+        assert_eq!(event.unwrap().id, last_event_id + 1);
+    }
+
+    #[actix_rt::test]
+    async fn test_delete_team_with_players_returns_400() {
+        let repositories = repositories();
+        let team = create_team(&repositories);
+        repositories.players.create(player_form("Tua", Some(team.id), true)).unwrap();
+
+        let req = test::TestRequest::delete().uri(format!("/teams/{}", team.id).as_str()).to_request();
+        let (status, body): (http::StatusCode, serde_json::Value) = get_repositories_response(&repositories, req).await;
+
+        assert_eq!(status, http::StatusCode::BAD_REQUEST);
+        assert_eq!(body["detail"], "Cannot delete team: players still exist in the team");
+        assert!(repositories.teams.find(team.id).is_ok());
+    }
+}
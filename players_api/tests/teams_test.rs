@@ -10,9 +10,12 @@ mod teams_test {
     use uuid::Uuid;
 
     use players_api;
+    use players_api::players::models::Player;
+    use players_api::schema::players::table as players_table;
     use players_api::schema::teams::table as teams_table;
     use players_api::teams::models::{Team, CreateTeamForm, UpdateTeamForm};
-    use crate::common::{get_response, get_status};
+    use players_api::auth::SERVICE_TOKEN_HEADER;
+    use crate::common::{get_response, get_status, service_token};
     use crate::common::db_connection::get_pool;
 
     fn cardinals(id: &Uuid) -> Team {
@@ -149,4 +152,118 @@ mod teams_test {
         let team = teams_table.find(id).first::<Team>(&connection);
         assert_eq!(team, Err(DieselError::NotFound));
     }
+
+    #[actix_rt::test]
+    async fn test_create_team_returns_403_when_not_admin() {
+        let db_pool = get_pool();
+
+        let req = test::TestRequest::post().uri("/teams")
+            .header(SERVICE_TOKEN_HEADER, service_token("user"))
+            .set_json(&CreateTeamForm {
+                display_name: "Not Allowed".to_string(),
+                abbreviation: "NA".to_string()
+            })
+            .to_request();
+
+        let status = get_status(&db_pool, req).await;
+        assert_eq!(status, http::StatusCode::FORBIDDEN);
+    }
+
+    #[actix_rt::test]
+    async fn test_delete_team_returns_401_for_invalid_service_token() {
+        let db_pool = get_pool();
+
+        let req = test::TestRequest::delete().uri(format!("/teams/{}", Uuid::new_v4()).as_str())
+            .header(SERVICE_TOKEN_HEADER, "not a token")
+            .to_request();
+
+        let status = get_status(&db_pool, req).await;
+        assert_eq!(status, http::StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_rt::test]
+    async fn test_get_team_not_found_sends_error_code() {
+        let db_pool = get_pool();
+
+        let req = test::TestRequest::get().uri(format!("/teams/{}", Uuid::new_v4()).as_str()).to_request();
+        let (status, body): (http::StatusCode, serde_json::Value) = get_response(&db_pool, req).await;
+
+        assert_eq!(status, http::StatusCode::NOT_FOUND);
+        assert_eq!(body, serde_json::json!({
+            "type": "urn:players-api:problem:not_found",
+            "title": "Not Found",
+            "status": 404,
+            "detail": "Team not found",
+            "code": "not_found",
+        }));
+    }
+
+    #[actix_rt::test]
+    async fn test_create_team_with_invalid_body_sends_validation_error() {
+        let db_pool = get_pool();
+
+        let req = test::TestRequest::post().uri("/teams")
+            .header("Content-Type", "application/json")
+            .set_payload(r#"{ "display_name": "Arizona Cardinals" }"#)
+            .to_request();
+        let (status, body): (http::StatusCode, serde_json::Value) = get_response(&db_pool, req).await;
+
+        assert_eq!(status, http::StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "validation_failed");
+        assert_eq!(body["instance"], "/teams");
+    }
+
+    #[actix_rt::test]
+    async fn test_create_team_with_invalid_fields_returns_422() {
+        let db_pool = get_pool();
+
+        let req = test::TestRequest::post().uri("/teams").set_json(
+            &CreateTeamForm {
+                display_name: "".to_string(),
+                abbreviation: "arizona cardinals".to_string()
+            }
+        ).to_request();
+        let (status, body): (http::StatusCode, serde_json::Value) = get_response(&db_pool, req).await;
+
+        assert_eq!(status, http::StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["instance"], "/teams");
+        assert_eq!(body["errors"], serde_json::json!([
+            { "field": "display_name", "message": "display_name must be between 1 and 64 characters long" },
+            { "field": "abbreviation", "message": "abbreviation must be between 2 and 4 characters long" },
+            { "field": "abbreviation", "message": "abbreviation must be upper case letters" },
+        ]));
+    }
+
+    #[actix_rt::test]
+    async fn test_delete_team_with_players_returns_400() {
+        let db_pool = get_pool();
+        let connection = db_pool.get().unwrap();
+
+        let id = Uuid::new_v4();
+        diesel::insert_into(teams_table)
+            .values(cardinals(&id))
+            .execute(&connection).unwrap();
+        let player_id = Uuid::new_v4();
+        diesel::insert_into(players_table)
+            .values(Player {
+                id: player_id,
+                first_name: "Larry".to_string(),
+                last_name: "Fitzgerald".to_string(),
+                created_at: None,
+                updated_at: None,
+                team_id: Some(id),
+                rookie: false,
+            })
+            .execute(&connection).unwrap();
+
+        let req = test::TestRequest::delete().uri(format!("/teams/{}", id).as_str()).to_request();
+        let (status, body): (http::StatusCode, serde_json::Value) = get_response(&db_pool, req).await;
+
+        assert_eq!(status, http::StatusCode::BAD_REQUEST);
+        assert_eq!(body["detail"], "Cannot delete team: players still exist in the team");
+        assert!(teams_table.find(id).first::<Team>(&connection).is_ok());
+
+        diesel::delete(players_table.find(player_id)).execute(&connection).unwrap();
+        diesel::delete(teams_table.find(id)).execute(&connection).unwrap();
+    }
 }
@@ -0,0 +1,343 @@
+mod common;
+
+#[cfg(test)]
+mod webhooks_test {
+    use actix_web::http::StatusCode;
+    use actix_web::test;
+    use diesel::pg::PgConnection;
+    use diesel::prelude::*;
+    use std::time::{Duration, SystemTime};
+    use uuid::Uuid;
+
+    use players_api::events::{self, ChangeEvent};
+    use players_api::events::models::Event;
+    use players_api::players::models::{Player, UpdatePlayerForm};
+    use players_api::schema::players::table as players_table;
+    use players_api::schema::events::{event_type, table as events_table};
+    use players_api::schema::{webhook_dead_letters, webhook_deliveries, webhooks};
+    use players_api::webhooks::check_public_url;
+    use players_api::webhooks::delivery::{claim_due, record_attempt, retry_after, sign, LEASE, MAX_ATTEMPTS};
+    use players_api::webhooks::models::{CreateWebhookForm, DeliveryStatus, Webhook, WebhookDelivery};
+    use crate::common::{get_response, get_status};
+    use crate::common::db_connection::get_pool;
+
+    fn create_webhook(connection: &PgConnection, event_types: &[&str]) -> Webhook {
+        diesel::insert_into(webhooks::table)
+            .values(CreateWebhookForm {
+                url: "http://localhost:9/hook".to_string(),
+                event_types: event_types.iter().map(|name| name.to_string()).collect(),
+                secret: "shh".to_string(),
+            })
+            .get_result(connection).unwrap()
+    }
+
+    /// Other tests record the same event types, so a webhook can have
+    /// deliveries for their events too
+    fn get_delivery(connection: &PgConnection, webhook_id: Uuid, event_id: i64) -> WebhookDelivery {
+        webhook_deliveries::table
+            .filter(webhook_deliveries::webhook_id.eq(webhook_id))
+            .filter(webhook_deliveries::event_id.eq(event_id))
+            .first(connection).unwrap()
+    }
+
+    #[actix_rt::test]
+    async fn test_create_webhook_does_not_send_back_secret() {
+        let db_pool = get_pool();
+        let connection = db_pool.get().unwrap();
+
+        let req = test::TestRequest::post().uri("/webhooks").set_json(
+            &CreateWebhookForm {
+                url: "https://203.0.113.10/hook".to_string(),
+                event_types: vec!["PlayerUpdated".to_string()],
+                secret: "shh".to_string(),
+            }
+        ).to_request();
+        let (status, body): (StatusCode, serde_json::Value) = get_response(&db_pool, req).await;
+
+        assert!(status.is_success());
+        assert_eq!(body["url"], "https://203.0.113.10/hook");
+        assert!(body.get("secret").is_none());
+
+        let id: Uuid = serde_json::from_value(body["id"].clone()).unwrap();
+        let webhook = webhooks::table.find(id).first::<Webhook>(&connection).unwrap();
+        assert_eq!(webhook.secret, "shh");
+
+        diesel::delete(webhooks::table.find(id)).execute(&connection).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_create_webhook_rejects_unknown_event_type() {
+        let db_pool = get_pool();
+
+        let req = test::TestRequest::post().uri("/webhooks").set_json(
+            &CreateWebhookForm {
+                url: "https://203.0.113.10/hook".to_string(),
+                event_types: vec!["PlayerInjured".to_string()],
+                secret: "shh".to_string(),
+            }
+        ).to_request();
+        let (status, body): (StatusCode, serde_json::Value) = get_response(&db_pool, req).await;
+
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["errors"], serde_json::json!([
+            { "field": "event_types", "message": "Unknown event type PlayerInjured" },
+        ]));
+    }
+
+    #[actix_rt::test]
+    async fn test_create_webhook_rejects_non_http_url() {
+        let db_pool = get_pool();
+
+        let req = test::TestRequest::post().uri("/webhooks").set_json(
+            &CreateWebhookForm {
+                url: "ftp://example.com/hook".to_string(),
+                event_types: vec!["PlayerUpdated".to_string()],
+                secret: "shh".to_string(),
+            }
+        ).to_request();
+
+        assert_eq!(get_status(&db_pool, req).await, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[actix_rt::test]
+    async fn test_create_webhook_rejects_private_address() {
+        let db_pool = get_pool();
+
+        for url in &["http://localhost:8080/hook", "http://10.0.0.1/hook", "http://169.254.169.254/latest", "http://[::1]/hook"] {
+            let req = test::TestRequest::post().uri("/webhooks").set_json(
+                &CreateWebhookForm {
+                    url: url.to_string(),
+                    event_types: vec!["PlayerUpdated".to_string()],
+                    secret: "shh".to_string(),
+                }
+            ).to_request();
+
+            assert_eq!(get_status(&db_pool, req).await, StatusCode::UNPROCESSABLE_ENTITY, "{}", url);
+        }
+    }
+
+    #[test]
+    fn test_check_public_url() {
+        assert!(check_public_url("https://203.0.113.10/hook").is_ok());
+        assert!(check_public_url("https://[2001:db8::1]/hook").is_ok());
+
+        assert!(check_public_url("http://127.0.0.1/hook").is_err());
+        assert!(check_public_url("http://192.168.1.10/hook").is_err());
+        assert!(check_public_url("http://172.16.0.1/hook").is_err());
+        assert!(check_public_url("http://100.64.0.1/hook").is_err());
+        assert!(check_public_url("http://0.0.0.0/hook").is_err());
+        assert!(check_public_url("http://[fd00::1]/hook").is_err());
+        assert!(check_public_url("http://[fe80::1]/hook").is_err());
+        assert!(check_public_url("http://[::ffff:10.0.0.1]/hook").is_err());
+    }
+
+    #[actix_rt::test]
+    async fn test_update_player_queues_delivery_for_subscribed_webhook() {
+        let db_pool = get_pool();
+        let connection = db_pool.get().unwrap();
+        let subscribed = create_webhook(&connection, &["PlayerUpdated"]);
+        let not_subscribed = create_webhook(&connection, &["TeamDeleted"]);
+
+        let id = Uuid::new_v4();
+        diesel::insert_into(players_table)
+            .values(Player {
+                id,
+                first_name: "Michael".to_string(),
+                last_name: "Thomas".to_string(),
+                created_at: None,
+                updated_at: None,
+                team_id: None,
+                rookie: false,
+            })
+            .execute(&connection).unwrap();
+
+        let req = test::TestRequest::put().uri(format!("/players/{}", id).as_str()).set_json(
+            &UpdatePlayerForm {
+                first_name: "Mike".to_string(),
+                last_name: "Thomas".to_string(),
+                team_id: None,
+                rookie: Some(false),
+            }
+        ).to_request();
+        assert!(get_status(&db_pool, req).await.is_success());
+
+        let delivery = webhook_deliveries::table
+            .filter(webhook_deliveries::webhook_id.eq(subscribed.id))
+            .first::<WebhookDelivery>(&connection).unwrap();
+        assert_eq!(delivery.status, DeliveryStatus::Pending);
+        assert_eq!(delivery.attempts, 0);
+
+        let event = events_table.find(delivery.event_id).first::<Event>(&connection).unwrap();
+        assert_eq!(event.event_type, "PlayerUpdated");
+        assert_eq!(event.data["id"], id.to_string());
+
+        // Other tests can delete teams at the same time so only look for this update
+        let not_subscribed_deliveries = webhook_deliveries::table
+            .inner_join(events_table)
+            .filter(webhook_deliveries::webhook_id.eq(not_subscribed.id))
+            .filter(event_type.eq("PlayerUpdated"))
+            .count()
+            .get_result::<i64>(&connection).unwrap();
+        assert_eq!(not_subscribed_deliveries, 0);
+
+        diesel::delete(webhooks::table.filter(webhooks::id.eq_any(vec![subscribed.id, not_subscribed.id])))
+            .execute(&connection).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_record_attempt_retries_with_backoff() {
+        let db_pool = get_pool();
+        let connection = db_pool.get().unwrap();
+        let webhook = create_webhook(&connection, &["TeamDeleted"]);
+        let event = events::record(&connection, ChangeEvent::TeamDeleted { id: Uuid::new_v4() }).unwrap();
+        let delivery = get_delivery(&connection, webhook.id, event.id);
+
+        let now = SystemTime::now();
+        let delivery = record_attempt(&connection, &delivery, Ok(500), now).unwrap();
+        assert_eq!(delivery.status, DeliveryStatus::Pending);
+        assert_eq!(delivery.attempts, 1);
+        assert_eq!(delivery.response_status, Some(500));
+        assert!(delivery.next_attempt_at > now + retry_after(1) - Duration::from_secs(1));
+
+        let delivery = record_attempt(&connection, &delivery, Ok(204), now).unwrap();
+        assert_eq!(delivery.status, DeliveryStatus::Delivered);
+        assert_eq!(delivery.last_error, None);
+
+        diesel::delete(webhooks::table.find(webhook.id)).execute(&connection).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_record_attempt_dead_letters_after_last_attempt() {
+        let db_pool = get_pool();
+        let connection = db_pool.get().unwrap();
+        let webhook = create_webhook(&connection, &["PlayerDeleted"]);
+        let event = events::record(&connection, ChangeEvent::PlayerDeleted { id: Uuid::new_v4() }).unwrap();
+
+        let mut delivery = get_delivery(&connection, webhook.id, event.id);
+        for _ in 0..MAX_ATTEMPTS {
+            delivery = record_attempt(&connection, &delivery, Err("connection refused".to_string()), SystemTime::now()).unwrap();
+        }
+        assert_eq!(delivery.status, DeliveryStatus::Failed);
+
+        let dead_letter_error = webhook_dead_letters::table
+            .filter(webhook_dead_letters::delivery_id.eq(delivery.id))
+            .select(webhook_dead_letters::last_error)
+            .first::<String>(&connection).unwrap();
+        assert_eq!(dead_letter_error, "connection refused");
+
+        diesel::delete(webhooks::table.find(webhook.id)).execute(&connection).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_dead_letters_are_kept_when_their_event_is_deleted() {
+        let db_pool = get_pool();
+        let connection = db_pool.get().unwrap();
+        let webhook = create_webhook(&connection, &["PlayerDeleted"]);
+        let event = events::record(&connection, ChangeEvent::PlayerDeleted { id: Uuid::new_v4() }).unwrap();
+
+        let mut delivery = get_delivery(&connection, webhook.id, event.id);
+        for _ in 0..MAX_ATTEMPTS {
+            delivery = record_attempt(&connection, &delivery, Err("connection refused".to_string()), SystemTime::now()).unwrap();
+        }
+        let data = events_table.find(event.id).first::<Event>(&connection).unwrap().data;
+        // Webhooks of the other tests can have pending deliveries of the event too
+        diesel::delete(
+            webhook_deliveries::table
+                .filter(webhook_deliveries::event_id.eq(event.id))
+                .filter(webhook_deliveries::webhook_id.ne(webhook.id))
+        ).execute(&connection).unwrap();
+        diesel::update(events_table.find(event.id))
+            .set(players_api::schema::events::created_at.eq(SystemTime::now() - events::RETENTION * 2))
+            .execute(&connection).unwrap();
+
+        events::delete_before(&connection, SystemTime::now() - events::RETENTION).unwrap();
+
+        assert!(events_table.find(event.id).first::<Event>(&connection).is_err());
+        let (event_id, dead_letter_type, dead_letter_data) = webhook_dead_letters::table
+            .filter(webhook_dead_letters::webhook_id.eq(webhook.id))
+            .select((webhook_dead_letters::event_id, webhook_dead_letters::event_type, webhook_dead_letters::data))
+            .first::<(Option<i64>, String, serde_json::Value)>(&connection).unwrap();
+        assert_eq!(event_id, None);
+        assert_eq!(dead_letter_type, "PlayerDeleted");
+        assert_eq!(dead_letter_data, data);
+
+        diesel::delete(webhooks::table.find(webhook.id)).execute(&connection).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_claim_due_leases_deliveries() {
+        let db_pool = get_pool();
+        let connection = db_pool.get().unwrap();
+        let webhook = create_webhook(&connection, &["TeamDeleted"]);
+        let event = events::record(&connection, ChangeEvent::TeamDeleted { id: Uuid::new_v4() }).unwrap();
+        let delivery = get_delivery(&connection, webhook.id, event.id);
+
+        // Other tests queue deliveries too, so claim until this one is claimed
+        let now = SystemTime::now();
+        loop {
+            let claimed = claim_due(&connection, now).unwrap();
+            assert!(!claimed.is_empty());
+            if claimed.iter().any(|claimed| claimed.id == delivery.id) {
+                break;
+            }
+        }
+
+        let claimed = get_delivery(&connection, webhook.id, event.id);
+        assert_eq!(claimed.status, DeliveryStatus::Pending);
+        assert_eq!(claimed.attempts, 0);
+        assert!(claimed.next_attempt_at > now + LEASE - Duration::from_secs(1));
+
+        // It is not claimed again until the lease is up
+        let claimed = claim_due(&connection, now).unwrap();
+        assert!(claimed.iter().all(|claimed| claimed.id != delivery.id));
+
+        diesel::delete(webhooks::table.find(webhook.id)).execute(&connection).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_retry_after_doubles() {
+        assert_eq!(retry_after(1), Duration::from_secs(30));
+        assert_eq!(retry_after(2), Duration::from_secs(60));
+        assert_eq!(retry_after(4), Duration::from_secs(240));
+    }
+
+    #[actix_rt::test]
+    async fn test_sign_is_hmac_of_timestamp_and_body() {
+        let signature = sign("shh", 1582000000, "{}");
+
+        assert_eq!(signature, sign("shh", 1582000000, "{}"));
+        assert_ne!(signature, sign("shh", 1582000001, "{}"));
+        assert_ne!(signature, sign("other", 1582000000, "{}"));
+        assert_eq!(signature.len(), 64);
+    }
+
+    #[actix_rt::test]
+    async fn test_get_deliveries_filters_by_status() {
+        let db_pool = get_pool();
+        let connection = db_pool.get().unwrap();
+        let webhook = create_webhook(&connection, &["TeamDeleted"]);
+        let event = events::record(&connection, ChangeEvent::TeamDeleted { id: Uuid::new_v4() }).unwrap();
+        events::record(&connection, ChangeEvent::TeamDeleted { id: Uuid::new_v4() }).unwrap();
+
+        let delivery = get_delivery(&connection, webhook.id, event.id);
+        record_attempt(&connection, &delivery, Ok(200), SystemTime::now()).unwrap();
+
+        let req = test::TestRequest::get()
+            .uri(format!("/webhooks/{}/deliveries", webhook.id).as_str())
+            .to_request();
+        let (status, all): (StatusCode, Vec<serde_json::Value>) = get_response(&db_pool, req).await;
+        assert!(status.is_success());
+        // Other tests can delete teams at the same time so there can be more
+        assert!(all.len() >= 2);
+
+        let req = test::TestRequest::get()
+            .uri(format!("/webhooks/{}/deliveries?status=delivered", webhook.id).as_str())
+            .to_request();
+        let (_, delivered): (StatusCode, Vec<serde_json::Value>) = get_response(&db_pool, req).await;
+        assert_eq!(delivered.len(), 1);
+        assert_eq!(delivered[0]["id"], delivery.id);
+        assert_eq!(delivered[0]["response_status"], 200);
+
+        diesel::delete(webhooks::table.find(webhook.id)).execute(&connection).unwrap();
+    }
+}